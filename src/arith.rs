@@ -0,0 +1,429 @@
+//! `--expr` conditions: arithmetic on a row's columns compared with another
+//! value, such as `col("price") * col("qty") > 1000`, parsed once and
+//! evaluated against every row.
+//!
+//! Grammar:
+//!
+//! ```text
+//! comparison := sum op sum
+//! sum        := product (("+" | "-") product)*
+//! product    := unary (("*" | "/") unary)*
+//! unary      := "-" unary | number | column | "(" sum ")"
+//! column     := "col" "(" ("'name'" | "\"name\"") ")"
+//! op         := "=" | "==" | "!=" | "<>" | "<" | "<=" | ">" | ">="
+//! ```
+//!
+//! Every cell is read as a number. A null cell, a missing column, a cell
+//! that isn't a number or a division by zero leaves the value unknown, and
+//! the row out.
+
+use crate::filter::Op;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// An arithmetic expression over columns named by `C`: a `String` as
+/// written, and after [`Comparison::bind`] an index into one file's headers
+/// (`None` when the file lacks the column).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term<C> {
+    Number(f64),
+    Column(C),
+    Neg(Box<Term<C>>),
+    Binary(Box<Term<C>>, ArithOp, Box<Term<C>>),
+}
+
+/// Two arithmetic expressions compared, the whole of an `--expr`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison<C> {
+    pub left: Term<C>,
+    pub op: Op,
+    pub right: Term<C>,
+}
+
+pub type ColumnExpr = Comparison<String>;
+
+impl ColumnExpr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            source: s,
+        };
+        let left = parser.sum()?;
+        let op = match parser.next("a comparison")? {
+            Token::Op(op) => op,
+            token => {
+                return Err(format!("expected a comparison, got '{}' in '{}'", token, s));
+            }
+        };
+        let right = parser.sum()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Self { left, op, right }),
+            Some(token) => Err(format!("unexpected '{}' in '{}'", token, s)),
+        }
+    }
+
+    /// Every column the expression refers to, in order of appearance.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        for term in [&self.left, &self.right] {
+            term.visit_columns(&mut |column: &String| columns.push(column.as_str()));
+        }
+        columns
+    }
+
+    /// Resolve column names against one file's headers.
+    pub fn bind(
+        &self,
+        resolve: &mut impl FnMut(&str) -> Option<usize>,
+    ) -> Comparison<Option<usize>> {
+        Comparison {
+            left: self
+                .left
+                .map_columns(&mut |column: &String| resolve(column)),
+            op: self.op,
+            right: self
+                .right
+                .map_columns(&mut |column: &String| resolve(column)),
+        }
+    }
+}
+
+impl<C> Term<C> {
+    fn visit_columns<'a>(&'a self, f: &mut impl FnMut(&'a C)) {
+        match self {
+            Term::Number(_) => {}
+            Term::Column(c) => f(c),
+            Term::Neg(inner) => inner.visit_columns(f),
+            Term::Binary(a, _, b) => {
+                a.visit_columns(f);
+                b.visit_columns(f);
+            }
+        }
+    }
+
+    fn map_columns<D>(&self, f: &mut impl FnMut(&C) -> D) -> Term<D> {
+        match self {
+            Term::Number(n) => Term::Number(*n),
+            Term::Column(c) => Term::Column(f(c)),
+            Term::Neg(inner) => Term::Neg(Box::new(inner.map_columns(f))),
+            Term::Binary(a, op, b) => {
+                Term::Binary(Box::new(a.map_columns(f)), *op, Box::new(b.map_columns(f)))
+            }
+        }
+    }
+}
+
+impl Comparison<Option<usize>> {
+    /// Whether the comparison holds for one row; never when either side is
+    /// unknown.
+    pub fn eval(&self, row: &[String], is_null: &dyn Fn(usize) -> bool) -> bool {
+        match (self.left.eval(row, is_null), self.right.eval(row, is_null)) {
+            (Some(left), Some(right)) => self.op.holds(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl Term<Option<usize>> {
+    /// The value for one row, or `None` when it's unknown.
+    fn eval(&self, row: &[String], is_null: &dyn Fn(usize) -> bool) -> Option<f64> {
+        match self {
+            Term::Number(n) => Some(*n),
+            Term::Column(idx) => {
+                let idx = (*idx)?;
+                if is_null(idx) {
+                    return None;
+                }
+                // Not 'inf' or 'NaN', which are text here
+                row.get(idx)?
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|v: &f64| v.is_finite())
+            }
+            Term::Neg(inner) => inner.eval(row, is_null).map(|n| -n),
+            Term::Binary(a, op, b) => {
+                let (a, b) = (a.eval(row, is_null)?, b.eval(row, is_null)?);
+                let n = match op {
+                    ArithOp::Add => a + b,
+                    ArithOp::Sub => a - b,
+                    ArithOp::Mul => a * b,
+                    ArithOp::Div => a / b,
+                };
+                // Division by zero, or an overflow
+                n.is_finite().then_some(n)
+            }
+        }
+    }
+}
+
+// ── tokenizer ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Word(String),
+    Op(Op),
+    Arith(ArithOp),
+    LParen,
+    RParen,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+            Token::Word(w) => write!(f, "{}", w),
+            Token::Op(op) => write!(f, "{}", op),
+            Token::Arith(ArithOp::Add) => write!(f, "+"),
+            Token::Arith(ArithOp::Sub) => write!(f, "-"),
+            Token::Arith(ArithOp::Mul) => write!(f, "*"),
+            Token::Arith(ArithOp::Div) => write!(f, "/"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if matches!(c, '\'' | '"') {
+            let start = i + 1;
+            let end = chars[start..]
+                .iter()
+                .position(|&q| q == c)
+                .map(|len| start + len)
+                .ok_or_else(|| format!("unterminated {} in '{}'", c, s))?;
+            tokens.push(Token::Str(chars[start..end].iter().collect()));
+            i = end + 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric()
+                    || chars[i] == '.'
+                    || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number in '{}'", text, s))?;
+            tokens.push(Token::Number(n));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let (token, len) = match (two.as_str(), c) {
+                ("<=", _) => (Token::Op(Op::Le), 2),
+                (">=", _) => (Token::Op(Op::Ge), 2),
+                ("==", _) => (Token::Op(Op::Eq), 2),
+                ("!=" | "<>", _) => (Token::Op(Op::Ne), 2),
+                (_, '<') => (Token::Op(Op::Lt), 1),
+                (_, '>') => (Token::Op(Op::Gt), 1),
+                (_, '=') => (Token::Op(Op::Eq), 1),
+                (_, '+') => (Token::Arith(ArithOp::Add), 1),
+                (_, '-') => (Token::Arith(ArithOp::Sub), 1),
+                (_, '*') => (Token::Arith(ArithOp::Mul), 1),
+                (_, '/') => (Token::Arith(ArithOp::Div), 1),
+                (_, '(') => (Token::LParen, 1),
+                (_, ')') => (Token::RParen, 1),
+                _ => return Err(format!("unexpected '{}' in '{}'", c, s)),
+            };
+            tokens.push(token);
+            i += len;
+        }
+    }
+    Ok(tokens)
+}
+
+// ── parser ───────────────────────────────────────────────────────────────────
+
+struct Parser<'s> {
+    tokens: Vec<Token>,
+    pos: usize,
+    source: &'s str,
+}
+
+impl Parser<'_> {
+    fn next(&mut self, expected: &str) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| format!("expected {} at end of '{}'", expected, self.source))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        let found = self.next(&format!("'{}'", token))?;
+        if found == token {
+            Ok(())
+        } else {
+            Err(format!(
+                "expected '{}', got '{}' in '{}'",
+                token, found, self.source
+            ))
+        }
+    }
+
+    /// The next token, if it's one of `ops`.
+    fn eat_arith(&mut self, ops: &[ArithOp]) -> Option<ArithOp> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Arith(op)) if ops.contains(op) => {
+                self.pos += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Term<String>, String> {
+        let mut term = self.product()?;
+        while let Some(op) = self.eat_arith(&[ArithOp::Add, ArithOp::Sub]) {
+            term = Term::Binary(Box::new(term), op, Box::new(self.product()?));
+        }
+        Ok(term)
+    }
+
+    fn product(&mut self) -> Result<Term<String>, String> {
+        let mut term = self.unary()?;
+        while let Some(op) = self.eat_arith(&[ArithOp::Mul, ArithOp::Div]) {
+            term = Term::Binary(Box::new(term), op, Box::new(self.unary()?));
+        }
+        Ok(term)
+    }
+
+    fn unary(&mut self) -> Result<Term<String>, String> {
+        match self.next("a number, column or '('")? {
+            Token::Arith(ArithOp::Sub) => Ok(Term::Neg(Box::new(self.unary()?))),
+            Token::Number(n) => Ok(Term::Number(n)),
+            Token::LParen => {
+                let term = self.sum()?;
+                self.expect(Token::RParen)?;
+                Ok(term)
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("col") => {
+                self.expect(Token::LParen)?;
+                let column = match self.next("a quoted column name")? {
+                    Token::Str(column) => column,
+                    token => {
+                        return Err(format!(
+                            "expected a quoted column name, got '{}' in '{}'",
+                            token, self.source
+                        ));
+                    }
+                };
+                self.expect(Token::RParen)?;
+                Ok(Term::Column(column))
+            }
+            Token::Word(word) => Err(format!(
+                "unknown name '{}' in '{}'; write columns as col(\"{}\")",
+                word, self.source, word
+            )),
+            token => Err(format!(
+                "expected a number, column or '(', got '{}' in '{}'",
+                token, self.source
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expr_eval() {
+        let headers = ["price", "qty", "discount"];
+        let rows = [
+            ["100", "20", "0.1"],
+            ["9.5", "4", ""],
+            ["abc", "1000", "0"],
+            ["2000", "1", "1"],
+            ["inf", "NaN", "0"],
+        ];
+        // Each expression and the (1-based) rows it keeps
+        let cases: &[(&str, &[usize])] = &[
+            ("col(\"price\") * col(\"qty\") > 1000", &[1, 4]),
+            ("col('price') * col('qty') <= 38", &[2]),
+            ("col(\"price\") * (1 - col(\"discount\")) >= 90", &[1]),
+            ("col(\"price\") + col(\"qty\") * 2 == 140", &[1]),
+            ("-col(\"qty\") < -10", &[1, 3]),
+            ("col(\"qty\") / col(\"discount\") >= 1", &[1, 4]),
+            ("100 / (col(\"price\") - 100) != 0", &[2, 4]),
+            ("1e3 = col(\"qty\")", &[3]),
+            // Non-finite text isn't a number
+            ("col(\"price\") > 100", &[4]),
+            ("col(\"qty\") != 5", &[1, 2, 3, 4]),
+            // A missing column is unknown
+            ("col(\"missing\") + 1 > 0", &[]),
+        ];
+
+        for (expr, expected) in cases {
+            let parsed = ColumnExpr::parse(expr).unwrap_or_else(|e| panic!("{}: {}", expr, e));
+            let bound = parsed.bind(&mut |name| headers.iter().position(|h| *h == name));
+            let kept: Vec<usize> = rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| {
+                    let row: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+                    bound.eval(&row, &|i| row[i].is_empty())
+                })
+                .map(|(i, _)| i + 1)
+                .collect();
+            assert_eq!(&kept, expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_expr_parse_errors() {
+        assert_eq!(
+            ColumnExpr::parse("col('a') * (col('b') + 1) > col('c')")
+                .unwrap()
+                .columns(),
+            ["a", "b", "c"]
+        );
+        for bad in [
+            "",
+            "col('a')",
+            "col('a') >",
+            "price > 5",
+            "col(a) > 5",
+            "col('a' > 5",
+            "(col('a') > 5)",
+            "col('a') > 5 > 3",
+            "col('a') # 5",
+            "col('a) > 5",
+            "1.2.3 > 1",
+        ] {
+            assert!(
+                ColumnExpr::parse(bad).is_err(),
+                "{:?} should not parse",
+                bad
+            );
+        }
+    }
+}
@@ -1,6 +1,39 @@
+use crate::arith::ColumnExpr;
+use crate::filter::{ColumnMatch, ColumnSet, Filter};
+use crate::grep::ValueFormat;
+use crate::output::{ColorSpec, ColumnColors, Palette};
+use crate::query::Query;
+use crate::sort::SortKey;
 use anyhow::Result;
-use clap::{Parser, ValueEnum};
+use arrow::array::timezone::Tz;
+use clap::{ArgGroup, Parser, ValueEnum};
+use encoding_rs::Encoding;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use regex::{Regex, RegexBuilder};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// Format of the `--output-file`
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFileFormat {
+    Csv,
+    Tsv,
+    Psv,
+    Parquet,
+}
+
+impl OutputFileFormat {
+    /// The format a file name's extension implies.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "tsv" | "tab" => Some(Self::Tsv),
+            "psv" => Some(Self::Psv),
+            "parquet" | "pq" | "parq" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+}
 
 /// Output format for matching rows
 #[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
@@ -9,6 +42,202 @@ pub enum OutputFormat {
     Csv,
     /// Pretty-printed table with borders
     Table,
+    /// One JSON object per matching row (JSON Lines)
+    Json,
+    /// GitHub-flavored markdown table
+    Markdown,
+    /// One `column: value` line per cell, a block per row (for wide tables)
+    Vertical,
+}
+
+/// Text representation of binary cells
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum BinaryEncoding {
+    /// Lowercase hexadecimal
+    #[default]
+    Hex,
+    /// Standard base64, with padding
+    Base64,
+}
+
+/// When to color output
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal
+    #[default]
+    Auto,
+    /// Even when piped, e.g. into `less -R`
+    Always,
+    /// Never
+    Never,
+}
+
+/// Order of the --group-by histogram
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum GroupSort {
+    /// Most frequent values first
+    #[default]
+    Count,
+    /// Values in lexical order
+    Value,
+}
+
+/// How to treat subdirectories when PATH is a directory (grep's `-d`)
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum Directories {
+    /// Search the whole directory tree
+    #[default]
+    Recurse,
+    /// Only search files directly inside the directory
+    Nonrecursive,
+}
+
+/// Order in which a directory's files are searched (`--sort-files`)
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum FileOrder {
+    /// By path
+    #[default]
+    Name,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    Mtime,
+}
+
+/// Input format of the searched data
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InputFormat {
+    /// Comma-separated values
+    Csv,
+    /// Tab-separated values
+    Tsv,
+    /// Pipe-separated values
+    Psv,
+    /// Apache Parquet
+    Parquet,
+    /// Newline-delimited JSON objects
+    Jsonl,
+    /// Arrow IPC (Feather v2), file or streaming format
+    Arrow,
+    /// Excel workbook (.xlsx, .xlsm, .xlsb or .xls)
+    Xlsx,
+}
+
+/// File extensions of each input format. `.txt` and `.dat` files are read
+/// as CSV with a sniffed delimiter.
+const FORMAT_EXTENSIONS: &[(&str, InputFormat)] = &[
+    ("csv", InputFormat::Csv),
+    ("txt", InputFormat::Csv),
+    ("dat", InputFormat::Csv),
+    ("tsv", InputFormat::Tsv),
+    ("tab", InputFormat::Tsv),
+    ("psv", InputFormat::Psv),
+    ("parquet", InputFormat::Parquet),
+    ("pq", InputFormat::Parquet),
+    ("parq", InputFormat::Parquet),
+    ("jsonl", InputFormat::Jsonl),
+    ("ndjson", InputFormat::Jsonl),
+    ("arrow", InputFormat::Arrow),
+    ("feather", InputFormat::Arrow),
+    ("arrows", InputFormat::Arrow),
+    ("xlsx", InputFormat::Xlsx),
+    ("xlsm", InputFormat::Xlsx),
+    ("xlsb", InputFormat::Xlsx),
+    ("xls", InputFormat::Xlsx),
+];
+
+/// File extensions of each compression codec.
+const COMPRESSION_EXTENSIONS: &[(&str, Compression)] = &[
+    ("gz", Compression::Gzip),
+    ("zst", Compression::Zstd),
+    ("zstd", Compression::Zstd),
+    ("bz2", Compression::Bzip2),
+    ("xz", Compression::Xz),
+];
+
+/// Look up `path`'s extension in `table`.
+fn by_extension<T: Copy>(path: &Path, table: &[(&str, T)]) -> Option<T> {
+    let extension = path.extension()?.to_str()?;
+    table
+        .iter()
+        .find(|(name, _)| *name == extension)
+        .map(|&(_, value)| value)
+}
+
+impl InputFormat {
+    /// Detect the input format from a file's extension. Compressed delimited
+    /// text (`.csv.gz`, `.tsv.zst`, ...) is detected from the inner extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        if Compression::from_path(path).is_some() {
+            return match Self::from_path(Path::new(path.file_stem()?)) {
+                Some(format @ (Self::Csv | Self::Tsv | Self::Psv)) => Some(format),
+                _ => None,
+            };
+        }
+        by_extension(path, FORMAT_EXTENSIONS)
+    }
+
+    /// Every extension `from_path` recognizes, for messages about files
+    /// that were skipped.
+    pub fn supported_extensions() -> String {
+        let plain: Vec<String> = FORMAT_EXTENSIONS
+            .iter()
+            .map(|(name, _)| format!(".{}", name))
+            .collect();
+        let compressed: Vec<String> = COMPRESSION_EXTENSIONS
+            .iter()
+            .map(|(name, _)| format!(".{}", name))
+            .collect();
+        format!(
+            "{}, and delimited text compressed with {}",
+            plain.join(", "),
+            compressed.join(", ")
+        )
+    }
+}
+
+/// A compression codec that delimited text can be wrapped in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// The codec a file's extension names: `.gz`, `.zst`, `.bz2` or `.xz`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        by_extension(path, COMPRESSION_EXTENSIONS)
+    }
+
+    /// The codec whose magic bytes `data` starts with, for compressed files
+    /// whose name doesn't say so.
+    pub fn from_magic(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else if is_bzip2(data) {
+            Some(Self::Bzip2)
+        } else if data.starts_with(b"\xfd7zXZ\0") {
+            Some(Self::Xz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `data` starts a bzip2 stream: `BZh`, the block size digit, then
+/// the magic of the first block (or of the end of an empty stream). The
+/// three letters alone could well begin a CSV header.
+fn is_bzip2(data: &[u8]) -> bool {
+    const BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+    const END_MAGIC: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+    data.len() >= 10
+        && data.starts_with(b"BZh")
+        && (b'1'..=b'9').contains(&data[3])
+        && (data[4..10] == BLOCK_MAGIC || data[4..10] == END_MAGIC)
 }
 
 /// table-grep: grep through CSV and Parquet table files
@@ -18,35 +247,109 @@ pub enum OutputFormat {
     version,
     about = "Search for patterns in CSV and Parquet table files",
     long_about = "table-grep lets you search for patterns across rows in CSV and Parquet files,\n\
-                  either in a single file or recursively across an entire directory."
+                  either in a single file or recursively across an entire directory.",
+    override_usage = "table-grep [OPTIONS] <PATTERN> <PATH>\n       \
+                      table-grep [OPTIONS] -e <PATTERN>... <PATH>",
+    // Later options win, so the command line overrides config defaults
+    args_override_self = true,
+    group(ArgGroup::new("counting").args(["count", "count_distinct"]).multiple(true)),
+    group(ArgGroup::new("summarizing").args(["count_distinct", "group_by"]))
 )]
 pub struct Cli {
-    /// Pattern to search for (supports regex)
-    pub pattern: String,
+    /// Pattern to search for (supports regex); omit it when -e, --file,
+    /// --query or --expr give the pattern
+    #[arg(value_name = "PATTERN")]
+    first_positional: String,
 
-    /// File or directory to search
-    pub path: String,
+    /// File or directory to search ('-' reads from stdin)
+    #[arg(
+        value_name = "PATH",
+        required_unless_present_any = [
+            "regexp", "pattern_file", "query", "exprs", "in_file", "not_in_file",
+            "list_columns", "schema", "dry_run", "check_schema",
+        ]
+    )]
+    second_positional: Option<String>,
 
-    /// Search only in specific columns (comma-separated column names)
-    #[arg(long, value_delimiter = ',', value_name = "COLUMNS")]
+    /// Search only in specific columns: comma-separated names, zero-based
+    /// indices, or ranges (1-4, 3-); prefix with ! or - to exclude columns
+    #[arg(
+        short = 'k',
+        long,
+        value_delimiter = ',',
+        value_name = "COLUMNS",
+        allow_hyphen_values = true
+    )]
     pub columns: Option<Vec<String>>,
 
+    /// Pattern to search for; repeat to match rows containing any of them
+    #[arg(short = 'e', long, value_name = "PATTERN", allow_hyphen_values = true)]
+    pub regexp: Vec<String>,
+
+    /// Read patterns from a file, one per line (blank lines are skipped)
+    #[arg(long = "file", value_name = "PATH")]
+    pub pattern_file: Option<String>,
+
+    /// Also search every column whose header name matches this regex
+    #[arg(long, value_name = "REGEX")]
+    pub column_regex: Option<String>,
+
+    /// Search all columns in files where --column-regex matches no header
+    #[arg(long, requires = "column_regex")]
+    pub column_regex_fallback: bool,
+
     /// Case-insensitive matching
     #[arg(short = 'i', long)]
     pub ignore_case: bool,
 
+    /// Match column names given to --columns, --filter, --sort etc. ignoring
+    /// case and surrounding whitespace (an exact match still wins)
+    #[arg(long)]
+    pub ignore_case_columns: bool,
+
     /// Invert match: show rows that do NOT match
     #[arg(short = 'v', long)]
     pub invert: bool,
 
-    /// Show only matching column values (not full rows)
+    /// Replace every match in the searched cells of matching rows with TEXT
+    /// ($1, ${name} refer to capture groups); applies to --output-file too
+    #[arg(
+        short = 'r',
+        long,
+        value_name = "TEXT",
+        conflicts_with = "only_matching"
+    )]
+    pub replace: Option<String>,
+
+    /// Show only matching column values (not full rows); with -v, every
+    /// searched value of the rows that don't match
     #[arg(short = 'o', long)]
     pub only_matching: bool,
 
+    /// Show only the searched column values of matching rows that don't
+    /// themselves match, e.g. the odd cell out in a row
+    #[arg(long, conflicts_with_all = ["only_matching", "invert", "replace"])]
+    pub only_non_matching: bool,
+
     /// Suppress filename headers in output
     #[arg(long = "no-filename")]
     pub no_filename: bool,
 
+    /// In CSV output, print each file's name as a heading above its matches
+    /// (the default)
+    #[arg(long, overrides_with = "no_heading")]
+    pub heading: bool,
+
+    /// In CSV output, prefix every row with its file name ('path:3: ...')
+    /// instead of printing it as a heading, so each line stands alone
+    #[arg(long, overrides_with = "heading")]
+    pub no_heading: bool,
+
+    /// Follow each printed file name with a NUL byte instead of its usual
+    /// newline or ':' (for `xargs -0`)
+    #[arg(short = 'Z', long)]
+    pub null: bool,
+
     /// Print column headers in output
     #[arg(short = 'H', long = "with-headers", default_value_t = true, action = clap::ArgAction::Set)]
     pub with_headers: bool,
@@ -55,19 +358,595 @@ pub struct Cli {
     #[arg(short = 'c', long)]
     pub count: bool,
 
+    /// With -c, also print a count of 0 for files without matches, as grep
+    /// -c does
+    #[arg(long, alias = "count-zero", requires = "count")]
+    pub include_zero: bool,
+
+    /// With -c, also print the total count across all files (with
+    /// --count-distinct, the number of distinct values across all files)
+    #[arg(long, requires = "counting")]
+    pub total: bool,
+
+    /// With -c or --count-distinct, print only the total across all files
+    #[arg(long, requires = "counting")]
+    pub count_only_total: bool,
+
+    /// Print how many distinct values COLUMN takes among each file's matching
+    /// rows instead of the rows
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        conflicts_with_all = ["count", "only_matching", "only_non_matching", "files_with_matches", "files_without_matches"]
+    )]
+    pub count_distinct: Option<String>,
+
+    /// With --count-distinct, print the distinct values themselves, sorted
+    #[arg(long, requires = "count_distinct")]
+    pub list_distinct: bool,
+
+    /// With --count-distinct or --group-by, leave out NULL and empty values
+    #[arg(long, requires = "summarizing")]
+    pub skip_null: bool,
+
+    /// Print how many of each file's matching rows have each value of COLUMN
+    /// (a `value: count` histogram) instead of the rows
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        conflicts_with_all = ["count", "only_matching", "only_non_matching", "files_with_matches", "files_without_matches"]
+    )]
+    pub group_by: Option<String>,
+
+    /// Print how many cells of each file's matching rows matched in each
+    /// searched column (a `column: count` tally, most first) instead of the
+    /// rows
+    #[arg(
+        long,
+        conflicts_with_all = ["count", "only_matching", "only_non_matching", "files_with_matches", "files_without_matches", "summarizing"]
+    )]
+    pub count_by_column: bool,
+
+    /// Order of the --group-by histogram: count (most frequent first) or value
+    #[arg(long, value_enum, value_name = "ORDER", default_value_t = GroupSort::Count, requires = "group_by")]
+    pub group_sort: GroupSort,
+
+    /// With --group-by, show only the N most frequent values
+    #[arg(long, value_name = "N", requires = "group_by")]
+    pub top: Option<usize>,
+
+    /// Only print the names of files containing a match
+    #[arg(short = 'l', long, conflicts_with_all = ["count", "only_matching", "only_non_matching", "output_file"])]
+    pub files_with_matches: bool,
+
+    /// Only print the names of files without any match
+    #[arg(
+        short = 'L',
+        long,
+        visible_alias = "files-without-match",
+        conflicts_with_all = ["files_with_matches", "count", "only_matching", "only_non_matching", "output_file"]
+    )]
+    pub files_without_matches: bool,
+
+    /// Print nothing; only report through the exit status whether anything
+    /// matched (0 = match, 1 = no match, 2 = error)
+    #[arg(
+        short = 'q',
+        long,
+        visible_alias = "silent",
+        conflicts_with_all = ["output_file"]
+    )]
+    pub quiet: bool,
+
     /// Treat pattern as a literal string (not regex)
     #[arg(short = 'F', long)]
     pub fixed_strings: bool,
 
+    /// Only match whole words
+    #[arg(short = 'w', long)]
+    pub word_regexp: bool,
+
+    /// Only match when the pattern matches an entire cell
+    #[arg(short = 'x', long)]
+    pub line_regexp: bool,
+
+    /// Only match at the start of a cell, e.g. '-F --starts-with ORD.' for a
+    /// literal prefix; with --ends-with, like -x
+    #[arg(long)]
+    pub starts_with: bool,
+
+    /// Only match at the end of a cell
+    #[arg(long)]
+    pub ends_with: bool,
+
+    /// Match against the whole row, its searched cells joined with the
+    /// delimiter (e.g. 'Alice,30'), rather than cell by cell; -x and anchors
+    /// then apply to the joined row. CSV output highlights matches in the
+    /// joined row; other formats still highlight cell by cell, so a match
+    /// across cells isn't highlighted there
+    #[arg(
+        long,
+        conflicts_with_all = ["only_matching", "only_non_matching", "count_by_column", "replace", "in_place", "replace_output_dir"]
+    )]
+    pub whole_row: bool,
+
+    /// Drop rows that also match this pattern, e.g. 'ERROR' but not
+    /// 'timeout' (the regex engine has no lookaround); repeat to exclude
+    /// several
+    #[arg(long = "exclude-pattern", value_name = "PATTERN")]
+    pub exclude_patterns: Vec<String>,
+
+    /// Columns --exclude-pattern is tested against, like --columns
+    /// (default: the searched columns)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "COLUMNS",
+        allow_hyphen_values = true,
+        requires = "exclude_patterns"
+    )]
+    pub exclude_columns: Option<Vec<String>>,
+
+    /// Only keep rows where a numeric comparison holds, e.g. 'age>65' or
+    /// 'price<=9.99'; repeat to require several
+    #[arg(long = "filter", visible_alias = "where", value_name = "EXPR", value_parser = Filter::parse)]
+    pub filters: Vec<Filter>,
+
+    /// Require a regex to match in one column, e.g. 'region=^EU'; repeat to
+    /// require several. Rows lacking the column never qualify
+    #[arg(long = "match", value_name = "COLUMN=REGEX")]
+    pub column_matches: Vec<String>,
+
+    /// Only keep rows whose COLUMN value is exactly one of the lines of PATH,
+    /// e.g. 'id=bad_ids.txt'; repeat to require several. Rows lacking the
+    /// column never qualify
+    #[arg(long, value_name = "COLUMN=PATH")]
+    pub in_file: Vec<String>,
+
+    /// Only keep rows whose COLUMN value is none of the lines of PATH;
+    /// repeatable. Rows lacking the column never qualify
+    #[arg(long, value_name = "COLUMN=PATH")]
+    pub not_in_file: Vec<String>,
+
+    /// Only keep rows where a SQL-style condition holds, e.g. "status =
+    /// 'active' AND (amount > 100 OR region LIKE 'EU%')"; PATTERN may then be
+    /// omitted
+    #[arg(long, value_name = "EXPR", value_parser = Query::parse, allow_hyphen_values = true)]
+    pub query: Option<Query>,
+
+    /// Only keep rows where arithmetic on columns compares true, e.g.
+    /// 'col("price") * col("qty") > 1000'; repeat to require several. Rows
+    /// with a null or non-numeric operand never qualify; PATTERN may then be
+    /// omitted
+    #[arg(long = "expr", value_name = "EXPR", value_parser = ColumnExpr::parse, allow_hyphen_values = true)]
+    pub exprs: Vec<ColumnExpr>,
+
+    /// Only keep rows where COLUMN is null (an empty CSV/TSV field, a missing
+    /// or null JSON value, or a real Parquet/Arrow null); repeatable
+    #[arg(long, value_name = "COLUMN")]
+    pub is_null: Vec<String>,
+
+    /// Only keep rows where COLUMN is not null; repeatable
+    #[arg(long, value_name = "COLUMN")]
+    pub not_null: Vec<String>,
+
     /// Limit output to N matching rows per file
     #[arg(short = 'm', long, value_name = "N")]
     pub max_count: Option<usize>,
 
-    /// Disable color output
+    /// Stop the whole search after N matching rows in all, across files
+    #[arg(long, value_name = "N")]
+    pub max_total: Option<usize>,
+
+    /// Print a random sample of up to N matching rows per file, drawn from
+    /// the whole file (which is always read to the end), in file order
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["max_count", "max_total", "output_file", "count", "only_matching", "only_non_matching", "context", "after_context", "before_context"]
+    )]
+    pub sample: Option<usize>,
+
+    /// Seed for --sample, so the same search draws the same sample
+    #[arg(long, value_name = "N", requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Stop searching a file after SECS seconds, reporting it as failed
+    /// (the rest of a directory is still searched)
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Most memory, in MiB, a compiled pattern may take (default: 10);
+    /// patterns too large for it are refused rather than compiled
+    #[arg(long, value_name = "MIB", value_parser = clap::value_parser!(u32).range(1..))]
+    pub regex_size_limit: Option<u32>,
+
+    /// Only search the first N rows of each file
+    #[arg(long, value_name = "N")]
+    pub head: Option<usize>,
+
+    /// Only search the last N rows of each file; with --head, the last N of
+    /// the rows it keeps (like 'head | tail')
+    #[arg(long, value_name = "N")]
+    pub tail: Option<usize>,
+
+    /// Print N non-matching rows after each matching row
+    #[arg(short = 'A', long, value_name = "N")]
+    pub after_context: Option<usize>,
+
+    /// Print N non-matching rows before each matching row
+    #[arg(short = 'B', long, value_name = "N")]
+    pub before_context: Option<usize>,
+
+    /// Print N rows of context before and after each matching row
+    #[arg(short = 'C', long, value_name = "N")]
+    pub context: Option<usize>,
+
+    /// Also search files and directories excluded by .gitignore, .ignore and
+    /// similar files
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Also search hidden files and directories (names starting with '.')
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Descend at most N directory levels below PATH (1 = only its own files)
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Only search files directly inside PATH, not its subdirectories
+    #[arg(long)]
+    pub no_recursive: bool,
+
+    /// How to search directories: recurse (default) or nonrecursive
+    #[arg(short = 'd', long, value_enum, value_name = "ACTION", default_value_t = Directories::Recurse)]
+    pub directories: Directories,
+
+    /// Follow symbolic links when walking a directory (loops are detected and
+    /// skipped with a warning)
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Order to search a directory's files in: name (by path, the default),
+    /// size (largest first) or mtime (newest first)
+    #[arg(long, value_enum, value_name = "ORDER", default_value_t = FileOrder::Name)]
+    pub sort_files: FileOrder,
+
+    /// When searching a directory, only search files whose name matches this
+    /// glob (e.g. '*.parquet'); repeatable
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// When searching a directory, skip files whose name matches this glob
+    /// (e.g. '*_tmp.csv'); repeatable, and wins over --include
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Print a summary (files searched and matched, rows scanned, matches,
+    /// elapsed time) on stderr at the end
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Report extra diagnostics (such as the columns searched) on stderr
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Don't show a progress bar on stderr while searching a directory or
+    /// prefix (by default shown when stderr is a terminal and stdout isn't)
     #[arg(long)]
+    pub no_progress: bool,
+
+    /// Stop at the first file that can't be read, rather than reporting it
+    /// and searching the rest of a directory
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Ignore the config file and the TABLE_GREP_OPTS environment variable
+    #[arg(long)]
+    pub no_config: bool,
+
+    /// When to color output
+    #[arg(long, value_name = "WHEN", default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Disable color output (same as --color never)
+    #[arg(long, overrides_with = "color")]
     pub no_color: bool,
 
-    /// Output format for matching rows [csv, table]
+    /// Color and style of matched text: a color (red, bright-blue, ...),
+    /// bold, underline, italic, or none, comma-separated [default: red,bold]
+    #[arg(long, value_name = "STYLE", value_parser = ColorSpec::parse)]
+    pub highlight_color: Option<ColorSpec>,
+
+    /// Color and style of file names [default: cyan]
+    #[arg(long, value_name = "STYLE", value_parser = ColorSpec::parse)]
+    pub filename_color: Option<ColorSpec>,
+
+    /// Color and style of row numbers [default: yellow]
+    #[arg(long, value_name = "STYLE", value_parser = ColorSpec::parse)]
+    pub row_number_color: Option<ColorSpec>,
+
+    /// Color and style of matched text in particular columns, e.g.
+    /// error=red,warning=yellow,underline; other columns keep
+    /// --highlight-color
+    #[arg(long, value_name = "COLUMN=STYLE,...", value_parser = ColumnColors::parse)]
+    pub color_by_column: Option<ColumnColors>,
+
+    /// Field delimiter for delimited text files (default: tab for .tsv/.tab,
+    /// '|' for .psv, and sniffed from the first lines for other files)
+    #[arg(long, value_name = "CHAR", value_parser = parse_delimiter)]
+    pub delimiter: Option<u8>,
+
+    /// Character encoding of delimited text files, e.g. latin1, windows-1252,
+    /// utf-16le or shift_jis (default: UTF-8). A byte order mark overrides it
+    #[arg(long, value_name = "ENCODING", value_parser = parse_encoding)]
+    pub encoding: Option<&'static Encoding>,
+
+    /// Split each line of delimited text files into fields wherever this
+    /// regex matches (e.g. '\|\|' or '\s+\|\s+'), instead of parsing them as
+    /// CSV. Quotes get no special treatment
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = parse_split_regex,
+        conflicts_with_all = ["delimiter", "quote", "no_quoting", "escape"]
+    )]
+    pub split_regex: Option<Regex>,
+
+    /// Strip whitespace around cells (and headers) of delimited text files
+    /// before matching and printing
+    #[arg(long)]
+    pub trim: bool,
+
+    /// Quote character for delimited text files (default: '"')
+    #[arg(long, value_name = "CHAR", value_parser = parse_ascii_char, conflicts_with = "no_quoting")]
+    pub quote: Option<u8>,
+
+    /// Treat quote characters in delimited text files as ordinary text
+    #[arg(long)]
+    pub no_quoting: bool,
+
+    /// Escape character for quotes inside quoted fields (e.g. '\'), for
+    /// files that don't escape quotes by doubling them
+    #[arg(long, value_name = "CHAR", value_parser = parse_ascii_char)]
+    pub escape: Option<u8>,
+
+    /// CSV/TSV files have no header row: the first line is data, and columns
+    /// are named col1, col2, ...
+    #[arg(long)]
+    pub no_header_row: bool,
+
+    /// Warn about each CSV/TSV row with more or fewer fields than the header,
+    /// by its number
+    #[arg(long)]
+    pub strict_columns: bool,
+
+    /// Fill out CSV/TSV rows with too few fields with empty cells, and drop
+    /// the extra fields of rows with too many, so cells line up with the
+    /// header's columns
+    #[arg(long)]
+    pub pad: bool,
+
+    /// Time zone to display timezone-aware timestamps in, as an IANA name
+    /// (Europe/Paris) or offset (+05:30) [default: UTC]
+    #[arg(long, value_name = "TZ", value_parser = parse_timezone)]
+    pub timezone: Option<Tz>,
+
+    /// How binary Parquet/Arrow cells are rendered for matching and display
+    #[arg(long, value_enum, default_value_t = BinaryEncoding::Hex, value_name = "ENCODING")]
+    pub binary_encoding: BinaryEncoding,
+
+    /// Text shown for null Parquet/Arrow/JSONL values, e.g. '' or '\N'
+    /// (JSON output always uses null)
+    #[arg(
+        long,
+        value_name = "TEXT",
+        default_value = "NULL",
+        allow_hyphen_values = true
+    )]
+    pub null_text: String,
+
+    /// Number CSV/TSV/JSONL rows by the line they start on in the file (the
+    /// header is line 1) instead of by record. Parquet and Arrow rows are
+    /// always numbered by record
+    #[arg(short = 'n', long)]
+    pub line_number: bool,
+
+    /// Don't use Parquet row-group statistics to skip row groups
+    #[arg(long)]
+    pub no_stats: bool,
+
+    /// With -c, count Parquet matches of an exact literal in one column from
+    /// row-group statistics alone, without reading any data; row groups the
+    /// statistics can't settle count all their non-null cells, so the total
+    /// is an upper bound
+    #[arg(long, requires = "count", conflicts_with = "no_stats")]
+    pub estimate: bool,
+
+    /// Only read these Parquet row groups (zero-based, comma-separated)
+    #[arg(long, value_delimiter = ',', value_name = "INDICES")]
+    pub row_groups: Vec<usize>,
+
+    /// Only read Parquet rows numbered START to END (inclusive, as printed);
+    /// either end may be left open, e.g. '100000:'
+    #[arg(long, value_name = "START:END", value_parser = RowRange::parse)]
+    pub row_range: Option<RowRange>,
+
+    /// Number of threads used to scan a Parquet file's row groups, or a CSV
+    /// file with --parallel (default: one per CPU; 1 scans serially)
+    #[arg(short = 'j', long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+
+    /// Search large CSV/TSV files on several threads (see --threads) by
+    /// splitting them between records; assumes quote characters only appear
+    /// in quoted fields. Ignored with -m, -l, -L, -q, context, --escape,
+    /// --strict-columns, --split-regex, --encoding or --show-location
+    #[arg(long)]
+    pub parallel: bool,
+
+    /// Input format, overriding extension detection (defaults to csv for stdin)
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub input_format: Option<InputFormat>,
+
+    /// Excel worksheet to search, by name or zero-based index (default: the
+    /// first)
+    #[arg(long, value_name = "NAME|INDEX")]
+    pub sheet: Option<String>,
+
+    /// Search every worksheet of an Excel workbook, naming each as
+    /// 'FILE:SHEET' in the output
+    #[arg(long, conflicts_with = "sheet")]
+    pub all_sheets: bool,
+
+    /// Write matching rows from all files to PATH (.csv, .tsv, .psv or
+    /// .parquet), or with '-' to stdout instead of printing them
+    #[arg(long, value_name = "PATH")]
+    pub output_file: Option<String>,
+
+    /// Format of the --output-file, when its name doesn't say (default for
+    /// '-': csv)
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "output_file")]
+    pub output_format: Option<OutputFileFormat>,
+
+    /// With --output-file, also print matching rows to stdout
+    #[arg(long, requires = "output_file")]
+    pub also_print: bool,
+
+    /// Write a copy of each file with matches replaced (see --replace) under
+    /// DIR, at its path relative to PATH, and print the copies' paths
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "replace",
+        conflicts_with_all = ["output_file", "count", "files_with_matches", "files_without_matches", "summarizing", "list_columns", "schema", "search_headers", "dry_run"]
+    )]
+    pub replace_output_dir: Option<String>,
+
+    /// Rewrite each file with matches replaced (see --replace), through a
+    /// temporary file renamed over the original
+    #[arg(
+        long,
+        requires = "replace",
+        conflicts_with_all = ["replace_output_dir", "output_file", "count", "files_with_matches", "files_without_matches", "summarizing", "list_columns", "schema", "search_headers", "dry_run"]
+    )]
+    pub in_place: bool,
+
+    /// Shorten printed cells to N characters, ending in '…' (matching still
+    /// sees the full value)
+    #[arg(long, value_name = "N")]
+    pub max_cell_width: Option<usize>,
+
+    /// Highlight only the first N matches in each printed cell (default:
+    /// all), which saves time on cells with hundreds of matches; which rows
+    /// match is unchanged
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_matches_per_cell: Option<u32>,
+
+    /// Print numbers with their digits grouped in thousands (1,000,000), and
+    /// let numbers match the pattern written either way
+    #[arg(long)]
+    pub format_numbers: bool,
+
+    /// Thousands separator for --format-numbers
+    #[arg(long, value_name = "SEP", default_value = ",", requires = "format_numbers", value_parser = parse_thousands_sep)]
+    pub thousands_sep: String,
+
+    /// Print only these columns of matching rows (names, indices or ranges,
+    /// as for --columns), whichever columns are searched
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "COLUMNS",
+        allow_hyphen_values = true
+    )]
+    pub show_columns: Option<Vec<String>>,
+
+    /// Print only the first N columns, followed by a '…' column
+    #[arg(long, value_name = "N")]
+    pub max_columns: Option<usize>,
+
+    /// Add where each printed row sits in its file as extra columns: the row
+    /// group and the row within it for Parquet, the byte offset for CSV
+    #[arg(long)]
+    pub show_location: bool,
+
+    /// With --format table, annotate headers with Parquet/Arrow column types,
+    /// e.g. 'price (f64)'
+    #[arg(long)]
+    pub show_types: bool,
+
+    /// Print cells holding a JSON object or array indented over several
+    /// lines (CSV, table and vertical output); matching still sees the
+    /// original text
+    #[arg(long)]
+    pub pretty_json: bool,
+
+    /// Print each file's column names with their indices (and types, for
+    /// Parquet and Arrow) instead of searching
+    #[arg(long)]
+    pub list_columns: bool,
+
+    /// Print a summary of each file (rows, columns with types, size) instead
+    /// of searching; with --format json, one object per file
+    #[arg(long, conflicts_with = "list_columns")]
+    pub schema: bool,
+
+    /// Match the pattern against each file's column names instead of its
+    /// rows, and print the matching names (with -l, the files with any)
+    #[arg(long, conflicts_with_all = ["list_columns", "schema"])]
+    pub search_headers: bool,
+
+    /// Print the files that would be searched, each with the columns that
+    /// would be searched in it, without reading any rows
+    #[arg(long, conflicts_with_all = ["list_columns", "schema", "search_headers"])]
+    pub dry_run: bool,
+
+    /// Warn about files whose columns differ from the first file's, listing
+    /// the columns added and removed; without a pattern, only check
+    #[arg(long)]
+    pub check_schema: bool,
+
+    /// Sort each file's matching rows (with --merge-tables, all of them) by
+    /// COLUMN, optionally descending ('ts:desc'); repeat to break ties with
+    /// further columns
+    #[arg(
+        long,
+        value_name = "COLUMN[:desc]",
+        value_parser = SortKey::parse,
+        conflicts_with_all = ["count", "only_matching", "only_non_matching", "context", "before_context", "after_context"]
+    )]
+    pub sort: Vec<SortKey>,
+
+    /// With --format table, print the matches of every file as one table
+    /// with a leading 'file' column, once all files are searched; --sort
+    /// then sorts across files
+    #[arg(
+        long,
+        conflicts_with_all = ["count", "only_matching", "only_non_matching", "context", "before_context", "after_context"]
+    )]
+    pub merge_tables: bool,
+
+    /// Collapse identical matching rows in each file to the first one; with
+    /// -c, count distinct rows
+    #[arg(long, conflicts_with_all = ["context", "before_context", "after_context"])]
+    pub unique: bool,
+
+    /// Like --unique, but rows count as identical when these columns (names,
+    /// indices or ranges, as for --columns) are
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "COLUMNS",
+        conflicts_with_all = ["context", "before_context", "after_context"]
+    )]
+    pub unique_by: Option<Vec<String>>,
+
+    /// JSON output: wrap all rows in a single array instead of one object per line
+    #[arg(long)]
+    pub json_array: bool,
+
+    /// Output format for matching rows [csv, table, json, markdown, vertical]
     #[arg(
         long,
         short = 'f',
@@ -80,17 +959,815 @@ pub struct Cli {
 
 impl Cli {
     pub fn build_regex(&self) -> Result<Regex> {
-        let pattern = if self.fixed_strings {
-            regex::escape(&self.pattern)
-        } else {
-            self.pattern.clone()
+        let patterns = self.patterns()?;
+        let escaped: Vec<String> = patterns
+            .iter()
+            .map(|p| {
+                if self.fixed_strings {
+                    regex::escape(p)
+                } else {
+                    p.clone()
+                }
+            })
+            .collect();
+
+        // One alternation keeps highlighting working unchanged: whichever
+        // alternative matched in a cell is what gets highlighted.
+        let mut combined = match escaped.as_slice() {
+            [single] => single.clone(),
+            many => many
+                .iter()
+                .map(|p| format!("(?:{})", p))
+                .collect::<Vec<_>>()
+                .join("|"),
         };
+        if self.word_regexp {
+            // Wrap the whole alternation so every branch gets both boundaries.
+            // Like grep, a match needs no word character just outside it, so
+            // half boundaries: `\b` would never match before '-1' or after 'a.'.
+            combined = format!(r"\b{{start-half}}(?:{})\b{{end-half}}", combined);
+        }
+        // Anchoring (rather than checking a match's span) lets the regex
+        // engine try every alternative: `a|ab` must still match "ab".
+        if self.matches_whole_cell() {
+            combined = format!("^(?:{})$", combined);
+        } else if self.starts_with {
+            combined = format!("^(?:{})", combined);
+        } else if self.ends_with {
+            combined = format!("(?:{})$", combined);
+        }
 
-        let re = RegexBuilder::new(&pattern)
+        let compile = |pattern: &str| {
+            let mut builder = RegexBuilder::new(pattern);
+            builder.case_insensitive(self.ignore_case);
+            if let Some(mib) = self.regex_size_limit {
+                builder.size_limit((mib as usize) << 20);
+            }
+            builder.build()
+        };
+        compile(&combined).map_err(|e| {
+            if let regex::Error::CompiledTooBig(limit) = e {
+                return anyhow::anyhow!(
+                    "Regex pattern '{}' is too big: it compiles to over the {} MiB limit. \
+                     Simplify it (large counted repetitions such as {{1000}} grow it fastest), \
+                     or raise the limit with --regex-size-limit",
+                    patterns.join("', '"),
+                    limit >> 20
+                );
+            }
+            // Report the offending pattern rather than the whole alternation
+            for (original, escaped) in patterns.iter().zip(&escaped) {
+                if let Err(e) = compile(escaped) {
+                    return anyhow::anyhow!("Invalid regex pattern '{}': {}", original, e);
+                }
+            }
+            anyhow::anyhow!("Invalid regex pattern '{}': {}", patterns.join("', '"), e)
+        })
+    }
+
+    /// The positional PATTERN. When -e, --file, --query or --expr give the
+    /// pattern, a lone positional is PATH, so there's no PATTERN.
+    pub fn pattern(&self) -> Option<&str> {
+        self.second_positional
+            .as_ref()
+            .map(|_| self.first_positional.as_str())
+    }
+
+    /// The file or directory to search.
+    pub fn path(&self) -> &str {
+        self.second_positional
+            .as_deref()
+            .unwrap_or(&self.first_positional)
+    }
+
+    /// All patterns to search for: the positional PATTERN, or every -e value
+    /// plus the lines of --file.
+    fn patterns(&self) -> Result<Vec<String>> {
+        let mut patterns = self.regexp.clone();
+        if let Some(path) = &self.pattern_file {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read pattern file '{}': {}", path, e))?;
+            patterns.extend(
+                text.lines()
+                    .map(|line| line.strip_suffix('\r').unwrap_or(line))
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string),
+            );
+        }
+
+        match self.pattern() {
+            Some(_) if self.pattern_file.is_some() || !self.regexp.is_empty() => anyhow::bail!(
+                "A positional PATTERN can't be combined with -e or --file; pass every pattern with -e"
+            ),
+            Some(pattern) => Ok(vec![pattern.to_string()]),
+            None if self.pattern_file.is_some() && patterns.is_empty() => {
+                anyhow::bail!("Pattern file contains no patterns")
+            }
+            // --query, --expr or --in-file/--not-in-file alone: every row is a
+            // candidate; --list-columns, --schema, --dry-run and --check-schema
+            // alone don't search at all
+            None if patterns.is_empty()
+                && (self.query.is_some()
+                    || !self.exprs.is_empty()
+                    || !self.in_file.is_empty()
+                    || !self.not_in_file.is_empty()
+                    || self.describes_only())
+                && !self.search_headers =>
+            {
+                Ok(vec![String::new()])
+            }
+            None if patterns.is_empty() => {
+                anyhow::bail!("No pattern given: pass PATTERN or use -e/--file")
+            }
+            None => Ok(patterns),
+        }
+    }
+
+    /// Compile `--column-regex`, if given.
+    pub fn build_column_regex(&self) -> Result<Option<Regex>> {
+        self.column_regex
+            .as_deref()
+            .map(|re| {
+                Regex::new(re)
+                    .map_err(|e| anyhow::anyhow!("Invalid --column-regex '{}': {}", re, e))
+            })
+            .transpose()
+    }
+
+    /// Compile the `--exclude-pattern`s into one regex, honoring -i and -F.
+    pub fn build_exclude_regex(&self) -> Result<Option<Regex>> {
+        if self.exclude_patterns.is_empty() {
+            return Ok(None);
+        }
+        let combined = self
+            .exclude_patterns
+            .iter()
+            .map(|p| {
+                if self.fixed_strings {
+                    format!("(?:{})", regex::escape(p))
+                } else {
+                    format!("(?:{})", p)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        RegexBuilder::new(&combined)
             .case_insensitive(self.ignore_case)
             .build()
-            .map_err(|e| anyhow::anyhow!("Invalid regex pattern '{}': {}", self.pattern, e))?;
+            .map(Some)
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid --exclude-pattern '{}': {}",
+                    self.exclude_patterns.join("', '"),
+                    e
+                )
+            })
+    }
+
+    /// Parse and compile every `--match COLUMN=REGEX`, honoring -i.
+    pub fn build_column_matches(&self) -> Result<Vec<ColumnMatch>> {
+        self.column_matches
+            .iter()
+            .map(|m| ColumnMatch::parse(m, self.ignore_case))
+            .collect()
+    }
+
+    /// Load every `--in-file` and `--not-in-file` set.
+    pub fn build_column_sets(&self) -> Result<Vec<ColumnSet>> {
+        self.in_file
+            .iter()
+            .map(|s| ColumnSet::load(s, true))
+            .chain(self.not_in_file.iter().map(|s| ColumnSet::load(s, false)))
+            .collect()
+    }
+
+    /// How deep the directory walk may go: --no-recursive (or
+    /// `-d nonrecursive`) is depth 1, capped further by --max-depth.
+    pub fn walk_depth(&self) -> Option<usize> {
+        let nonrecursive = self.no_recursive || self.directories == Directories::Nonrecursive;
+        match (self.max_depth, nonrecursive) {
+            (Some(depth), true) => Some(depth.min(1)),
+            (None, true) => Some(1),
+            (depth, false) => depth,
+        }
+    }
+
+    /// Compile the --include/--exclude globs.
+    pub fn build_file_globs(&self) -> Result<FileGlobs> {
+        Ok(FileGlobs {
+            include: if self.include.is_empty() {
+                None
+            } else {
+                Some(build_glob_set(&self.include, "--include")?)
+            },
+            exclude: build_glob_set(&self.exclude, "--exclude")?,
+        })
+    }
+
+    /// How typed (Parquet/Arrow) values are rendered as text.
+    pub fn value_format(&self) -> ValueFormat {
+        ValueFormat {
+            timezone: self.timezone,
+            binary_encoding: self.binary_encoding,
+            null_text: self.null_text.clone(),
+        }
+    }
+
+    /// Whether files are rewritten with their matches replaced
+    /// (--in-place or --replace-output-dir) rather than searched.
+    pub fn rewrites_files(&self) -> bool {
+        self.in_place || self.replace_output_dir.is_some()
+    }
+
+    /// Whether matching rows are written to stdout in place of the usual
+    /// output (`--output-file -`).
+    pub fn writes_matches_to_stdout(&self) -> bool {
+        self.output_file.as_deref() == Some("-")
+    }
+
+    /// Whether only file names are printed (-l/-L).
+    pub fn lists_files(&self) -> bool {
+        self.files_with_matches || self.files_without_matches
+    }
+
+    /// Whether matching rows are summarized (--count-distinct, --group-by,
+    /// --count-by-column) rather than printed.
+    pub fn aggregates(&self) -> bool {
+        self.count_distinct.is_some() || self.group_by.is_some() || self.count_by_column
+    }
+
+    /// Whether output should be colored, per --color/--no-color and whether
+    /// stdout is a terminal. Machine- and paste-oriented formats never carry
+    /// ANSI codes.
+    pub fn use_color(&self) -> bool {
+        if matches!(self.format, OutputFormat::Json | OutputFormat::Markdown) {
+            return false;
+        }
+        match self.color {
+            _ if self.no_color => false,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => atty::is(atty::Stream::Stdout),
+        }
+    }
+
+    /// The output colors, with any --highlight-color, --filename-color or
+    /// --row-number-color in place of the defaults.
+    pub fn palette(&self) -> Palette {
+        let defaults = Palette::default();
+        Palette {
+            highlight: self.highlight_color.unwrap_or(defaults.highlight),
+            filename: self.filename_color.unwrap_or(defaults.filename),
+            row_number: self.row_number_color.unwrap_or(defaults.row_number),
+        }
+    }
+
+    /// Whether files are only described (--list-columns, --schema,
+    /// --search-headers, --dry-run, or --check-schema without a pattern), not
+    /// searched.
+    pub fn describes_only(&self) -> bool {
+        self.list_columns
+            || self.schema
+            || self.search_headers
+            || self.dry_run
+            || self.checks_schema_only()
+    }
+
+    /// Whether --check-schema was given with nothing to search for, so only
+    /// each file's columns are read.
+    pub fn checks_schema_only(&self) -> bool {
+        self.check_schema
+            && self.pattern().is_none()
+            && self.regexp.is_empty()
+            && self.pattern_file.is_none()
+            && self.query.is_none()
+            && self.exprs.is_empty()
+            && self.in_file.is_empty()
+            && self.not_in_file.is_empty()
+    }
+
+    /// The 1-based numbers of the rows --head and --tail leave to search in a
+    /// file of `total` rows, or `None` when neither was given.
+    pub fn head_tail_range(&self, total: usize) -> Option<RangeInclusive<usize>> {
+        if self.head.is_none() && self.tail.is_none() {
+            return None;
+        }
+        let end = self.head.map_or(total, |head| head.min(total));
+        let start = self.tail.map_or(1, |tail| end.saturating_sub(tail) + 1);
+        Some(start..=end)
+    }
+
+    /// Whether matching rows are shown as a list of their cells rather than
+    /// whole (-o/--only-non-matching).
+    pub fn lists_cells(&self) -> bool {
+        self.only_matching || self.only_non_matching
+    }
+
+    /// Whether the first match settles everything needed from a file, so
+    /// reading can stop there (-l/-L/-q).
+    pub fn stops_at_first_match(&self) -> bool {
+        self.lists_files() || self.quiet
+    }
+
+    /// How many threads may search at once (--threads, or one per CPU).
+    pub fn thread_count(&self) -> usize {
+        match self.threads {
+            Some(n) => n as usize,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// Whether matches may be found out of order, on several threads: not
+    /// when they count towards -m, the first one ends the search (-l, -L,
+    /// -q), or context rows surround them.
+    pub fn allows_parallel_scan(&self) -> bool {
+        self.max_count.is_none() && !self.stops_at_first_match() && !self.has_context()
+    }
+
+    /// Whether any context rows were requested with -A/-B/-C.
+    pub fn has_context(&self) -> bool {
+        [self.after_context, self.before_context, self.context]
+            .iter()
+            .any(|n| n.is_some_and(|n| n > 0))
+    }
+
+    /// Whether the pattern must match an entire cell: -x, or --starts-with
+    /// and --ends-with together.
+    fn matches_whole_cell(&self) -> bool {
+        self.line_regexp || (self.starts_with && self.ends_with)
+    }
+
+    /// The literal a cell must equal for the search to match, when the pattern
+    /// is a plain anchored literal such as `^abc$` (or any literal with -x).
+    /// Only then is it sound to rule out Parquet row groups from their
+    /// min/max statistics. With --format-numbers a cell may match in another
+    /// grouping than the literal's, so there's none.
+    pub fn exact_literal(&self) -> Option<String> {
+        if self.ignore_case || self.invert || self.pattern_file.is_some() || self.format_numbers {
+            return None;
+        }
+        let [pattern] = &self.patterns().ok()?[..] else {
+            return None;
+        };
+        if self.fixed_strings {
+            return self.matches_whole_cell().then(|| pattern.clone());
+        }
+        let anchored = pattern.strip_prefix('^').and_then(|p| p.strip_suffix('$'));
+        let inner = match anchored {
+            Some(inner) => inner,
+            None if self.matches_whole_cell() => pattern,
+            None => return None,
+        };
+        (regex::escape(inner) == inner).then(|| inner.to_string())
+    }
+}
+
+fn parse_timezone(s: &str) -> Result<Tz, String> {
+    s.parse()
+        .map_err(|e| format!("unknown time zone '{}': {}", s, e))
+}
+
+/// A `--row-range`: 1-based row numbers, inclusive, open where `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowRange {
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl RowRange {
+    fn parse(s: &str) -> Result<Self, String> {
+        let (start, end) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected START:END, got '{}'", s))?;
+        let bound = |b: &str| {
+            (!b.is_empty())
+                .then(|| b.parse::<usize>())
+                .transpose()
+                .map_err(|_| format!("'{}' is not a row number in '{}'", b, s))
+        };
+        let range = Self {
+            start: bound(start)?,
+            end: bound(end)?,
+        };
+        if range.start == Some(0) {
+            return Err("rows are numbered from 1".to_string());
+        }
+        if let (Some(start), Some(end)) = (range.start, range.end)
+            && start > end
+        {
+            return Err(format!("empty row range '{}'", s));
+        }
+        Ok(range)
+    }
+
+    pub fn contains(&self, row_num: usize) -> bool {
+        self.start.is_none_or(|start| row_num >= start) && self.end.is_none_or(|end| row_num <= end)
+    }
+
+    /// The rows in both ranges.
+    pub fn intersect(&self, other: &RowRange) -> RowRange {
+        let bound =
+            |a: Option<usize>, b: Option<usize>, pick: fn(usize, usize) -> usize| match (a, b) {
+                (Some(a), Some(b)) => Some(pick(a, b)),
+                (a, b) => a.or(b),
+            };
+        RowRange {
+            start: bound(self.start, other.start, usize::max),
+            end: bound(self.end, other.end, usize::min),
+        }
+    }
+
+    /// Whether no row is in the range.
+    pub fn is_empty(&self) -> bool {
+        matches!((self.start, self.end), (Some(start), Some(end)) if start > end)
+    }
+}
+
+fn parse_thousands_sep(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.contains(|c: char| c.is_ascii_digit() || c == '-') {
+        return Err(format!("'{}' can't separate digits", s));
+    }
+    Ok(s.to_string())
+}
+
+/// Parse a single-byte delimiter, translating shell-escaped sequences like `\t`.
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "\t" => Ok(b'\t'),
+        _ if s.len() == 1 => Ok(s.as_bytes()[0]),
+        _ => Err(format!(
+            "delimiter must be a single ASCII character (or '\\t'), got '{}'",
+            s
+        )),
+    }
+}
+
+/// Parse an `--encoding` label, as the WHATWG Encoding Standard names them.
+fn parse_encoding(s: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(s.trim().as_bytes()).ok_or_else(|| format!("unknown encoding '{}'", s))
+}
+
+/// Parse a `--split-regex` separator, which must match at least one
+/// character.
+fn parse_split_regex(s: &str) -> Result<Regex, String> {
+    let regex = Regex::new(s).map_err(|e| e.to_string())?;
+    if regex.is_match("") {
+        return Err(format!(
+            "'{}' matches an empty string, so it can't separate fields",
+            s
+        ));
+    }
+    Ok(regex)
+}
+
+/// Parse a single ASCII character, such as a quote or escape character.
+fn parse_ascii_char(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [b] if b.is_ascii() => Ok(*b),
+        _ => Err(format!("expected a single ASCII character, got '{}'", s)),
+    }
+}
+
+/// File name filters for directory searches (`--include`/`--exclude`).
+pub struct FileGlobs {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileGlobs {
+    /// Whether a file should be searched, judged by its name alone. A file
+    /// must match some include (if any were given) and no exclude.
+    pub fn allows(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+        !self.exclude.is_match(name) && self.include.as_ref().is_none_or(|i| i.is_match(name))
+    }
+}
+
+fn build_glob_set(globs: &[String], flag: &str) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(
+            Glob::new(glob)
+                .map_err(|e| anyhow::anyhow!("Invalid {} glob '{}': {}", flag, glob, e))?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        let use_color = |args: &[&str]| {
+            Cli::parse_from(["table-grep"].iter().chain(args).chain(&["x", "f.csv"])).use_color()
+        };
+        assert!(use_color(&["--color", "always"]));
+        assert!(!use_color(&["--color", "never"]));
+        assert!(!use_color(&["--color", "always", "--no-color"]));
+        assert!(!use_color(&["--color", "always", "--format", "json"]));
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter(","), Ok(b','));
+        assert_eq!(parse_delimiter("|"), Ok(b'|'));
+        // Escaped tab as typed in a shell, and a literal tab byte
+        assert_eq!(parse_delimiter("\\t"), Ok(b'\t'));
+        assert_eq!(parse_delimiter("\t"), Ok(b'\t'));
+        assert!(parse_delimiter("ab").is_err());
+        assert!(parse_delimiter("").is_err());
+    }
+
+    #[test]
+    fn test_multiple_patterns() {
+        let cli = Cli::parse_from(["table-grep", "-e", "cat", "-e", "d.g", "f.csv"]);
+        assert_eq!(cli.pattern(), None);
+        assert_eq!(cli.path(), "f.csv");
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("a cat"));
+        assert!(re.is_match("dog"));
+        assert!(!re.is_match("bird"));
+
+        // Each pattern is escaped on its own before joining
+        let cli = Cli::parse_from(["table-grep", "-F", "-e", "a.b", "-e", "(x", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("a.b") && re.is_match("(x"));
+        assert!(!re.is_match("axb"));
+
+        let cli = Cli::parse_from(["table-grep", "-e", "ok", "-e", "(bad", "f.csv"]);
+        let err = cli.build_regex().unwrap_err().to_string();
+        assert!(err.contains("'(bad'"), "{}", err);
+
+        assert!(Cli::try_parse_from(["table-grep", "f.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_positionals_around_options() {
+        // Options can sit between PATTERN and PATH, as with grep
+        let cli = Cli::parse_from(["table-grep", "Alice", "-c", "file.csv"]);
+        assert_eq!(cli.pattern(), Some("Alice"));
+        assert_eq!(cli.path(), "file.csv");
+        assert!(cli.count);
+
+        let cli = Cli::parse_from(["table-grep", "Alice", "-f", "table", "file.csv"]);
+        assert_eq!(cli.pattern(), Some("Alice"));
+        assert_eq!(cli.path(), "file.csv");
+        assert_eq!(cli.format, OutputFormat::Table);
+
+        // After --, a pattern can start with a dash
+        let cli = Cli::parse_from(["table-grep", "--", "-x", "file.csv"]);
+        assert_eq!(cli.pattern(), Some("-x"));
+        assert_eq!(cli.path(), "file.csv");
+        assert!(cli.build_regex().unwrap().is_match("a-x"));
+
+        // --query or --expr alone: the lone positional is PATH
+        let cli = Cli::parse_from(["table-grep", "--query", "a > 1", "file.csv"]);
+        assert_eq!(cli.pattern(), None);
+        assert_eq!(cli.path(), "file.csv");
+        let cli = Cli::parse_from(["table-grep", "file.csv", "--expr", "col(\"a\") > 1"]);
+        assert_eq!(cli.path(), "file.csv");
+    }
+
+    #[test]
+    fn test_pattern_file() {
+        let path = std::env::temp_dir().join(format!("table-grep-patterns-{}", std::process::id()));
+        let lines: Vec<String> = (0..1000).map(|i| format!("ACCT-{:04}.x", i)).collect();
+        std::fs::write(&path, format!("{}\n\n", lines.join("\n"))).unwrap();
+
+        let cli = Cli::parse_from([
+            "table-grep",
+            "-F",
+            "--file",
+            path.to_str().unwrap(),
+            "-e",
+            "extra",
+            "f.csv",
+        ]);
+        let start = std::time::Instant::now();
+        let re = cli.build_regex().unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(re.is_match("id ACCT-0999.x"));
+        assert!(re.is_match("extra"));
+        // Escaped literally: '.' doesn't match any character
+        assert!(!re.is_match("ACCT-0999yx"));
+        assert!(!re.is_match("ACCT-1000.x"));
+    }
+
+    #[test]
+    fn test_word_regexp() {
+        let cli = Cli::parse_from(["table-grep", "-w", "cat", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("the cat sat"));
+        assert!(!re.is_match("category"));
+        assert!(!re.is_match("concatenate"));
+
+        let cli = Cli::parse_from(["table-grep", "-w", "-i", "-e", "cat", "-e", "dog", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("a Dog barked"));
+        assert!(re.is_match("CAT"));
+        // Neither branch may match inside a longer word
+        assert!(!re.is_match("dogma"));
+        assert!(!re.is_match("bobcat"));
+
+        // Patterns may start or end with a non-word character
+        let cli = Cli::parse_from(["table-grep", "-w", "-e", "-1", "-e", "a.", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("-1"));
+        assert!(re.is_match("x -1"));
+        assert!(!re.is_match("x-1"));
+        assert!(!re.is_match("-10"));
+        assert!(re.is_match("a."));
+        assert!(re.is_match("a. b"));
+        assert!(!re.is_match("ba."));
+    }
+
+    #[test]
+    fn test_line_regexp() {
+        let cli = Cli::parse_from(["table-grep", "-x", "ERROR", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("ERROR"));
+        assert!(!re.is_match("ERROR_RECOVERED"));
+        assert_eq!(cli.exact_literal().as_deref(), Some("ERROR"));
+
+        // Every alternative is tried against the whole cell
+        let cli = Cli::parse_from(["table-grep", "-x", "a|ab", "f.csv"]);
+        assert!(cli.build_regex().unwrap().is_match("ab"));
+        assert_eq!(cli.exact_literal(), None);
+
+        let cli = Cli::parse_from(["table-grep", "-x", "-F", "a.b", "f.csv"]);
+        assert_eq!(cli.exact_literal().as_deref(), Some("a.b"));
+        let cli = Cli::parse_from(["table-grep", "-F", "a.b", "f.csv"]);
+        assert_eq!(cli.exact_literal(), None);
+        let cli = Cli::parse_from(["table-grep", "-x", "--format-numbers", "1,234", "f.csv"]);
+        assert_eq!(cli.exact_literal(), None);
+    }
+
+    #[test]
+    fn test_starts_and_ends_with() {
+        let cli = Cli::parse_from(["table-grep", "-F", "--starts-with", "ORD.*", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("ORD.*-1234"));
+        assert!(!re.is_match("ORD-1234"));
+        assert!(!re.is_match("X-ORD.*"));
+        assert_eq!(cli.exact_literal(), None);
+
+        let cli = Cli::parse_from(["table-grep", "-F", "--ends-with", ".csv", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("data.csv"));
+        assert!(!re.is_match("datacsv"));
+        assert!(!re.is_match("data.csv.gz"));
+
+        // Anchors go outside -w's word boundaries
+        let cli = Cli::parse_from(["table-grep", "-w", "--starts-with", "a|b", "f.csv"]);
+        let re = cli.build_regex().unwrap();
+        assert_eq!(re.as_str(), r"^(?:\b{start-half}(?:a|b)\b{end-half})");
+        assert!(re.is_match("b c"));
+        assert!(!re.is_match("c b"));
+
+        // Both together mean the whole cell, like -x
+        let args = ["table-grep", "-F", "--starts-with", "--ends-with", "a.b"];
+        let cli = Cli::parse_from(args.iter().chain(&["f.csv"]));
+        let re = cli.build_regex().unwrap();
+        assert!(re.is_match("a.b"));
+        assert!(!re.is_match("a.bc"));
+        assert_eq!(cli.exact_literal().as_deref(), Some("a.b"));
+    }
+
+    #[test]
+    fn test_regex_size_limit() {
+        let pattern = r"\w{100}";
+        let cli = Cli::parse_from(["table-grep", pattern, "f.csv"]);
+        assert!(cli.build_regex().is_ok());
+
+        let cli = Cli::parse_from(["table-grep", "--regex-size-limit", "1", pattern, "f.csv"]);
+        let err = cli.build_regex().unwrap_err().to_string();
+        assert!(err.contains("too big"), "{}", err);
+        assert!(err.contains("--regex-size-limit"), "{}", err);
+    }
+
+    #[test]
+    fn test_head_tail_range() {
+        let range = |args: &[&str], total: usize| {
+            Cli::parse_from(["table-grep"].iter().chain(args).chain(&["x", "f.csv"]))
+                .head_tail_range(total)
+        };
+        assert_eq!(range(&[], 100), None);
+        assert_eq!(range(&["--head", "10"], 100), Some(1..=10));
+        assert_eq!(range(&["--tail", "10"], 100), Some(91..=100));
+        assert_eq!(range(&["--head", "10", "--tail", "3"], 100), Some(8..=10));
+        assert_eq!(range(&["--head", "10"], 4), Some(1..=4));
+        assert!(range(&["--tail", "0"], 4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_row_range() {
+        let range = RowRange::parse("10:20").unwrap();
+        assert!(!range.contains(9));
+        assert!(range.contains(10) && range.contains(20));
+        assert!(!range.contains(21));
+
+        assert!(RowRange::parse("5:").unwrap().contains(usize::MAX));
+        assert!(RowRange::parse(":5").unwrap().contains(1));
+        assert!(RowRange::parse("7:7").unwrap().contains(7));
+
+        assert!(RowRange::parse("10").is_err());
+        assert!(RowRange::parse("0:5").is_err());
+        assert!(RowRange::parse("9:3").is_err());
+        assert!(RowRange::parse("a:3").is_err());
+
+        let both = RowRange::parse("10:")
+            .unwrap()
+            .intersect(&RowRange::parse(":5").unwrap());
+        assert!(both.is_empty());
+        let both = RowRange::parse("10:")
+            .unwrap()
+            .intersect(&RowRange::parse("5:20").unwrap());
+        assert_eq!((both.start, both.end), (Some(10), Some(20)));
+    }
+
+    #[test]
+    fn test_file_globs() {
+        let cli = Cli::parse_from([
+            "table-grep",
+            "--include",
+            "*.csv",
+            "--include",
+            "*.parquet",
+            "--exclude",
+            "*_tmp.csv",
+            "x",
+            "dir",
+        ]);
+        let globs = cli.build_file_globs().unwrap();
+        assert!(globs.allows(Path::new("dir/sales.csv")));
+        assert!(globs.allows(Path::new("dir/sub/sales.parquet")));
+        assert!(!globs.allows(Path::new("dir/sales.jsonl")));
+        assert!(!globs.allows(Path::new("dir/sales_tmp.csv")));
+
+        let cli = Cli::parse_from(["table-grep", "--exclude", "*.gz", "x", "dir"]);
+        let globs = cli.build_file_globs().unwrap();
+        assert!(globs.allows(Path::new("a.tsv")));
+        assert!(!globs.allows(Path::new("a.csv.gz")));
+
+        let cli = Cli::parse_from(["table-grep", "--include", "[", "x", "dir"]);
+        assert!(cli.build_file_globs().is_err());
+    }
+
+    #[test]
+    fn test_input_format_from_path() {
+        let format = |p: &str| InputFormat::from_path(Path::new(p));
+        assert_eq!(format("a/b.csv"), Some(InputFormat::Csv));
+        assert_eq!(format("b.csv.gz"), Some(InputFormat::Csv));
+        assert_eq!(format("b.tsv.gz"), Some(InputFormat::Tsv));
+        assert_eq!(format("b.parquet.gz"), None);
+        assert_eq!(format("b.gz"), None);
+        assert_eq!(format("b.feather"), Some(InputFormat::Arrow));
+        assert_eq!(format("b.txt"), Some(InputFormat::Csv));
+        assert_eq!(format("b.psv.gz"), Some(InputFormat::Psv));
+        assert_eq!(format("b.csv.zst"), Some(InputFormat::Csv));
+        assert_eq!(format("b.tsv.xz"), Some(InputFormat::Tsv));
+        assert_eq!(format("b.parquet.bz2"), None);
+        assert_eq!(format("report.xlsx"), Some(InputFormat::Xlsx));
+        assert_eq!(format("legacy.xls"), Some(InputFormat::Xlsx));
+        assert_eq!(format("b.tsv.zstd"), Some(InputFormat::Tsv));
+
+        // The skipped-files message names what from_path accepts
+        let supported = InputFormat::supported_extensions();
+        for extension in [".arrows", ".xlsm", ".xlsb", ".tab", ".zstd"] {
+            assert!(supported.contains(extension), "{}", supported);
+        }
+    }
+
+    #[test]
+    fn test_dry_run_needs_no_pattern() {
+        let cli = Cli::parse_from(["table-grep", "--dry-run", "dir"]);
+        assert_eq!(cli.path(), "dir");
+        assert!(cli.describes_only());
+        assert!(cli.build_regex().is_ok());
+
+        let cli = Cli::parse_from(["table-grep", "--dry-run", "-k", "id", "x", "dir"]);
+        assert!(cli.build_regex().unwrap().is_match("x"));
+    }
+
+    #[test]
+    fn test_compression_from_magic() {
+        use std::io::Write;
 
-        Ok(re)
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::best());
+        encoder.write_all(b"a,b\n1,2\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(
+            Compression::from_magic(&compressed),
+            Some(Compression::Bzip2)
+        );
+        // A CSV whose header happens to start like a bzip2 file
+        assert_eq!(Compression::from_magic(b"BZh,score\n1,2\n"), None);
+        assert_eq!(Compression::from_magic(b"BZh9"), None);
     }
 }
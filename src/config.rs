@@ -0,0 +1,240 @@
+//! Default options from a config file and the `TABLE_GREP_OPTS` environment
+//! variable. Precedence, lowest first: the config file, `TABLE_GREP_OPTS`,
+//! then the command line. `--no-config` skips both.
+
+use crate::cli::Cli;
+use anyhow::Result;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding options to put before the command line's.
+const OPTS_VAR: &str = "TABLE_GREP_OPTS";
+
+/// Parse the command line on top of any configured defaults. Exits, as
+/// `Cli::parse` does, on a usage error or `--help`.
+pub fn parse_cli() -> Result<Cli> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let opts = std::env::var(OPTS_VAR).ok();
+    parse_with_defaults(args, config_path().as_deref(), opts.as_deref()).map_err(|e| {
+        match e.downcast::<clap::Error>() {
+            Ok(e) => e.exit(),
+            Err(e) => e,
+        }
+    })
+}
+
+/// `~/.config/table-grep/config.toml`, or under `$XDG_CONFIG_HOME` if set.
+fn config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_dir.join("table-grep").join("config.toml"))
+}
+
+fn parse_with_defaults(
+    args: Vec<OsString>,
+    config: Option<&Path>,
+    opts: Option<&str>,
+) -> Result<Cli> {
+    let explicit = Cli::command().try_get_matches_from(&args)?;
+    if explicit.get_flag("no_config") {
+        return Ok(Cli::from_arg_matches(&explicit)?);
+    }
+
+    let mut defaults = Vec::new();
+    if let Some(path) = config
+        && path.is_file()
+    {
+        defaults = config_args(path, &explicit)?;
+    }
+    if let Some(opts) = opts {
+        defaults.extend(split_opts(opts)?);
+    }
+    if defaults.is_empty() {
+        return Ok(Cli::from_arg_matches(&explicit)?);
+    }
+
+    // Options given later override earlier ones (see `args_override_self`)
+    let mut args = args.into_iter();
+    let combined: Vec<OsString> = args
+        .next()
+        .into_iter()
+        .chain(defaults.into_iter().map(OsString::from))
+        .chain(args)
+        .collect();
+    let matches = Cli::command().try_get_matches_from(combined)?;
+    Ok(Cli::from_arg_matches(&matches)?)
+}
+
+/// The options set in a config file, as arguments. Keys are long option
+/// names (`delimiter`, `no-color`, or `no_color`); flags take `true` or
+/// `false`, repeatable options an array. Options given on the command line
+/// are left out, so repeatable ones aren't added to.
+fn config_args(path: &Path, explicit: &ArgMatches) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read config file '{}': {}", path.display(), e))?;
+    let table: toml::Table = text
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid config file '{}': {}", path.display(), e))?;
+
+    let command = Cli::command();
+    let mut args = Vec::new();
+    for (key, value) in &table {
+        let long = key.replace('_', "-");
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()))
+        else {
+            anyhow::bail!(
+                "Unknown option '{}' in config file '{}'",
+                key,
+                path.display()
+            );
+        };
+        if explicit.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+
+        let is_flag = matches!(arg.get_action(), ArgAction::SetTrue | ArgAction::Count);
+        let values = match value {
+            toml::Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::Boolean(true) if is_flag => {
+                    args.push(format!("--{}", long));
+                    continue;
+                }
+                toml::Value::Boolean(false) if is_flag => continue,
+                _ if is_flag => anyhow::bail!(
+                    "Option '{}' in config file '{}' is a flag; set it to true or false",
+                    key,
+                    path.display()
+                ),
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                _ => anyhow::bail!(
+                    "Option '{}' in config file '{}' needs a string or number",
+                    key,
+                    path.display()
+                ),
+            };
+            args.push(format!("--{}={}", long, value));
+        }
+    }
+    Ok(args)
+}
+
+/// Split `TABLE_GREP_OPTS` into arguments at whitespace, keeping quoted
+/// ('...' or "...") whitespace.
+fn split_opts(opts: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in opts.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert_default().push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        anyhow::bail!("Unterminated quote in {}", OPTS_VAR);
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], config: Option<&Path>, opts: Option<&str>) -> Cli {
+        let args = ["table-grep"]
+            .iter()
+            .chain(args)
+            .map(OsString::from)
+            .collect();
+        parse_with_defaults(args, config, opts).unwrap()
+    }
+
+    #[test]
+    fn test_cli_overrides_config() {
+        let path =
+            std::env::temp_dir().join(format!("table-grep-config-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "delimiter = \";\"\nno_color = true\ncolumns = [\"name\", \"age\"]\nmax-count = 5\n",
+        )
+        .unwrap();
+        let config = Some(path.as_path());
+
+        let cli = parse(&["x", "f.csv"], config, None);
+        assert_eq!(cli.delimiter, Some(b';'));
+        assert!(cli.no_color);
+        assert_eq!(
+            cli.columns,
+            Some(vec!["name".to_string(), "age".to_string()])
+        );
+        assert_eq!(cli.max_count, Some(5));
+
+        let cli = parse(
+            &[
+                "--delimiter",
+                "|",
+                "-k",
+                "id",
+                "--color",
+                "always",
+                "x",
+                "f.csv",
+            ],
+            config,
+            None,
+        );
+        assert_eq!(cli.delimiter, Some(b'|'));
+        assert_eq!(cli.columns, Some(vec!["id".to_string()]));
+        assert!(cli.use_color());
+        assert_eq!(cli.max_count, Some(5));
+
+        // TABLE_GREP_OPTS sits between the config file and the command line
+        let cli = parse(&["x", "f.csv"], config, Some("--delimiter '\\t' -m 2"));
+        assert_eq!(cli.delimiter, Some(b'\t'));
+        assert_eq!(cli.max_count, Some(2));
+        let cli = parse(&["-m", "1", "x", "f.csv"], config, Some("-m 2"));
+        assert_eq!(cli.max_count, Some(1));
+
+        let cli = parse(&["--no-config", "x", "f.csv"], config, Some("-m 2"));
+        assert_eq!(cli.delimiter, None);
+        assert_eq!(cli.max_count, None);
+
+        std::fs::write(&path, "no_such_option = 1\n").unwrap();
+        let args = ["table-grep", "x", "f.csv"].map(OsString::from).to_vec();
+        assert!(parse_with_defaults(args, config, None).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_split_opts() {
+        assert_eq!(
+            split_opts("  --no-color -k 'first name',age --delimiter \";\" ").unwrap(),
+            ["--no-color", "-k", "first name,age", "--delimiter", ";"]
+        );
+        assert_eq!(split_opts("--null-text ''").unwrap(), ["--null-text", ""]);
+        assert!(split_opts("--delimiter '").is_err());
+    }
+}
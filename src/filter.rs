@@ -0,0 +1,249 @@
+//! Row conditions ANDed with the main pattern: numeric comparisons
+//! (`--filter 'age>65'`), per-column patterns (`--match status=active`) and
+//! set membership (`--in-file id=bad_ids.txt`).
+
+use regex::{Regex, RegexBuilder};
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+/// A parsed `COLUMN OP NUMBER` comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub column: String,
+    pub op: Op,
+    pub value: f64,
+}
+
+impl Filter {
+    /// Parse an expression such as `age>65` or `price <= 9.99`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let op_start = s
+            .find(['<', '>', '=', '!'])
+            .ok_or_else(|| format!("expected COLUMN<OP>NUMBER (e.g. 'age>65'), got '{}'", s))?;
+        let (column, rest) = s.split_at(op_start);
+
+        // Two-character operators first so '<=' isn't read as '<'.
+        let (op, literal) = [
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+            ("=", Op::Eq),
+        ]
+        .iter()
+        .find_map(|(token, op)| rest.strip_prefix(token).map(|lit| (*op, lit)))
+        .ok_or_else(|| format!("unknown comparison operator in '{}'", s))?;
+
+        let column = column.trim();
+        if column.is_empty() {
+            return Err(format!("missing column name in '{}'", s));
+        }
+        let value = literal
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("'{}' is not a number in '{}'", literal.trim(), s))?;
+
+        Ok(Self {
+            column: column.to_string(),
+            op,
+            value,
+        })
+    }
+
+    /// Whether a cell satisfies the comparison. Cells that don't parse as a
+    /// finite number (including NULL, and text like 'inf' or 'NaN') never do.
+    pub fn matches(&self, cell: &str) -> bool {
+        let Some(n) = cell.trim().parse::<f64>().ok().filter(|n| n.is_finite()) else {
+            return false;
+        };
+        self.op.holds(n, self.value)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.column, self.op, self.value)
+    }
+}
+
+impl Op {
+    /// Whether `a OP b` holds.
+    pub fn holds(self, a: f64, b: f64) -> bool {
+        match self {
+            Op::Lt => a < b,
+            Op::Le => a <= b,
+            Op::Gt => a > b,
+            Op::Ge => a >= b,
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+        })
+    }
+}
+
+/// A regex bound to one column by `--match COLUMN=REGEX`.
+#[derive(Debug, Clone)]
+pub struct ColumnMatch {
+    /// Column name, index or range, resolved per file like `--columns`.
+    pub column: String,
+    pub pattern: Regex,
+}
+
+impl ColumnMatch {
+    pub fn parse(s: &str, ignore_case: bool) -> anyhow::Result<Self> {
+        let (column, pattern) = s
+            .split_once('=')
+            .filter(|(column, _)| !column.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Invalid --match '{}': expected COLUMN=REGEX", s))?;
+        let pattern = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid --match pattern '{}': {}", pattern, e))?;
+        Ok(Self {
+            column: column.to_string(),
+            pattern,
+        })
+    }
+}
+
+/// A set of values loaded by `--in-file COLUMN=PATH` (or `--not-in-file`),
+/// one per line of the file, that a column's exact value is looked up in.
+#[derive(Debug, Clone)]
+pub struct ColumnSet {
+    /// Column name, resolved per file like `--filter`'s.
+    pub column: String,
+    pub values: HashSet<String>,
+    /// Whether rows must have a value in the set (`--in-file`) rather than
+    /// one outside it (`--not-in-file`).
+    pub member: bool,
+}
+
+impl ColumnSet {
+    /// Parse `COLUMN=PATH` and load the file's non-empty lines.
+    pub fn load(s: &str, member: bool) -> anyhow::Result<Self> {
+        let flag = if member { "--in-file" } else { "--not-in-file" };
+        let (column, path) = s
+            .split_once('=')
+            .filter(|(column, path)| !column.is_empty() && !path.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Invalid {} '{}': expected COLUMN=PATH", flag, s))?;
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {} file '{}': {}", flag, path, e))?;
+        let values = text
+            .lines()
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        Ok(Self {
+            column: column.to_string(),
+            values,
+            member,
+        })
+    }
+
+    /// Whether a cell's value passes: in the set for `--in-file`, outside it
+    /// for `--not-in-file`.
+    pub fn matches(&self, cell: &str) -> bool {
+        self.values.contains(cell) == self.member
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_match() {
+        let f = Filter::parse("age>65").unwrap();
+        assert_eq!((f.column.as_str(), f.op, f.value), ("age", Op::Gt, 65.0));
+        assert!(f.matches("70"));
+        assert!(!f.matches("65"));
+        assert!(!f.matches("NULL"));
+        assert!(!f.matches("old"));
+        // Text that Rust would read as a float isn't a number here
+        assert!(!f.matches("inf"));
+        assert!(!f.matches("infinity"));
+        assert!(!Filter::parse("v!=5").unwrap().matches("NaN"));
+
+        let f = Filter::parse("unit price <= 9.99").unwrap();
+        assert_eq!((f.column.as_str(), f.op), ("unit price", Op::Le));
+        assert!(f.matches(" 9.99 "));
+
+        assert_eq!(Filter::parse("n=3").unwrap().op, Op::Eq);
+        assert_eq!(Filter::parse("n!=3").unwrap().op, Op::Ne);
+        assert!(Filter::parse("age").is_err());
+        assert!(Filter::parse(">5").is_err());
+        assert!(Filter::parse("age>old").is_err());
+        assert!(Filter::parse("age=>5").is_err());
+        assert!(Filter::parse("age<inf").is_err());
+        assert!(Filter::parse("age!=NaN").is_err());
+    }
+
+    #[test]
+    fn test_parse_column_match() {
+        let m = ColumnMatch::parse("region=^EU", false).unwrap();
+        assert_eq!(m.column, "region");
+        assert!(m.pattern.is_match("EU-West"));
+        assert!(!m.pattern.is_match("eu-west"));
+
+        // Only the first '=' separates the column from the pattern
+        let m = ColumnMatch::parse("expr=a=b", true).unwrap();
+        assert!(m.pattern.is_match("A=B"));
+
+        assert!(ColumnMatch::parse("region", false).is_err());
+        assert!(ColumnMatch::parse("=x", false).is_err());
+        assert!(ColumnMatch::parse("region=(", false).is_err());
+    }
+
+    #[test]
+    fn test_column_set() {
+        let path = std::env::temp_dir().join(format!("table-grep-set-{}", std::process::id()));
+        let ids: Vec<String> = (0..10_000).map(|i| format!("ID-{:05}", i * 2)).collect();
+        std::fs::write(&path, format!("{}\r\n\n", ids.join("\n"))).unwrap();
+        let arg = format!("id={}", path.display());
+
+        let set = ColumnSet::load(&arg, true).unwrap();
+        assert_eq!(set.column, "id");
+        assert_eq!(set.values.len(), 10_000);
+        assert!(set.matches("ID-00000"));
+        assert!(set.matches("ID-19998"));
+        assert!(!set.matches("ID-00001"));
+        assert!(!set.matches("id-00000"));
+        assert!(!set.matches(""));
+
+        let set = ColumnSet::load(&arg, false).unwrap();
+        assert!(!set.matches("ID-04242"));
+        assert!(set.matches("ID-04243"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(ColumnSet::load(&arg, true).is_err());
+        assert!(ColumnSet::load("id", true).is_err());
+        assert!(ColumnSet::load("=ids.txt", false).is_err());
+    }
+}
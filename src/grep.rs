@@ -1,285 +1,3765 @@
-use crate::cli::{Cli, OutputFormat};
-use crate::output::Printer;
+use crate::arith::Comparison;
+use crate::cli::{
+    BinaryEncoding, Cli, Compression, GroupSort, InputFormat, OutputFormat, RowRange,
+};
+use crate::filter::{ColumnMatch, ColumnSet, Filter};
+use crate::output::{
+    MergedTable, OutputRow, Printer, group_thousands, is_null_cell, json_value, truncate_display,
+    ungroup_thousands,
+};
+use crate::query::Expr;
+use crate::report;
+use crate::searcher::MatchRow;
+use crate::sink::OutputSink;
+use crate::sort::sort_rows;
+use crate::stats::{self, LiteralCount};
 use anyhow::Result;
+use arrow::array::timezone::Tz;
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatchReader;
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::bufread::MultiGzDecoder;
 use regex::Regex;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
+use std::ops::Range;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, LazyLock, mpsc};
+use std::time::{Duration, Instant};
 
-pub fn search_file(path: &Path, pattern: &Regex, cli: &Cli) -> Result<()> {
-    let filename = path.display().to_string();
-    let use_color = !cli.no_color && atty::is(atty::Stream::Stdout);
-    let show_filename = !cli.no_filename;
+/// Run-wide state shared by every file searched.
+pub struct SearchContext<'a> {
+    pub pattern: &'a Regex,
+    pub cli: &'a Cli,
+    pub printer: &'a Printer,
+    /// Compiled `--column-regex`, selecting columns by header name.
+    pub column_pattern: Option<Regex>,
+    /// Compiled `--match COLUMN=REGEX` conditions.
+    pub column_matches: Vec<ColumnMatch>,
+    /// Loaded `--in-file`/`--not-in-file` sets.
+    pub column_sets: Vec<ColumnSet>,
+    /// Compiled `--exclude-pattern`: rows it matches are dropped.
+    pub exclude_pattern: Option<Regex>,
+    /// Destination for `--output-file`, shared so matches from every file land
+    /// in one writer.
+    pub sink: Option<RefCell<OutputSink>>,
+    /// Where a library `Searcher` collects matching rows, instead of their
+    /// being printed.
+    pub collected: Option<RefCell<Vec<MatchRow>>>,
+    /// With --merge-tables, every file's matches, printed as one table once
+    /// all are searched.
+    pub merged: Option<RefCell<MergedTable>>,
+    /// `--count-distinct` values seen in every file so far, for --total.
+    pub distinct_values: RefCell<HashSet<String>>,
+    /// With --check-schema, the first file read and its headers, which every
+    /// later file is compared with.
+    pub schema_reference: RefCell<Option<(String, Vec<String>)>>,
+    /// With --check-schema, how many files had different columns from the
+    /// first.
+    pub schema_drifts: Cell<usize>,
+    /// With --max-total, how many more matches the files still to be searched
+    /// may have.
+    pub match_budget: Cell<Option<usize>>,
+}
+
+impl<'a> SearchContext<'a> {
+    /// A context searching for `pattern` as `cli` says, with no conditions
+    /// beyond it compiled and nowhere for rows to go but `printer`.
+    pub fn new(pattern: &'a Regex, cli: &'a Cli, printer: &'a Printer) -> Self {
+        Self {
+            pattern,
+            cli,
+            printer,
+            column_pattern: None,
+            column_matches: Vec::new(),
+            column_sets: Vec::new(),
+            exclude_pattern: None,
+            sink: None,
+            collected: None,
+            merged: None,
+            distinct_values: RefCell::default(),
+            schema_reference: RefCell::default(),
+            schema_drifts: Cell::default(),
+            match_budget: Cell::new(cli.max_total),
+        }
+    }
+
+    /// With --max-total, take a file's `matches` from the budget. Returns
+    /// whether it's used up, so the search should stop.
+    pub fn spend_budget(&self, matches: usize) -> bool {
+        let Some(budget) = self.match_budget.get() else {
+            return false;
+        };
+        let left = budget.saturating_sub(matches);
+        self.match_budget.set(Some(left));
+        left == 0
+    }
+
+    /// With --merge-tables, print the matches of every file searched as one
+    /// table, sorted across files by any --sort keys.
+    pub fn print_merged(&self) {
+        let Some(merged) = &self.merged else {
+            return;
+        };
+        let mut merged = merged.borrow_mut();
+        if merged.rows.is_empty() {
+            return;
+        }
+        merged.pad_rows();
+        let types = merged.column_types();
+        let MergedTable { headers, rows, .. } = &mut *merged;
+        if !self.cli.sort.is_empty() {
+            sort_rows(
+                rows,
+                headers,
+                types.as_deref(),
+                &self.cli.sort,
+                &self.cli.null_text,
+                self.cli.ignore_case_columns,
+                self.cli.path(),
+            );
+        }
+        let highlight = if self.cli.replace.is_some() || self.pattern.as_str().is_empty() {
+            &NO_HIGHLIGHT
+        } else {
+            self.pattern
+        };
+        self.printer.print_table(
+            headers,
+            types.as_deref(),
+            rows,
+            highlight,
+            self.cli.with_headers,
+            1,
+        );
+    }
+
+    /// Resolve the columns to search in one file: the union of `--columns`
+    /// and every header matching `--column-regex`. `None` means all columns.
+    fn resolve_columns(&self, filename: &str, headers: &[String]) -> Option<Vec<usize>> {
+        let mut indices =
+            resolve_column_indices(headers, &self.cli.columns, self.cli.ignore_case_columns);
+
+        if let Some(column_pattern) = &self.column_pattern {
+            let matched: Vec<usize> = (0..headers.len())
+                .filter(|&idx| column_pattern.is_match(&headers[idx]))
+                .collect();
+
+            if matched.is_empty() && indices.is_none() {
+                if self.cli.column_regex_fallback {
+                    report!(
+                        "Warning: no columns in '{}' match --column-regex; searching all columns",
+                        filename
+                    );
+                    return None;
+                }
+                report!("Warning: no columns in '{}' match --column-regex", filename);
+            }
+
+            let selected = indices.get_or_insert_with(Vec::new);
+            for idx in matched {
+                if !selected.contains(&idx) {
+                    selected.push(idx);
+                }
+            }
+        }
+
+        if self.cli.verbose
+            && let Some(selected) = &indices
+        {
+            let names: Vec<&str> = selected.iter().map(|&i| headers[i].as_str()).collect();
+            report!("{}: searching columns: {}", filename, names.join(", "));
+        }
 
-    let printer = Printer::new(use_color, show_filename, cli.format);
+        indices
+    }
 
-    match path.extension().and_then(|e| e.to_str()) {
-        Some("csv") => search_csv(path, &filename, pattern, cli, &printer),
-        Some("parquet") => search_parquet(path, &filename, pattern, cli, &printer),
-        _ => Ok(()),
+    /// With --check-schema, warn when `headers` differ from the first file's,
+    /// listing the columns added and removed. The first file seen becomes the
+    /// reference.
+    fn check_schema(&self, filename: &str, headers: &[String]) {
+        if !self.cli.check_schema {
+            return;
+        }
+        let mut reference = self.schema_reference.borrow_mut();
+        let Some((first, expected)) = reference.as_ref() else {
+            *reference = Some((filename.to_string(), headers.to_vec()));
+            return;
+        };
+        let Some(difference) = schema_difference(expected, headers) else {
+            return;
+        };
+        self.schema_drifts.set(self.schema_drifts.get() + 1);
+        report!(
+            "Warning: '{}' has different columns from '{}': {}",
+            filename,
+            first,
+            difference
+        );
     }
 }
 
-// ── shared output logic ───────────────────────────────────────────────────────
+/// How `headers` differ from `expected`, or `None` when they're the same.
+fn schema_difference(expected: &[String], headers: &[String]) -> Option<String> {
+    if expected == headers {
+        return None;
+    }
+    let missing_from = |columns: &[String], other: &[String]| -> Vec<String> {
+        columns
+            .iter()
+            .filter(|c| !other.contains(c))
+            .map(|c| format!("'{}'", c))
+            .collect()
+    };
+    let added = missing_from(headers, expected);
+    let removed = missing_from(expected, headers);
 
-/// Emit the collected matching rows in whichever format the user chose.
-fn emit_matches(
-    filename: &str,
-    headers: &[String],
-    matches: &[(usize, Vec<String>)],
-    pattern: &Regex,
-    cli: &Cli,
-    printer: &Printer,
-) {
-    if matches.is_empty() {
-        return;
+    let mut parts = Vec::new();
+    if !added.is_empty() {
+        parts.push(format!("added {}", added.join(", ")));
+    }
+    if !removed.is_empty() {
+        parts.push(format!("removed {}", removed.join(", ")));
     }
+    if parts.is_empty() {
+        parts.push("same columns in a different order".to_string());
+    }
+    Some(parts.join("; "))
+}
 
-    printer.print_file_header(filename);
+/// Search one file. The match count is just 1 when -l/-L/-q stop reading at
+/// the first match.
+pub fn search_file(path: &Path, ctx: &SearchContext) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let Some(input_format) = cli.input_format.or_else(|| InputFormat::from_path(path)) else {
+        return Ok(SearchStats::default());
+    };
 
-    match printer.format {
-        OutputFormat::Csv => {
-            if cli.with_headers {
-                printer.print_headers(headers);
-            }
-            for (row_num, row) in matches {
-                printer.print_match(*row_num, row, pattern);
+    let filename = path.display().to_string();
+    let delimiter = resolve_delimiter(input_format, cli);
+
+    match input_format {
+        InputFormat::Csv | InputFormat::Tsv | InputFormat::Psv => {
+            // Splitting on quote parity can't account for escaped quotes.
+            // --head and --tail need rows counted from the ends, and
+            // --strict-columns reports rows by number as they're read. Split
+            // and transcoded text is only read as a stream, and so is text
+            // whose byte offsets --show-location reports.
+            if cli.parallel
+                && cli.allows_parallel_scan()
+                && cli.escape.is_none()
+                && cli.split_regex.is_none()
+                && cli.encoding.is_none()
+                && cli.head.is_none()
+                && cli.tail.is_none()
+                && !cli.strict_columns
+                && !cli.show_location
+                && Compression::from_path(path).is_none()
+            {
+                return search_csv_parallel(path, &filename, delimiter, ctx);
             }
-            printer.print_separator();
+            let reader = open_maybe_compressed(path, &filename)?;
+            search_csv(reader, &filename, delimiter, ctx)
+        }
+        InputFormat::Parquet => {
+            let open = || {
+                File::open(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to open Parquet '{}': {}", filename, e))
+            };
+            search_parquet(open, &filename, ctx)
         }
-        OutputFormat::Table => {
-            // print_table handles its own header row
-            printer.print_table(headers, matches, pattern, cli.with_headers);
+        InputFormat::Jsonl => {
+            let file = File::open(path)
+                .map_err(|e| anyhow::anyhow!("Failed to open JSONL '{}': {}", filename, e))?;
+            search_jsonl(BufReader::new(file), &filename, ctx)
+        }
+        InputFormat::Arrow => {
+            let file = File::open(path)
+                .map_err(|e| anyhow::anyhow!("Failed to open Arrow '{}': {}", filename, e))?;
+            search_arrow(file, &filename, ctx)
+        }
+        InputFormat::Xlsx => {
+            let workbook = calamine::open_workbook_auto(path)
+                .map_err(|e| anyhow::anyhow!("Failed to open Excel '{}': {}", filename, e))?;
+            search_xlsx(workbook, &filename, ctx)
         }
     }
 }
 
-// ── CSV ───────────────────────────────────────────────────────────────────────
+/// Counters from searching one or more files, for the exit status and --stats.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SearchStats {
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub rows_scanned: usize,
+    pub matches: usize,
+    /// Files skipped after an error reading them
+    pub files_failed: usize,
+}
 
-fn search_csv(
-    path: &Path,
-    filename: &str,
-    pattern: &Regex,
-    cli: &Cli,
-    printer: &Printer,
-) -> Result<()> {
-    let mut rdr = csv::ReaderBuilder::new()
-        .flexible(true)
-        .from_path(path)
-        .map_err(|e| anyhow::anyhow!("Failed to open CSV '{}': {}", filename, e))?;
+impl std::ops::AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.files_searched += other.files_searched;
+        self.files_matched += other.files_matched;
+        self.rows_scanned += other.rows_scanned;
+        self.matches += other.matches;
+        self.files_failed += other.files_failed;
+    }
+}
 
-    let headers: Vec<String> = rdr.headers()?.iter().map(|h| h.to_string()).collect();
+/// Open a delimited text file, decompressing `.gz`, `.zst`, `.bz2` and `.xz`
+/// files (or ones whose first bytes show them compressed) as they're read,
+/// so that stopping early (e.g. at `-m`) also stops decompression.
+fn open_maybe_compressed(path: &Path, filename: &str) -> Result<Box<dyn Read>> {
+    let file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open CSV '{}': {}", filename, e))?;
+    decompress(file, Compression::from_path(path))
+        .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", filename, e))
+}
 
-    let col_indices = resolve_column_indices(&headers, &cli.columns);
+/// Wrap `reader` in a streaming decoder for `compression`, or, when the name
+/// didn't give one, for whichever codec its first bytes announce.
+fn decompress<'r, R: Read + 'r>(
+    reader: R,
+    compression: Option<Compression>,
+) -> Result<Box<dyn Read + 'r>> {
+    let mut reader = BufReader::new(reader);
+    let compression = match compression {
+        Some(compression) => Some(compression),
+        None => Compression::from_magic(reader.fill_buf()?),
+    };
+    Ok(match compression {
+        None => Box::new(reader),
+        Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::with_buffer(reader)?),
+        Some(Compression::Bzip2) => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+        Some(Compression::Xz) => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+    })
+}
 
-    let mut match_count = 0usize;
-    let mut row_num = 0usize;
-    let mut matched_rows: Vec<(usize, Vec<String>)> = Vec::new();
+/// Decode text in `encoding` (--encoding) to UTF-8 as it's read, dropping
+/// any byte order mark; a BOM for another encoding wins. UTF-8 text is read
+/// as is: the CSV reader skips a BOM itself.
+fn transcode<'r, R: Read + 'r>(
+    reader: R,
+    encoding: Option<&'static Encoding>,
+) -> Box<dyn Read + 'r> {
+    match encoding {
+        None => Box::new(reader),
+        Some(encoding) => Box::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(Some(encoding))
+                .bom_override(true)
+                .strip_bom(true)
+                .build(reader),
+        ),
+    }
+}
 
-    for result in rdr.records() {
-        let record =
-            result.map_err(|e| anyhow::anyhow!("CSV parse error in '{}': {}", filename, e))?;
-        row_num += 1;
+/// Search table data piped in on stdin. Delimited text is streamed; Parquet,
+/// JSONL, Arrow and Excel need random access (or two passes), so they are
+/// buffered first.
+pub fn search_stdin(ctx: &SearchContext) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let input_format = cli.input_format.unwrap_or(InputFormat::Csv);
+    let filename = "<stdin>";
+    let delimiter = resolve_delimiter(input_format, cli);
 
-        let row: Vec<String> = record.iter().map(|f| f.to_string()).collect();
+    let mut stdin = std::io::stdin().lock();
+    match input_format {
+        InputFormat::Csv | InputFormat::Tsv | InputFormat::Psv => {
+            search_csv(decompress(stdin, None)?, filename, delimiter, ctx)
+        }
+        InputFormat::Parquet => {
+            let mut buf = Vec::new();
+            stdin.read_to_end(&mut buf)?;
+            let bytes = bytes::Bytes::from(buf);
+            search_parquet(|| Ok(bytes.clone()), filename, ctx)
+        }
+        InputFormat::Jsonl => {
+            let mut buf = Vec::new();
+            stdin.read_to_end(&mut buf)?;
+            search_jsonl(Cursor::new(buf), filename, ctx)
+        }
+        InputFormat::Arrow => {
+            let mut buf = Vec::new();
+            stdin.read_to_end(&mut buf)?;
+            search_arrow(Cursor::new(buf), filename, ctx)
+        }
+        InputFormat::Xlsx => {
+            let mut buf = Vec::new();
+            stdin.read_to_end(&mut buf)?;
+            search_xlsx(open_workbook(Cursor::new(buf), filename)?, filename, ctx)
+        }
+    }
+}
 
-        if row_matches(&row, pattern, &col_indices, cli.invert) {
-            match_count += 1;
+/// Search a table at an http:// or https:// URL. Parquet is read with range
+/// requests where the server allows, fetching row groups only as they're
+/// searched; delimited text is streamed, JSONL and Arrow are downloaded first.
+#[cfg(feature = "http")]
+pub fn search_url(url: &str, ctx: &SearchContext) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let path = Path::new(crate::http::url_path(url));
+    let Some(input_format) = cli.input_format.or_else(|| InputFormat::from_path(path)) else {
+        anyhow::bail!(
+            "Can't tell the format of '{}' from its name; use --input-format",
+            url
+        );
+    };
+    let delimiter = resolve_delimiter(input_format, cli);
 
-            if !cli.count {
-                if cli.only_matching {
-                    // only_matching bypasses the buffering path
-                    if matched_rows.is_empty() {
-                        printer.print_file_header(filename);
-                    }
-                    print_only_matching(&row, &headers, pattern, &col_indices);
-                } else {
-                    matched_rows.push((row_num, row));
-                }
+    match input_format {
+        InputFormat::Csv | InputFormat::Tsv | InputFormat::Psv => {
+            let body = decompress(crate::http::open(url)?, Compression::from_path(path))?;
+            search_csv(body, url, delimiter, ctx)
+        }
+        InputFormat::Parquet => match crate::http::RemoteParquet::open(url)? {
+            crate::http::RemoteParquet::Ranged(file) => {
+                search_parquet(|| Ok(file.clone()), url, ctx)
             }
-
-            if let Some(max) = cli.max_count
-                && match_count >= max
-            {
-                break;
+            crate::http::RemoteParquet::Downloaded(bytes) => {
+                search_parquet(|| Ok(bytes.clone()), url, ctx)
             }
+        },
+        InputFormat::Jsonl => search_jsonl(Cursor::new(crate::http::download(url)?), url, ctx),
+        InputFormat::Arrow => search_arrow(Cursor::new(crate::http::download(url)?), url, ctx),
+        InputFormat::Xlsx => {
+            let workbook = open_workbook(Cursor::new(crate::http::download(url)?), url)?;
+            search_xlsx(workbook, url, ctx)
         }
     }
+}
+
+#[cfg(not(feature = "http"))]
+pub fn search_url(url: &str, _ctx: &SearchContext) -> Result<SearchStats> {
+    anyhow::bail!(
+        "Can't read '{}': table-grep was built without URL support (rebuild with --features http)",
+        url
+    )
+}
+
+/// Search one object in an object store. Parquet footers, row groups and
+/// delimited text are fetched a block at a time as they're read, so stopping
+/// early (-m, -l, -q) leaves the rest of the object undownloaded.
+#[cfg(feature = "object-store")]
+pub fn search_object(
+    object: crate::store::StoreObject,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    use crate::ranged::RangedFile;
 
-    if cli.count && match_count > 0 {
-        printer.print_count(filename, match_count);
-    } else if !cli.only_matching {
-        emit_matches(filename, &headers, &matched_rows, pattern, cli, printer);
+    let cli = ctx.cli;
+    let Some(input_format) = cli
+        .input_format
+        .or_else(|| InputFormat::from_path(object.key()))
+    else {
+        return Ok(SearchStats::default());
+    };
+    let filename = object.url().to_string();
+    let delimiter = resolve_delimiter(input_format, cli);
+
+    match input_format {
+        InputFormat::Csv | InputFormat::Tsv | InputFormat::Psv => {
+            let compression = Compression::from_path(object.key());
+            let reader = decompress(RangedFile::new(object).reader(0), compression)?;
+            search_csv(reader, &filename, delimiter, ctx)
+        }
+        InputFormat::Parquet => {
+            let file = RangedFile::new(object);
+            search_parquet(|| Ok(file.clone()), &filename, ctx)
+        }
+        InputFormat::Jsonl => search_jsonl(Cursor::new(object.download()?), &filename, ctx),
+        InputFormat::Arrow => search_arrow(Cursor::new(object.download()?), &filename, ctx),
+        InputFormat::Xlsx => {
+            let workbook = open_workbook(Cursor::new(object.download()?), &filename)?;
+            search_xlsx(workbook, &filename, ctx)
+        }
     }
+}
 
-    Ok(())
+/// The delimiter for a delimited text file, or `None` to sniff it.
+fn resolve_delimiter(input_format: InputFormat, cli: &Cli) -> Option<u8> {
+    cli.delimiter.or(match input_format {
+        InputFormat::Tsv => Some(b'\t'),
+        InputFormat::Psv => Some(b'|'),
+        _ => None,
+    })
 }
 
-// ── Parquet ───────────────────────────────────────────────────────────────────
+/// How much of a file `sniff_delimiter` looks at.
+const SNIFF_BYTES: u64 = 16 * 1024;
 
-fn search_parquet(
-    path: &Path,
+/// Delimiters `sniff_delimiter` chooses between, preferred in this order.
+const SNIFF_CANDIDATES: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// Guess the delimiter of CSV-like text from its first lines: the candidate
+/// that appears the same number of times on every line wins, the more often
+/// the better; failing that, the one on every line most often. Delimiters
+/// inside `quote`s don't count. Defaults to ','.
+fn sniff_delimiter(sample: &[u8], quote: Option<u8>) -> u8 {
+    let mut lines: Vec<&[u8]> = sample
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .take(10)
+        .collect();
+    // The sample may end mid-line
+    if lines.len() > 1 && sample.len() as u64 >= SNIFF_BYTES {
+        lines.pop();
+    }
+
+    let count = |line: &[u8], delimiter: u8| {
+        let mut in_quotes = false;
+        line.iter()
+            .filter(|&&b| {
+                if Some(b) == quote {
+                    in_quotes = !in_quotes;
+                }
+                b == delimiter && !in_quotes
+            })
+            .count()
+    };
+
+    SNIFF_CANDIDATES
+        .iter()
+        .rev()
+        .filter_map(|&delimiter| {
+            let counts: Vec<usize> = lines.iter().map(|line| count(line, delimiter)).collect();
+            let fewest = *counts.iter().min()?;
+            let consistent = counts.iter().all(|&n| n == counts[0]);
+            (fewest > 0).then_some(((consistent, fewest), delimiter))
+        })
+        // max_by_key keeps the last maximum, hence the reversed candidates
+        .max_by_key(|&(score, _)| score)
+        .map_or(b',', |(_, delimiter)| delimiter)
+}
+
+/// --list-columns/--schema: describe a file instead of searching it.
+/// `count_rows` reads the rest of the file, so it's only called for --schema.
+fn describe_table(
     filename: &str,
-    pattern: &Regex,
-    cli: &Cli,
-    printer: &Printer,
-) -> Result<()> {
-    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-    use std::fs::File;
+    headers: &[String],
+    column_types: Option<&[DataType]>,
+    count_rows: impl FnOnce() -> Result<u64>,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    if ctx.cli.dry_run {
+        let columns = ctx
+            .resolve_columns(filename, headers)
+            .map(|indices| indices.iter().map(|&i| headers[i].as_str()).collect());
+        ctx.printer.print_plan(filename, columns);
+        return Ok(SearchStats {
+            files_searched: 1,
+            ..SearchStats::default()
+        });
+    }
+    ctx.check_schema(filename, headers);
+    if ctx.cli.search_headers {
+        return search_headers(filename, headers, ctx);
+    }
+    if ctx.cli.list_columns {
+        ctx.printer.print_columns(filename, headers, column_types);
+    } else if ctx.cli.schema {
+        // Remote and piped input have no size to report
+        let size = Path::new(filename)
+            .is_file()
+            .then(|| std::fs::metadata(filename).map(|m| m.len()).ok())
+            .flatten();
+        let rows = count_rows()?;
+        ctx.printer
+            .print_schema(filename, headers, column_types, rows, size);
+    }
+    Ok(SearchStats {
+        files_searched: 1,
+        ..SearchStats::default()
+    })
+}
 
-    let file = File::open(path)
-        .map_err(|e| anyhow::anyhow!("Failed to open Parquet '{}': {}", filename, e))?;
+/// --search-headers: match the pattern against a file's column names, and
+/// report the ones that match as -l/-L/-c/-q would report matching rows.
+fn search_headers(filename: &str, headers: &[String], ctx: &SearchContext) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let matching: Vec<&String> = headers
+        .iter()
+        .filter(|name| ctx.pattern.is_match(name) != cli.invert)
+        .collect();
 
-    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
-        .map_err(|e| anyhow::anyhow!("Failed to read Parquet '{}': {}", filename, e))?;
+    if cli.quiet {
+        // exit status only
+    } else if cli.files_with_matches {
+        if !matching.is_empty() {
+            ctx.printer.print_filename(filename);
+        }
+    } else if cli.files_without_matches {
+        if matching.is_empty() {
+            ctx.printer.print_filename(filename);
+        }
+    } else if cli.count {
+        if !matching.is_empty() || cli.include_zero {
+            ctx.printer.print_count(filename, matching.len());
+        }
+    } else if !matching.is_empty() && !cli.count_only_total {
+        let highlight = if cli.invert {
+            &NO_HIGHLIGHT
+        } else {
+            ctx.pattern
+        };
+        ctx.printer
+            .print_matching_headers(filename, &matching, highlight);
+    }
+    Ok(SearchStats {
+        files_searched: 1,
+        files_matched: usize::from(!matching.is_empty()),
+        matches: matching.len(),
+        ..SearchStats::default()
+    })
+}
 
-    let schema = builder.schema().clone();
-    let headers: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+/// Count the records left in a CSV reader, without decoding them.
+fn count_csv_records<R: Read>(rdr: &mut csv::Reader<R>) -> Result<u64> {
+    let mut record = csv::ByteRecord::new();
+    let mut rows = 0;
+    while rdr.read_byte_record(&mut record)? {
+        rows += 1;
+    }
+    Ok(rows)
+}
 
-    let col_indices = resolve_column_indices(&headers, &cli.columns);
+// ── shared search logic ───────────────────────────────────────────────────────
 
-    let reader = builder
-        .build()
-        .map_err(|e| anyhow::anyhow!("Failed to build Parquet reader for '{}': {}", filename, e))?;
+/// How many rows are searched between checks of the --timeout clock.
+const DEADLINE_CHECK_ROWS: usize = 1024;
 
-    let mut match_count = 0usize;
-    let mut global_row_num = 0usize;
-    let mut matched_rows: Vec<(usize, Vec<String>)> = Vec::new();
+/// Number of matching text rows buffered before they're written to the sink.
+const SINK_BATCH_ROWS: usize = 8192;
 
-    'outer: for batch_result in reader {
-        let batch = batch_result
-            .map_err(|e| anyhow::anyhow!("Parquet batch error in '{}': {}", filename, e))?;
+/// Encoded binary cells longer than this are shortened with an ellipsis when
+/// printed.
+const BINARY_DISPLAY_CHARS: usize = 64;
 
-        for row_idx in 0..batch.num_rows() {
-            global_row_num += 1;
+/// Matches nothing: rows rewritten by --replace are printed unhighlighted.
+static NO_HIGHLIGHT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[^\s\S]").expect("valid regex"));
 
-            let row: Vec<String> = batch
-                .columns()
-                .iter()
-                .map(|col| array_value_to_string(col.as_ref(), row_idx))
-                .collect();
+/// Which rows of one file match: the pattern in the searched columns, and
+/// every `--filter`, `--match`, `--in-file`/`--not-in-file`,
+/// `--is-null`/`--not-null`, `--expr` and `--query` condition. Unlike
+/// `FileSearch` it can be shared across threads.
+#[derive(Clone)]
+struct RowPredicate<'a> {
+    pattern: &'a Regex,
+    col_indices: Option<Vec<usize>>,
+    invert: bool,
+    /// `--filter` comparisons with the index of their column, or `None` when
+    /// this file has no such column (so no row can pass).
+    filters: Vec<(Option<usize>, &'a Filter)>,
+    /// `--query`, resolved against this file's headers.
+    query: Option<Expr<Option<usize>>>,
+    /// `--expr` conditions, resolved against this file's headers.
+    exprs: Vec<Comparison<Option<usize>>>,
+    /// `--match` patterns with the columns they apply to in this file; a row
+    /// passes when the pattern matches in any of them.
+    column_matches: Vec<(Vec<usize>, &'a Regex)>,
+    /// `--in-file`/`--not-in-file` sets with the index of their column, or
+    /// `None` when this file has no such column (so no row can pass).
+    column_sets: Vec<(Option<usize>, &'a ColumnSet)>,
+    /// `--exclude-pattern` with the columns it's tested against: those of
+    /// `--exclude-columns`, else the searched ones.
+    exclude: Option<(&'a Regex, Option<Vec<usize>>)>,
+    /// `--is-null` (`true`) and `--not-null` (`false`) columns, `None` when
+    /// this file has no such column (so no row can pass).
+    null_checks: Vec<(Option<usize>, bool)>,
+    /// With --format-numbers, the thousands separator numbers may be written
+    /// with or without.
+    thousands_sep: Option<&'a str>,
+    /// With --whole-row, what the searched cells are joined with to be
+    /// matched as one string.
+    row_separator: Option<String>,
+}
 
-            if row_matches(&row, pattern, &col_indices, cli.invert) {
-                match_count += 1;
+impl<'a> RowPredicate<'a> {
+    fn new(
+        filename: &str,
+        headers: &[String],
+        col_indices: Option<Vec<usize>>,
+        ctx: &'a SearchContext<'a>,
+    ) -> Self {
+        let cli = ctx.cli;
+        let filters = cli
+            .filters
+            .iter()
+            .map(|filter| {
+                let idx = find_column(headers, &filter.column, cli.ignore_case_columns);
+                if idx.is_none() {
+                    report!(
+                        "Warning: column '{}' in --filter '{}' not found in '{}'",
+                        filter.column,
+                        filter,
+                        filename
+                    );
+                }
+                (idx, filter)
+            })
+            .collect();
 
-                if !cli.count {
-                    if cli.only_matching {
-                        if matched_rows.is_empty() {
-                            printer.print_file_header(filename);
-                        }
-                        print_only_matching(&row, &headers, pattern, &col_indices);
-                    } else {
-                        matched_rows.push((global_row_num, row));
+        let column_sets = ctx
+            .column_sets
+            .iter()
+            .map(|set| {
+                let idx = find_column(headers, &set.column, cli.ignore_case_columns);
+                if idx.is_none() {
+                    report!(
+                        "Warning: column '{}' in --{} not found in '{}'",
+                        set.column,
+                        if set.member { "in-file" } else { "not-in-file" },
+                        filename
+                    );
+                }
+                (idx, set)
+            })
+            .collect();
+
+        let query = cli.query.as_ref().map(|query| {
+            query.bind(&mut |column| {
+                let idx = find_column(headers, column, cli.ignore_case_columns);
+                if idx.is_none() {
+                    report!(
+                        "Warning: column '{}' in --query not found in '{}'",
+                        column,
+                        filename
+                    );
+                }
+                idx
+            })
+        });
+
+        let exprs = cli
+            .exprs
+            .iter()
+            .map(|expr| {
+                expr.bind(&mut |column| {
+                    let idx = find_column(headers, column, cli.ignore_case_columns);
+                    if idx.is_none() {
+                        report!(
+                            "Warning: column '{}' in --expr not found in '{}'",
+                            column,
+                            filename
+                        );
                     }
+                    idx
+                })
+            })
+            .collect();
+
+        let null_checks = cli
+            .is_null
+            .iter()
+            .map(|column| (column, true))
+            .chain(cli.not_null.iter().map(|column| (column, false)))
+            .map(|(column, want_null)| {
+                let idx = find_column(headers, column, cli.ignore_case_columns);
+                if idx.is_none() {
+                    report!(
+                        "Warning: column '{}' in --{} not found in '{}'",
+                        column,
+                        if want_null { "is-null" } else { "not-null" },
+                        filename
+                    );
                 }
+                (idx, want_null)
+            })
+            .collect();
 
-                if let Some(max) = cli.max_count
-                    && match_count >= max
-                {
-                    break 'outer;
+        let column_matches = ctx
+            .column_matches
+            .iter()
+            .map(|m| {
+                let indices = resolve_column_token(headers, &m.column, cli.ignore_case_columns);
+                (indices, &m.pattern)
+            })
+            .collect();
+
+        let exclude = ctx.exclude_pattern.as_ref().map(|pattern| {
+            let indices = match &cli.exclude_columns {
+                Some(_) => {
+                    resolve_column_indices(headers, &cli.exclude_columns, cli.ignore_case_columns)
                 }
+                None => col_indices.clone(),
+            };
+            (pattern, indices)
+        });
+
+        Self {
+            pattern: ctx.pattern,
+            col_indices,
+            invert: cli.invert,
+            filters,
+            query,
+            exprs,
+            column_matches,
+            column_sets,
+            exclude,
+            null_checks,
+            thousands_sep: cli.format_numbers.then_some(cli.thousands_sep.as_str()),
+            row_separator: cli
+                .whole_row
+                .then(|| (cli.delimiter.unwrap_or(b',') as char).to_string()),
+        }
+    }
+
+    /// With --whole-row, join cells with a delimited text file's own
+    /// delimiter, once it's known, as its rows are printed with.
+    fn set_delimiter(&mut self, delimiter: u8) {
+        if let Some(separator) = &mut self.row_separator {
+            *separator = (delimiter as char).to_string();
+        }
+    }
+
+    /// Whether rows match on the pattern alone, with no other conditions.
+    fn is_pattern_only(&self) -> bool {
+        !self.invert
+            && self.filters.is_empty()
+            && self.query.is_none()
+            && self.exprs.is_empty()
+            && self.column_matches.is_empty()
+            && self.column_sets.is_empty()
+            && self.exclude.is_none()
+            && self.null_checks.is_empty()
+            && self.thousands_sep.is_none()
+    }
+
+    /// Whether `row` matches. `is_null` tells typed nulls apart (see
+    /// `FileSearch::push_row_with_nulls`); `column_types` informs `--query`.
+    fn matches(
+        &self,
+        row: &[String],
+        is_null: Option<&dyn Fn(usize) -> bool>,
+        column_types: Option<&[DataType]>,
+    ) -> bool {
+        let pattern_matches = match (&self.row_separator, self.thousands_sep) {
+            (Some(separator), _) => {
+                let joined = joined_row(row, &self.col_indices, separator);
+                self.pattern.is_match(&joined) != self.invert
             }
+            (None, None) => row_matches(row, self.pattern, &self.col_indices, self.invert),
+            (None, Some(sep)) => {
+                // A number also matches in its grouped or plain form
+                let cell_matches = |cell: &String| {
+                    self.pattern.is_match(cell)
+                        || group_thousands(cell, sep)
+                            .or_else(|| ungroup_thousands(cell, sep))
+                            .is_some_and(|other| self.pattern.is_match(&other))
+                };
+                let any_match = match &self.col_indices {
+                    Some(indices) => indices.iter().filter_map(|&i| row.get(i)).any(cell_matches),
+                    None => row.iter().any(cell_matches),
+                };
+                any_match != self.invert
+            }
+        };
+        pattern_matches && !self.is_excluded(row) && self.passes_filters(row, is_null, column_types)
+    }
+
+    /// Whether --exclude-pattern matches in `row`, dropping it.
+    fn is_excluded(&self, row: &[String]) -> bool {
+        let Some((pattern, indices)) = &self.exclude else {
+            return false;
+        };
+        match &self.row_separator {
+            Some(separator) => pattern.is_match(&joined_row(row, indices, separator)),
+            None => row_matches(row, pattern, indices, false),
         }
     }
 
-    if cli.count && match_count > 0 {
-        printer.print_count(filename, match_count);
-    } else if !cli.only_matching {
-        emit_matches(filename, &headers, &matched_rows, pattern, cli, printer);
+    /// Whether `row` satisfies every `--filter` comparison, `--match`
+    /// pattern, `--in-file`/`--not-in-file` set, `--is-null`/`--not-null`
+    /// check and `--expr`, and `--query`.
+    fn passes_filters(
+        &self,
+        row: &[String],
+        is_null: Option<&dyn Fn(usize) -> bool>,
+        column_types: Option<&[DataType]>,
+    ) -> bool {
+        let cell_is_null = |i: usize| match is_null {
+            Some(is_null) => is_null(i),
+            None => row.get(i).is_none_or(|cell| cell.is_empty()),
+        };
+        self.filters.iter().all(|(idx, filter)| {
+            idx.and_then(|i| row.get(i))
+                .is_some_and(|cell| filter.matches(cell))
+        }) && self.column_matches.iter().all(|(indices, pattern)| {
+            indices
+                .iter()
+                .filter_map(|&i| row.get(i))
+                .any(|cell| pattern.is_match(cell))
+        }) && self.column_sets.iter().all(|(idx, set)| {
+            idx.and_then(|i| row.get(i))
+                .is_some_and(|cell| set.matches(cell))
+        }) && self
+            .null_checks
+            .iter()
+            .all(|&(idx, want_null)| idx.is_some_and(|i| cell_is_null(i) == want_null))
+            && self.exprs.iter().all(|expr| expr.eval(row, &cell_is_null))
+            && self
+                .query
+                .as_ref()
+                .is_none_or(|query| query.eval(row, &cell_is_null, column_types) == Some(true))
     }
+}
 
-    Ok(())
+/// Per-file search state shared by every input format. Readers feed rows in
+/// file order through `push_row`, which applies the match predicate, tracks
+/// context rows, and decides when reading can stop.
+struct FileSearch<'a> {
+    filename: &'a str,
+    headers: Vec<String>,
+    col_indices: Option<Vec<usize>>,
+    /// Arrow types of each column, for formats that carry them (Parquet).
+    column_types: Option<Vec<DataType>>,
+    /// Field separator used when printing rows in CSV mode.
+    delimiter: char,
+    pattern: &'a Regex,
+    /// What printed matching rows highlight: the pattern, unless --replace
+    /// has already rewritten what it matched.
+    highlight: &'a Regex,
+    cli: &'a Cli,
+    printer: &'a Printer,
+    sink: Option<&'a RefCell<OutputSink>>,
+    /// Whether matches are written to the sink by the reader, as whole arrow
+    /// batches, rather than row by row from `push_row`.
+    sink_takes_batches: bool,
+    /// Matching rows waiting to be written to the sink.
+    sink_rows: Vec<Vec<String>>,
+    /// For a `Searcher`, where matching rows go.
+    collected: Option<Collector<'a>>,
+    /// With --merge-tables, where the rows go instead of this file's own table.
+    merged: Option<&'a RefCell<MergedTable>>,
+    /// Whether matching rows are rendered to stdout.
+    print_rows: bool,
+    match_count: usize,
+    /// Rows read so far, matching or not.
+    rows_scanned: usize,
+    /// Whether rows are printed as soon as they're found (CSV output) rather
+    /// than collected in `output_rows` for `emit_matches`.
+    streaming: bool,
+    /// Row number of the last row streamed, to place group separators.
+    last_streamed: Option<usize>,
+    output_rows: Vec<OutputRow>,
+    before_context: usize,
+    after_context: usize,
+    /// Recent non-matching rows not yet emitted, kept for before-context.
+    pending_before: VecDeque<(usize, Vec<String>, Option<Vec<bool>>)>,
+    /// Rows still to emit as after-context of the most recent match.
+    after_remaining: usize,
+    /// Decides which rows match.
+    predicate: RowPredicate<'a>,
+    /// `--unique-by` key columns; `None` keys on the whole row.
+    unique_columns: Option<Vec<usize>>,
+    /// With --show-columns, the columns printed, in order.
+    show_columns: Option<Vec<usize>>,
+    /// With --unique/--unique-by, the keys of the matches kept so far.
+    seen_keys: Option<HashSet<Vec<String>>>,
+    /// The --count-distinct/--group-by column (`None` when this file lacks
+    /// it) and how many matching rows had each of its values.
+    value_counts: Option<(Option<usize>, BTreeMap<String, usize>)>,
+    /// Run-wide union of the --count-distinct values, for --total.
+    distinct_values: &'a RefCell<HashSet<String>>,
+    /// With --count-by-column, how many matching cells each column had.
+    column_hits: Option<Vec<usize>>,
+    /// With --show-location, where rows sit in the file (set by readers
+    /// that know).
+    locator: Option<Locator>,
+    /// Most matches to take from this file: -m, or what's left of the
+    /// --max-total budget if that's less.
+    max_count: Option<usize>,
+    /// With --timeout, when to give up on this file.
+    deadline: Option<Instant>,
+    /// Whether the --timeout deadline passed before the file was finished.
+    timed_out: bool,
+    /// With --sample, chooses which matches `output_rows` keeps.
+    sampler: Option<Sampler>,
 }
 
-// ── helpers ───────────────────────────────────────────────────────────────────
+/// --sample: reservoir sampling of a file's matches, with its own random
+/// number generator (SplitMix64) so a --seed always draws the same rows.
+struct Sampler {
+    size: usize,
+    state: u64,
+}
 
-fn resolve_column_indices(headers: &[String], filter: &Option<Vec<String>>) -> Option<Vec<usize>> {
-    filter.as_ref().map(|cols| {
-        cols.iter()
-            .filter_map(|col_name| {
-                let idx = headers.iter().position(|h| h == col_name);
-                if idx.is_none() {
-                    eprintln!("Warning: column '{}' not found", col_name);
-                }
-                idx
-            })
-            .collect()
-    })
+impl Sampler {
+    fn new(size: usize, seed: Option<u64>) -> Self {
+        let state = seed.unwrap_or_else(|| {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos() as u64)
+                .unwrap_or_default();
+            nanos ^ u64::from(std::process::id()).rotate_left(32)
+        });
+        Self { size, state }
+    }
+
+    /// Where the `seen`th match goes in a reservoir of rows: it's kept with
+    /// probability size/seen, replacing a random one once the reservoir is
+    /// full, so every match is equally likely to end up in it.
+    fn slot(&mut self, seen: usize) -> Option<usize> {
+        if seen <= self.size {
+            return Some(seen - 1);
+        }
+        let pick = self.below(seen);
+        (pick < self.size).then_some(pick)
+    }
+
+    /// A random number below `n`.
+    fn below(&mut self, n: usize) -> usize {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        ((u128::from(z) * n as u128) >> 64) as usize
+    }
 }
 
-pub fn row_matches(
-    row: &[String],
-    pattern: &Regex,
-    col_indices: &Option<Vec<usize>>,
-    invert: bool,
-) -> bool {
-    let mut cells_to_check: Box<dyn Iterator<Item = &String>> = match col_indices {
-        Some(indices) => Box::new(indices.iter().filter_map(|&i| row.get(i))),
-        None => Box::new(row.iter()),
-    };
+/// Where a `Searcher`'s matching rows go, and the headers they share.
+type Collector<'a> = (&'a RefCell<Vec<MatchRow>>, Arc<[String]>);
 
-    let any_match = cells_to_check.any(|cell| pattern.is_match(cell));
-    if invert { !any_match } else { any_match }
+/// Where --show-location places a row in its file.
+enum Locator {
+    /// Parquet: the 1-based number of the first row in each row group.
+    RowGroups(Vec<usize>),
+    /// CSV: the byte offsets of the records read last, as many as
+    /// before-context may still print, and of the rows printed.
+    ByteOffsets {
+        recent: VecDeque<(usize, u64)>,
+        printed: HashMap<usize, u64>,
+    },
 }
 
-fn print_only_matching(
-    row: &[String],
-    headers: &[String],
-    pattern: &Regex,
-    col_indices: &Option<Vec<usize>>,
-) {
-    let indices_to_check: Vec<usize> = match col_indices {
-        Some(indices) => indices.clone(),
-        None => (0..row.len()).collect(),
-    };
+impl Locator {
+    /// Names of the location columns.
+    fn headers(&self) -> &'static [&'static str] {
+        match self {
+            Locator::RowGroups(_) => &["_row_group", "_row_in_group"],
+            Locator::ByteOffsets { .. } => &["_byte_offset"],
+        }
+    }
 
-    for idx in indices_to_check {
-        if let Some(cell) = row.get(idx)
-            && pattern.is_match(cell)
-        {
-            let col_name = headers.get(idx).map(|s| s.as_str()).unwrap_or("?");
-            println!("  [{}] {}", col_name, cell);
+    /// The location columns of row `row_num`, blank when it's unknown.
+    fn cells(&self, row_num: usize) -> Vec<String> {
+        match self {
+            Locator::RowGroups(starts) => {
+                let group = starts
+                    .partition_point(|&start| start <= row_num)
+                    .saturating_sub(1);
+                let start = starts.get(group).copied().unwrap_or(1);
+                vec![group.to_string(), row_num.saturating_sub(start).to_string()]
+            }
+            Locator::ByteOffsets { printed, .. } => {
+                vec![
+                    printed
+                        .get(&row_num)
+                        .map(u64::to_string)
+                        .unwrap_or_default(),
+                ]
+            }
         }
     }
 }
 
-fn array_value_to_string(array: &dyn arrow::array::Array, index: usize) -> String {
-    use arrow::array::*;
-    use arrow::datatypes::DataType;
+impl<'a> FileSearch<'a> {
+    fn new(
+        filename: &'a str,
+        headers: Vec<String>,
+        col_indices: Option<Vec<usize>>,
+        ctx: &'a SearchContext<'a>,
+    ) -> Self {
+        let cli = ctx.cli;
+        // Context is meaningless when rows aren't printed.
+        let print_rows = (cli.output_file.is_none() || cli.also_print)
+            && ctx.collected.is_none()
+            && !cli.stops_at_first_match()
+            && !cli.aggregates();
+        let context_allowed = print_rows && !(cli.count || cli.lists_cells());
+        // Only CSV output can be printed row by row; the other formats need
+        // every row up front (column widths, JSON arrays, ...).
+        // Sorting and sampling need every row up front too.
+        let streaming = context_allowed
+            && cli.format == OutputFormat::Csv
+            && cli.sort.is_empty()
+            && cli.sample.is_none();
+        let context = |n: Option<usize>| {
+            if context_allowed {
+                n.or(cli.context).unwrap_or(0)
+            } else {
+                0
+            }
+        };
 
-    if array.is_null(index) {
-        return String::from("NULL");
+        ctx.check_schema(filename, &headers);
+        let predicate = RowPredicate::new(filename, &headers, col_indices.clone(), ctx);
+
+        let unique_columns =
+            resolve_column_indices(&headers, &cli.unique_by, cli.ignore_case_columns);
+        let show_columns =
+            resolve_column_indices(&headers, &cli.show_columns, cli.ignore_case_columns);
+        // Keying every row on no columns at all would keep just the first.
+        let unique_by_nothing = unique_columns.as_ref().is_some_and(Vec::is_empty);
+        if unique_by_nothing {
+            report!(
+                "Warning: no --unique-by column found in '{}'; keeping duplicate rows",
+                filename
+            );
+        }
+        let seen_keys =
+            (cli.unique || (cli.unique_by.is_some() && !unique_by_nothing)).then(HashSet::new);
+        // resolve_column_token warns about missing columns
+        let value_counts = cli
+            .count_distinct
+            .as_ref()
+            .or(cli.group_by.as_ref())
+            .map(|column| {
+                let idx = resolve_column_token(&headers, column, cli.ignore_case_columns)
+                    .first()
+                    .copied();
+                (idx, BTreeMap::new())
+            });
+        let column_hits = cli.count_by_column.then(|| vec![0; headers.len()]);
+        // Nothing to highlight under --replace, or with only --query. With
+        // --whole-row, the printed row is only what was matched when every
+        // column was searched.
+        let highlight: &Regex = if cli.replace.is_some()
+            || ctx.pattern.as_str().is_empty()
+            || (cli.whole_row && (col_indices.is_some() || cli.show_columns.is_some()))
+        {
+            &NO_HIGHLIGHT
+        } else {
+            ctx.pattern
+        };
+        let collected = ctx
+            .collected
+            .as_ref()
+            .map(|rows| (rows, Arc::from(headers.as_slice())));
+
+        Self {
+            filename,
+            headers,
+            col_indices,
+            column_types: None,
+            delimiter: cli.delimiter.unwrap_or(b',') as char,
+            pattern: ctx.pattern,
+            highlight,
+            cli,
+            printer: ctx.printer,
+            sink: ctx.sink.as_ref(),
+            sink_takes_batches: false,
+            sink_rows: Vec::new(),
+            collected,
+            merged: ctx.merged.as_ref(),
+            print_rows,
+            match_count: 0,
+            rows_scanned: 0,
+            streaming,
+            last_streamed: None,
+            output_rows: Vec::new(),
+            before_context: context(cli.before_context),
+            after_context: context(cli.after_context),
+            pending_before: VecDeque::new(),
+            after_remaining: 0,
+            predicate,
+            unique_columns,
+            show_columns,
+            seen_keys,
+            value_counts,
+            distinct_values: &ctx.distinct_values,
+            column_hits,
+            locator: None,
+            max_count: match (cli.max_count, ctx.match_budget.get()) {
+                (Some(max), Some(budget)) => Some(max.min(budget)),
+                (max, budget) => max.or(budget),
+            },
+            deadline: cli
+                .timeout
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+            timed_out: false,
+            sampler: cli.sample.map(|size| Sampler::new(size, cli.seed)),
+        }
     }
 
-    match array.data_type() {
-        DataType::Utf8 => array
-            .as_any()
-            .downcast_ref::<StringArray>()
-            .map(|a| a.value(index).to_string())
-            .unwrap_or_default(),
-        DataType::LargeUtf8 => array
-            .as_any()
-            .downcast_ref::<LargeStringArray>()
-            .map(|a| a.value(index).to_string())
-            .unwrap_or_default(),
-        DataType::Int8 => array
-            .as_any()
-            .downcast_ref::<Int8Array>()
-            .map(|a| a.value(index).to_string())
-            .unwrap_or_default(),
-        DataType::Int16 => array
-            .as_any()
-            .downcast_ref::<Int16Array>()
-            .map(|a| a.value(index).to_string())
-            .unwrap_or_default(),
-        DataType::Int32 => array
-            .as_any()
-            .downcast_ref::<Int32Array>()
-            .map(|a| a.value(index).to_string())
-            .unwrap_or_default(),
-        DataType::Int64 => array
-            .as_any()
-            .downcast_ref::<Int64Array>()
-            .map(|a| a.value(index).to_string())
-            .unwrap_or_default(),
+    /// The field separator of a delimited text file, once known: what its rows
+    /// are printed with, and joined with for --whole-row.
+    fn set_delimiter(&mut self, delimiter: u8) {
+        self.delimiter = delimiter as char;
+        self.predicate.set_delimiter(delimiter);
+    }
+
+    /// --show-location: note that the next row, `row_num`, starts at byte
+    /// `offset` of a CSV file.
+    fn note_offset(&mut self, row_num: usize, offset: u64) {
+        if let Some(Locator::ByteOffsets { recent, .. }) = &mut self.locator {
+            if recent.len() > self.before_context {
+                recent.pop_front();
+            }
+            recent.push_back((row_num, offset));
+        }
+    }
+
+    fn max_count_reached(&self) -> bool {
+        self.max_count.is_some_and(|max| self.match_count >= max)
+    }
+
+    /// Whether -c's count is all that's wanted from this file, and rows
+    /// match on the pattern alone, so a match can be counted without reading
+    /// its row.
+    fn counts_alone(&self) -> bool {
+        self.cli.count
+            && self.sink.is_none()
+            && self.collected.is_none()
+            && self.max_count.is_none()
+            && self.seen_keys.is_none()
+            && self.value_counts.is_none()
+            && self.column_hits.is_none()
+            && self.predicate.is_pattern_only()
+    }
+
+    /// Whether --timeout has run out for this file.
+    fn check_deadline(&mut self) -> bool {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.timed_out = true;
+        }
+        self.timed_out
+    }
+
+    /// Process the next row of a text format, where empty cells are null.
+    /// Returns `false` once no further rows are needed.
+    fn push_row(&mut self, row_num: usize, row: Vec<String>) -> Result<bool> {
+        self.push_row_with_nulls(row_num, row, None)
+    }
+
+    /// Like `push_row`, but `is_null` says which cells (by column index) are
+    /// null, for formats that know better than "empty".
+    fn push_row_with_nulls(
+        &mut self,
+        row_num: usize,
+        mut row: Vec<String>,
+        is_null: Option<&dyn Fn(usize) -> bool>,
+    ) -> Result<bool> {
+        self.rows_scanned += 1;
+        // Reading the clock for every row would slow the search down
+        if self.rows_scanned.is_multiple_of(DEADLINE_CHECK_ROWS) {
+            self.check_deadline();
+        }
+        if self.timed_out {
+            return Ok(false);
+        }
+
+        // Past the max-count, only trailing context is still wanted.
+        if self.max_count_reached() {
+            return Ok(self.push_after_context(row_num, row, is_null));
+        }
+
+        if !self
+            .predicate
+            .matches(&row, is_null, self.column_types.as_deref())
+        {
+            if self.after_remaining > 0 {
+                self.push_after_context(row_num, row, is_null);
+            } else if self.before_context > 0 {
+                if self.pending_before.len() == self.before_context {
+                    self.pending_before.pop_front();
+                }
+                let nulls = null_mask(&row, is_null);
+                self.pending_before.push_back((row_num, row, nulls));
+            }
+            return Ok(true);
+        }
+
+        if !self.is_first_of_its_kind(&row) {
+            return Ok(true);
+        }
+
+        self.match_count += 1;
+        if self.cli.stops_at_first_match() {
+            // The first match settles whether the file is listed.
+            return Ok(false);
+        }
+
+        if let Some((Some(idx), counts)) = &mut self.value_counts
+            && let Some(value) = row.get(*idx)
+            && !(self.cli.skip_null && is_null_cell(value, &self.cli.null_text))
+        {
+            *counts.entry(value.clone()).or_default() += 1;
+        }
+        if let Some(hits) = &mut self.column_hits {
+            for idx in only_matching_cells(&row, self.pattern, &self.col_indices, false) {
+                if let Some(hit) = hits.get_mut(idx) {
+                    *hit += 1;
+                }
+            }
+        }
+
+        if let Some(replacement) = &self.cli.replace {
+            replace_matches(&mut row, self.pattern, &self.col_indices, replacement);
+        }
+
+        if self.sink.is_some() && !self.sink_takes_batches {
+            self.sink_rows.push(row.clone());
+            if self.sink_rows.len() >= SINK_BATCH_ROWS {
+                self.flush_sink()?;
+            }
+        }
+
+        if let Some((rows, headers)) = &self.collected {
+            rows.borrow_mut().push(MatchRow {
+                file: self.filename.to_string(),
+                row_num,
+                headers: Arc::clone(headers),
+                cells: row.clone(),
+            });
+        }
+
+        if self.cli.count || !self.print_rows {
+            // only the tally is needed
+        } else if self.cli.lists_cells() {
+            // listing cells bypasses the buffering path
+            if self.match_count == 1 {
+                self.printer.print_file_header(self.filename);
+            }
+            print_only_matching(
+                &row,
+                &self.headers,
+                self.pattern,
+                &self.col_indices,
+                self.cli.invert || self.cli.only_non_matching,
+            );
+        } else {
+            for (row_num, cells, nulls) in std::mem::take(&mut self.pending_before) {
+                self.output_row(OutputRow {
+                    row_num,
+                    cells,
+                    is_context: true,
+                    nulls,
+                });
+            }
+            let nulls = null_mask(&row, is_null);
+            self.output_row(OutputRow {
+                row_num,
+                cells: row,
+                is_context: false,
+                nulls,
+            });
+            self.after_remaining = self.after_context;
+        }
+
+        Ok(!self.max_count_reached() || self.after_remaining > 0)
+    }
+
+    /// Take in the rows a worker thread matched in one chunk of the file
+    /// (see `search_chunks_parallel`), as though each had been pushed in
+    /// turn, adding `offset` to their row numbers. Returns `false` once no
+    /// further rows are needed, as `push_row` does.
+    fn push_chunk_matches(&mut self, found: ChunkMatches, offset: usize) -> Result<bool> {
+        let mut pushed = 0;
+        let mut keep_going = true;
+        for (rows, batch) in found.batches {
+            let mut accepted: Vec<u32> = Vec::new();
+            for (i, (row_num, row, nulls)) in rows.into_iter().enumerate() {
+                let matches_before = self.match_count;
+                let is_null = |col: usize| nulls.as_ref().is_some_and(|nulls| nulls[col]);
+                let is_null: Option<&dyn Fn(usize) -> bool> = match nulls {
+                    Some(_) => Some(&is_null),
+                    None => None,
+                };
+                keep_going = self.push_row_with_nulls(offset + row_num, row, is_null)?;
+                pushed += 1;
+                // --unique may still drop a row
+                if self.match_count > matches_before {
+                    accepted.push(i as u32);
+                }
+                if !keep_going {
+                    break;
+                }
+            }
+
+            if let Some(batch) = batch
+                && let Some(sink) = self.sink
+                && !accepted.is_empty()
+            {
+                let indices = arrow::array::UInt32Array::from(accepted);
+                let matched = arrow::compute::take_record_batch(&batch, &indices)?;
+                sink.borrow_mut().write_batch(self.filename, &matched)?;
+            }
+            if !keep_going {
+                break;
+            }
+        }
+        // The rows the worker ruled out were scanned too.
+        self.rows_scanned += found.rows_scanned - pushed;
+        Ok(keep_going)
+    }
+
+    /// Under --unique/--unique-by, whether no earlier match had the same key.
+    /// Always true otherwise.
+    fn is_first_of_its_kind(&mut self, row: &[String]) -> bool {
+        let Some(seen) = &mut self.seen_keys else {
+            return true;
+        };
+        let key = match &self.unique_columns {
+            Some(indices) => indices
+                .iter()
+                .map(|&i| row.get(i).cloned().unwrap_or_default())
+                .collect(),
+            None => row.to_vec(),
+        };
+        seen.insert(key)
+    }
+
+    /// Emit `row` as after-context if the last match still wants some.
+    /// Returns whether the row was consumed.
+    fn push_after_context(
+        &mut self,
+        row_num: usize,
+        row: Vec<String>,
+        is_null: Option<&dyn Fn(usize) -> bool>,
+    ) -> bool {
+        if self.after_remaining == 0 {
+            return false;
+        }
+        self.after_remaining -= 1;
+        let nulls = null_mask(&row, is_null);
+        self.output_row(OutputRow {
+            row_num,
+            cells: row,
+            is_context: true,
+            nulls,
+        });
+        true
+    }
+
+    /// Print a match or context row straight away when streaming, otherwise
+    /// keep it for `emit_matches`.
+    fn output_row(&mut self, row: OutputRow) {
+        if let Some(Locator::ByteOffsets { recent, printed }) = &mut self.locator
+            && let Some(&(_, offset)) = recent.iter().find(|(n, _)| *n == row.row_num)
+        {
+            printed.insert(row.row_num, offset);
+        }
+        if let Some(sampler) = &mut self.sampler {
+            match sampler.slot(self.match_count) {
+                Some(slot) if slot < self.output_rows.len() => self.output_rows[slot] = row,
+                Some(_) => self.output_rows.push(row),
+                None => {}
+            }
+            return;
+        }
+        if !self.streaming {
+            self.output_rows.push(row);
+            return;
+        }
+
+        let max_columns = self.shown_columns(self.headers.len());
+        let headers = self.display_headers(max_columns);
+        if self.last_streamed.is_none() {
+            self.print_csv_heading(&headers);
+        }
+        let mut cells = self.display_cells(&row.cells, max_columns);
+        if let Some(locator) = &self.locator {
+            cells.to_mut().extend(locator.cells(row.row_num));
+        }
+        self.print_csv_row(
+            row.row_num,
+            &cells,
+            &headers,
+            row.is_context,
+            self.last_streamed,
+        );
+        self.last_streamed = Some(row.row_num);
+    }
+
+    /// CSV mode: print what precedes a file's rows: the file name as a
+    /// heading (unless --no-heading) and, with --with-headers, the headers.
+    fn print_csv_heading(&self, headers: &[String]) {
+        if !self.cli.no_heading {
+            self.printer.print_file_header(self.filename);
+        }
+        if self.cli.with_headers {
+            if self.cli.no_heading {
+                self.printer.print_row_prefix(self.filename, ':');
+            }
+            self.printer.print_headers(headers, self.delimiter);
+        }
+    }
+
+    /// CSV mode: print one row, preceded by a group separator when it doesn't
+    /// follow on from the previous row printed, and by its file name with
+    /// --no-heading. `headers` are the printed columns' names.
+    fn print_csv_row(
+        &self,
+        row_num: usize,
+        cells: &[String],
+        headers: &[String],
+        is_context: bool,
+        prev_row_num: Option<usize>,
+    ) {
+        // Like grep, mark breaks between disjoint context groups.
+        if self.cli.has_context() && prev_row_num.is_some_and(|prev| row_num != prev + 1) {
+            self.printer.print_group_separator();
+        }
+        if self.cli.no_heading {
+            self.printer
+                .print_row_prefix(self.filename, if is_context { '-' } else { ':' });
+        }
+        if is_context {
+            self.printer.print_context(row_num, cells, self.delimiter);
+        } else {
+            self.printer
+                .print_match(row_num, cells, headers, self.highlight, self.delimiter);
+        }
+    }
+
+    /// Write buffered matching rows to the `--output-file` sink.
+    fn flush_sink(&mut self) -> Result<()> {
+        if let Some(sink) = self.sink
+            && !self.sink_rows.is_empty()
+        {
+            sink.borrow_mut()
+                .write_rows(self.filename, &self.headers, &self.sink_rows)?;
+            self.sink_rows.clear();
+        }
+        Ok(())
+    }
+
+    /// Flush and print this file's results.
+    fn finish(mut self) -> Result<SearchStats> {
+        self.flush_sink()?;
+        if self.timed_out {
+            report!(
+                "Warning: '{}': stopped after {} rows when --timeout {}s ran out; the rest wasn't searched",
+                self.filename,
+                self.rows_scanned,
+                self.cli.timeout.unwrap_or_default()
+            );
+        }
+
+        if self.cli.quiet {
+            // exit status only
+        } else if self.cli.files_with_matches {
+            if self.match_count > 0 {
+                self.printer.print_filename(self.filename);
+            }
+        } else if self.cli.files_without_matches {
+            if self.match_count == 0 {
+                self.printer.print_filename(self.filename);
+            }
+        } else if self.cli.count {
+            if let Some(count) = self.printed_count() {
+                self.printer.print_count(self.filename, count);
+            }
+        } else if let Some(hits) = &self.column_hits {
+            if self.match_count > 0 {
+                self.printer
+                    .print_histogram(self.filename, &column_tally(&self.headers, hits));
+            }
+        } else if let Some((_, counts)) = &self.value_counts {
+            if self.cli.total || self.cli.count_only_total {
+                let mut all = self.distinct_values.borrow_mut();
+                all.extend(counts.keys().cloned());
+            }
+            if self.match_count > 0 && !self.cli.count_only_total {
+                if self.cli.group_by.is_some() {
+                    self.printer
+                        .print_histogram(self.filename, &histogram(counts, self.cli));
+                } else if self.cli.list_distinct {
+                    self.printer.print_values(self.filename, counts.keys());
+                } else {
+                    self.printer.print_count(self.filename, counts.len());
+                }
+            }
+        } else if self.streaming {
+            if self.last_streamed.is_some() && !self.cli.no_heading {
+                self.printer.print_separator();
+            }
+        } else if self.print_rows && !self.cli.lists_cells() {
+            if self.sampler.is_some() {
+                self.output_rows.sort_by_key(|row| row.row_num);
+            }
+            // Merged rows are sorted together, once every file is in.
+            if !self.cli.sort.is_empty() && self.merged.is_none() {
+                sort_rows(
+                    &mut self.output_rows,
+                    &self.headers,
+                    self.column_types.as_deref(),
+                    &self.cli.sort,
+                    &self.cli.null_text,
+                    self.cli.ignore_case_columns,
+                    self.filename,
+                );
+            }
+            self.emit_matches();
+        }
+        Ok(SearchStats {
+            files_searched: 1,
+            files_matched: usize::from(self.match_count > 0),
+            rows_scanned: self.rows_scanned,
+            matches: self.match_count,
+            files_failed: usize::from(self.timed_out),
+        })
+    }
+
+    /// The `-c` count to print for this file, if any: files without matches
+    /// only get one with --include-zero.
+    fn printed_count(&self) -> Option<usize> {
+        let shown = self.match_count > 0 || self.cli.include_zero;
+        (shown && !self.cli.count_only_total).then_some(self.match_count)
+    }
+
+    /// Headers and rows as printed: just the `--show-columns`, long cells
+    /// shortened (binary cells, and every cell past `--max-cell-width`) and
+    /// columns beyond `--max-columns` collapsed into one `…` column, and the
+    /// --show-location columns after them. Matching and `--output-file`
+    /// output always see the full rows.
+    fn display_rows(&self) -> (Cow<'_, [String]>, Cow<'_, [OutputRow]>) {
+        let widest = self.output_rows.iter().map(|row| row.cells.len()).max();
+        let max_columns = self.shown_columns(widest.unwrap_or(0));
+        let rows = if self.is_binary_column(None)
+            || self.cli.format_numbers
+            || self.cli.max_cell_width.is_some()
+            || max_columns.is_some()
+            || self.show_columns.is_some()
+            || self.locator.is_some()
+        {
+            Cow::Owned(
+                self.output_rows
+                    .iter()
+                    .map(|row| {
+                        let mut cells = self.display_cells(&row.cells, max_columns).into_owned();
+                        if let Some(locator) = &self.locator {
+                            cells.extend(locator.cells(row.row_num));
+                        }
+                        OutputRow {
+                            cells,
+                            ..row.clone()
+                        }
+                    })
+                    .collect(),
+            )
+        } else {
+            Cow::Borrowed(self.output_rows.as_slice())
+        };
+        (self.display_headers(max_columns), rows)
+    }
+
+    /// Types of the printed columns; the `--max-columns` '…' column has none.
+    fn display_types(&self) -> Option<Cow<'_, [DataType]>> {
+        let widest = self.output_rows.iter().map(|row| row.cells.len()).max();
+        let max_columns = self.shown_columns(widest.unwrap_or(0));
+        let types = self.shown_types()?;
+        let shown = types.len().min(max_columns.unwrap_or(usize::MAX));
+        Some(match types {
+            Cow::Borrowed(types) => Cow::Borrowed(&types[..shown]),
+            Cow::Owned(mut types) => {
+                types.truncate(shown);
+                Cow::Owned(types)
+            }
+        })
+    }
+
+    /// The `--max-columns` limit, if rows `widest` cells wide (or the
+    /// `--show-columns`) exceed it.
+    fn shown_columns(&self, widest: usize) -> Option<usize> {
+        let widest = self.show_columns.as_ref().map_or(widest, Vec::len);
+        self.cli.max_columns.filter(|&n| widest > n)
+    }
+
+    /// Types of the `--show-columns`, or of all columns.
+    fn shown_types(&self) -> Option<Cow<'_, [DataType]>> {
+        let types = self.column_types.as_deref()?;
+        Some(match &self.show_columns {
+            Some(indices) => indices
+                .iter()
+                .filter_map(|&i| types.get(i).cloned())
+                .collect(),
+            None => Cow::Borrowed(types),
+        })
+    }
+
+    /// The cells of `cells` that are printed, with their column indices.
+    fn shown_cells<'r>(&self, cells: &'r [String]) -> Vec<(usize, &'r String)> {
+        static EMPTY: String = String::new();
+        match &self.show_columns {
+            Some(indices) => indices
+                .iter()
+                .map(|&i| (i, cells.get(i).unwrap_or(&EMPTY)))
+                .collect(),
+            None => cells.iter().enumerate().collect(),
+        }
+    }
+
+    /// Whether column `idx` (or, for `None`, any column) holds binary values.
+    fn is_binary_column(&self, idx: Option<usize>) -> bool {
+        let is_binary = |dt: &DataType| {
+            matches!(
+                dt,
+                DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_)
+            )
+        };
+        let types = self.column_types.as_deref().unwrap_or_default();
+        match idx {
+            Some(idx) => types.get(idx).is_some_and(is_binary),
+            None => types.iter().any(is_binary),
+        }
+    }
+
+    /// With --format-numbers, `cell` grouped in thousands, if it's a number
+    /// (in a numeric column, for typed formats).
+    fn group_number(&self, idx: usize, cell: &str) -> Option<String> {
+        if !self.cli.format_numbers {
+            return None;
+        }
+        let numeric = match self.column_types.as_deref() {
+            Some(types) => types.get(idx).is_some_and(|dt| {
+                dt.is_integer()
+                    || dt.is_floating()
+                    || matches!(dt, DataType::Decimal128(..) | DataType::Decimal256(..))
+            }),
+            None => true,
+        };
+        numeric
+            .then(|| group_thousands(cell, &self.cli.thousands_sep))
+            .flatten()
+    }
+
+    fn display_headers(&self, max_columns: Option<usize>) -> Cow<'_, [String]> {
+        if max_columns.is_none() && self.show_columns.is_none() && self.locator.is_none() {
+            return Cow::Borrowed(self.headers.as_slice());
+        }
+        let mut headers: Vec<String> = self
+            .shown_cells(&self.headers)
+            .into_iter()
+            .take(max_columns.unwrap_or(usize::MAX))
+            .map(|(_, header)| header.clone())
+            .collect();
+        if max_columns.is_some() {
+            headers.push("…".to_string());
+        }
+        if let Some(locator) = &self.locator {
+            headers.extend(locator.headers().iter().map(|name| name.to_string()));
+        }
+        Cow::Owned(headers)
+    }
+
+    fn display_cells<'r>(
+        &self,
+        cells: &'r [String],
+        max_columns: Option<usize>,
+    ) -> Cow<'r, [String]> {
+        let max_width = self.cli.max_cell_width;
+        if max_width.is_none()
+            && max_columns.is_none()
+            && !self.is_binary_column(None)
+            && !self.cli.format_numbers
+            && self.show_columns.is_none()
+        {
+            return Cow::Borrowed(cells);
+        }
+
+        let width_of = |idx: usize| {
+            let binary_width = self
+                .is_binary_column(Some(idx))
+                .then_some(BINARY_DISPLAY_CHARS);
+            match (max_width, binary_width) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        };
+        let cells = self.shown_cells(cells);
+        let mut shown: Vec<String> = cells
+            .iter()
+            .take(max_columns.unwrap_or(usize::MAX))
+            .map(|&(idx, cell)| {
+                let grouped = self.group_number(idx, cell);
+                let cell = grouped.as_ref().unwrap_or(cell);
+                match width_of(idx) {
+                    Some(width) => truncate_display(cell, width).into_owned(),
+                    None => cell.clone(),
+                }
+            })
+            .collect();
+        if max_columns.is_some_and(|n| cells.len() > n) {
+            shown.push("…".to_string());
+        }
+        Cow::Owned(shown)
+    }
+
+    /// Emit the collected output rows in whichever format the user chose.
+    fn emit_matches(&self) {
+        let rows = &self.output_rows;
+        if rows.is_empty() {
+            return;
+        }
+
+        let printer = self.printer;
+        if printer.format == OutputFormat::Json {
+            // Each row carries its own filename, so no header is needed.
+            // Values stay whole, but --show-columns still picks the keys.
+            let shown = |row: &OutputRow| -> (Vec<String>, Option<Vec<bool>>) {
+                let shown = self.shown_cells(&row.cells);
+                let nulls = row.nulls.as_ref().map(|nulls| {
+                    shown
+                        .iter()
+                        .map(|&(idx, _)| nulls.get(idx) == Some(&true))
+                        .collect()
+                });
+                (
+                    shown.into_iter().map(|(_, cell)| cell.clone()).collect(),
+                    nulls,
+                )
+            };
+            let (headers, rows) = match (&self.show_columns, &self.locator) {
+                (None, None) => (
+                    Cow::Borrowed(self.headers.as_slice()),
+                    Cow::Borrowed(rows.as_slice()),
+                ),
+                _ => (
+                    Cow::Owned(self.display_headers(None).into_owned()),
+                    Cow::Owned(
+                        rows.iter()
+                            .map(|row| {
+                                let (mut cells, nulls) = shown(row);
+                                if let Some(locator) = &self.locator {
+                                    cells.extend(locator.cells(row.row_num));
+                                }
+                                OutputRow {
+                                    cells,
+                                    nulls,
+                                    ..row.clone()
+                                }
+                            })
+                            .collect(),
+                    ),
+                ),
+            };
+            printer.print_json_rows(
+                self.filename,
+                &headers,
+                self.shown_types().as_deref(),
+                &rows,
+            );
+            return;
+        }
+
+        let (headers, rows) = self.display_rows();
+        let (headers, rows): (&[String], &[OutputRow]) = (&headers, &rows);
+        if printer.format == OutputFormat::Csv {
+            self.print_csv_heading(headers);
+            let mut prev_row_num: Option<usize> = None;
+            for row in rows {
+                self.print_csv_row(
+                    row.row_num,
+                    &row.cells,
+                    headers,
+                    row.is_context,
+                    prev_row_num,
+                );
+                prev_row_num = Some(row.row_num);
+            }
+            if !self.cli.no_heading {
+                printer.print_separator();
+            }
+            return;
+        }
+
+        if let Some(merged) = self.merged {
+            merged.borrow_mut().add(
+                self.filename,
+                headers,
+                self.display_types().as_deref(),
+                rows,
+            );
+            return;
+        }
+
+        printer.print_file_header(self.filename);
+        match printer.format {
+            OutputFormat::Table => {
+                // print_table handles its own header row
+                printer.print_table(
+                    headers,
+                    self.display_types().as_deref(),
+                    rows,
+                    self.highlight,
+                    self.cli.with_headers,
+                    0,
+                );
+                // Ends each file's matches like CSV output does.
+                printer.print_separator();
+            }
+            OutputFormat::Markdown => {
+                printer.print_markdown(headers, rows, self.highlight, self.cli.with_headers);
+            }
+            OutputFormat::Vertical => {
+                printer.print_vertical(headers, rows, self.highlight);
+            }
+            OutputFormat::Csv | OutputFormat::Json => unreachable!("handled above"),
+        }
+    }
+}
+
+// ── parallel scanning ─────────────────────────────────────────────────────────
+
+/// What a worker found in one chunk of a file (a Parquet row group, or a run
+/// of CSV records), batch by batch: the matching rows and, when the sink
+/// takes them, the same rows as an arrow batch.
+#[derive(Default)]
+struct ChunkMatches {
+    rows_scanned: usize,
+    /// How far this chunk moves row numbering along: the row numbers found in
+    /// each chunk are offset by the spans of the chunks before it. Zero when
+    /// they're already numbered from the start of the file.
+    numbering_span: usize,
+    batches: Vec<(Vec<MatchedRow>, Option<arrow::record_batch::RecordBatch>)>,
+}
+
+/// A row matched on a worker thread: its number, its cells and, for arrow
+/// data, which of them are null.
+type MatchedRow = (usize, Vec<String>, Option<Vec<bool>>);
+
+/// Scan `chunks` chunks on `workers` threads with `scan_chunk`, which takes
+/// a chunk's position. What they find goes through `search` in file order,
+/// with row numbers offset from `first_offset`, so the output is the same
+/// as a serial scan's.
+fn search_chunks_parallel(
+    search: &mut FileSearch,
+    chunks: usize,
+    workers: usize,
+    first_offset: usize,
+    scan_chunk: &(dyn Fn(usize) -> Result<ChunkMatches> + Sync),
+) -> Result<()> {
+    let next_chunk = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (next_chunk, stop) = (&next_chunk, &stop);
+            scope.spawn(move || {
+                while !stop.load(AtomicOrdering::Relaxed) {
+                    let position = next_chunk.fetch_add(1, AtomicOrdering::Relaxed);
+                    if position >= chunks || sender.send((position, scan_chunk(position))).is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Chunks finishing early wait here for the ones before them.
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        let mut offset = first_offset;
+        for (position, found) in receiver {
+            pending.insert(position, found);
+            while let Some(found) = pending.remove(&next) {
+                next += 1;
+                let pushed = found.and_then(|found| {
+                    let span = found.numbering_span;
+                    let keep_going = search.push_chunk_matches(found, offset)?;
+                    offset += span;
+                    Ok(keep_going)
+                });
+                match pushed {
+                    Err(e) => {
+                        stop.store(true, AtomicOrdering::Relaxed);
+                        return Err(e);
+                    }
+                    // e.g. --max-total's share of matches is found
+                    Ok(false) => {
+                        stop.store(true, AtomicOrdering::Relaxed);
+                        return Ok(());
+                    }
+                    Ok(true) => {}
+                }
+                if search.check_deadline() {
+                    stop.store(true, AtomicOrdering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+// ── CSV ───────────────────────────────────────────────────────────────────────
+
+fn search_csv<'r, R: Read + 'r>(
+    reader: R,
+    filename: &str,
+    delimiter: Option<u8>,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    let mut reader = transcode(reader, ctx.cli.encoding);
+    if let Some(separator) = &ctx.cli.split_regex {
+        return search_split(reader, filename, separator, ctx);
+    }
+    let mut sample = Vec::new();
+    let delimiter = match delimiter {
+        Some(delimiter) => delimiter,
+        None => {
+            (&mut reader).take(SNIFF_BYTES).read_to_end(&mut sample)?;
+            sniffed_delimiter(&sample, filename, ctx.cli)
+        }
+    };
+    // Put the sniffed sample back in front of the rest
+    let reader = Cursor::new(sample).chain(reader);
+
+    let mut rdr = csv_reader_builder(ctx.cli, delimiter).from_reader(reader);
+
+    let headers = read_csv_headers(&mut rdr)?;
+    if ctx.cli.describes_only() {
+        return describe_table(
+            filename,
+            &headers,
+            None,
+            || count_csv_records(&mut rdr),
+            ctx,
+        );
+    }
+
+    let col_indices = ctx.resolve_columns(filename, &headers);
+
+    let width = headers.len();
+    let mut search = FileSearch::new(filename, headers, col_indices, ctx);
+    search.set_delimiter(delimiter);
+
+    if ctx.cli.show_location {
+        search.locator = Some(Locator::ByteOffsets {
+            recent: VecDeque::new(),
+            printed: HashMap::new(),
+        });
+    }
+
+    let records = located_records(&mut rdr, filename, ctx.cli.line_number);
+    for result in head_and_tail(records, ctx.cli) {
+        let (row_num, mut row, offset) = result?;
+        fit_row_to_headers(&mut row, row_num, width, filename, ctx.cli);
+        search.note_offset(row_num, offset);
+        if !search.push_row(row_num, row)? {
+            break;
+        }
+    }
+
+    search.finish()
+}
+
+/// The delimiter `sniff_delimiter` picks from the start of a file, honoring
+/// the quoting options and reported under --verbose.
+fn sniffed_delimiter(sample: &[u8], filename: &str, cli: &Cli) -> u8 {
+    let quote = (!cli.no_quoting).then_some(cli.quote.unwrap_or(b'"'));
+    let delimiter = sniff_delimiter(sample, quote);
+    if cli.verbose {
+        report!("{}: using delimiter {:?}", filename, delimiter as char);
+    }
+    delimiter
+}
+
+/// Chunks of a file smaller than this aren't worth a thread of their own.
+const MIN_CSV_CHUNK_BYTES: usize = 1 << 20;
+
+/// `--parallel`: search a plain delimited text file by memory-mapping it and
+/// parsing runs of records on several threads.
+fn search_csv_parallel(
+    path: &Path,
+    filename: &str,
+    delimiter: Option<u8>,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open CSV '{}': {}", filename, e))?;
+    // SAFETY: the map is only read. As with any read, a file changed by
+    // another process mid-search may yield inconsistent rows.
+    let data = unsafe { memmap2::Mmap::map(&file) }
+        .map_err(|e| anyhow::anyhow!("Failed to map CSV '{}': {}", filename, e))?;
+    let data: &[u8] = &data;
+    // A compressed file whose name doesn't say so can't be split up.
+    if Compression::from_magic(data).is_some() {
+        return search_csv(
+            open_maybe_compressed(path, filename)?,
+            filename,
+            delimiter,
+            ctx,
+        );
+    }
+
+    let delimiter = delimiter.unwrap_or_else(|| {
+        let sample = &data[..data.len().min(SNIFF_BYTES as usize)];
+        sniffed_delimiter(sample, filename, cli)
+    });
+
+    let mut rdr = csv_reader_builder(cli, delimiter).from_reader(data);
+    let headers = read_csv_headers(&mut rdr)?;
+    if cli.describes_only() {
+        return describe_table(
+            filename,
+            &headers,
+            None,
+            || count_csv_records(&mut rdr),
+            ctx,
+        );
+    }
+    // With --no-header-row the "headers" were the first record, still to be
+    // searched.
+    let (body_start, lines_before) = if cli.no_header_row {
+        (0, 0)
+    } else {
+        let position = rdr.position();
+        (position.byte() as usize, position.line() as usize - 1)
+    };
+
+    let col_indices = ctx.resolve_columns(filename, &headers);
+    let width = headers.len();
+    let mut search = FileSearch::new(filename, headers, col_indices, ctx);
+    search.set_delimiter(delimiter);
+    let predicate = search.predicate.clone();
+
+    let workers = cli.thread_count();
+    let pieces = ((data.len() - body_start) / MIN_CSV_CHUNK_BYTES).clamp(1, workers * 4);
+    let quote = (!cli.no_quoting).then_some(cli.quote.unwrap_or(b'"'));
+    let chunks = record_chunks(data, body_start, pieces, quote);
+    if cli.verbose {
+        report!(
+            "{}: scanning {} chunks on {} threads",
+            filename,
+            chunks.len(),
+            workers
+        );
+    }
+
+    let scan_chunk = |position: usize| {
+        let chunk = &data[chunks[position].clone()];
+        let mut builder = csv_reader_builder(cli, delimiter);
+        let mut rdr = builder.has_headers(false).from_reader(chunk);
+        let mut found = ChunkMatches::default();
+        let mut rows = Vec::new();
+        for result in numbered_records(&mut rdr, filename, cli.line_number) {
+            let (row_num, mut row) = result?;
+            // Just --pad: rows are numbered from the chunk's start here, so
+            // --strict-columns scans serially
+            fit_row_to_headers(&mut row, row_num, width, filename, cli);
+            found.rows_scanned += 1;
+            if predicate.matches(&row, None, None) {
+                rows.push((row_num, row, None));
+            }
+        }
+        found.numbering_span = if cli.line_number {
+            chunk.iter().filter(|&&b| b == b'\n').count()
+        } else {
+            found.rows_scanned
+        };
+        found.batches.push((rows, None));
+        Ok(found)
+    };
+    let first_offset = if cli.line_number { lines_before } else { 0 };
+    search_chunks_parallel(
+        &mut search,
+        chunks.len(),
+        workers.min(chunks.len()),
+        first_offset,
+        &scan_chunk,
+    )?;
+    search.finish()
+}
+
+/// Split `data[start..]` into about `pieces` byte ranges of whole records,
+/// each ending just after a newline outside quotes. Quotes are tracked by
+/// parity, which is exact as long as `quote` only appears in quoted fields
+/// (doubled inside them): a stray quote in an unquoted field would throw
+/// the split off.
+fn record_chunks(data: &[u8], start: usize, pieces: usize, quote: Option<u8>) -> Vec<Range<usize>> {
+    let target = (data.len() - start).div_ceil(pieces.max(1)).max(1);
+    let mut chunks = Vec::with_capacity(pieces);
+    let mut chunk_start = start;
+    let mut in_quotes = false;
+    for (i, &b) in data.iter().enumerate().skip(start) {
+        if Some(b) == quote {
+            in_quotes = !in_quotes;
+        } else if b == b'\n' && !in_quotes && i + 1 - chunk_start >= target {
+            chunks.push(chunk_start..i + 1);
+            chunk_start = i + 1;
+        }
+    }
+    if chunk_start < data.len() {
+        chunks.push(chunk_start..data.len());
+    }
+    chunks
+}
+
+/// CSV parsing options from the command line.
+fn csv_reader_builder(cli: &Cli, delimiter: u8) -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .flexible(true)
+        .delimiter(delimiter)
+        .has_headers(!cli.no_header_row)
+        .quoting(!cli.no_quoting)
+        .escape(cli.escape);
+    if cli.trim {
+        builder.trim(csv::Trim::All);
+    }
+    if let Some(quote) = cli.quote {
+        builder.quote(quote);
+    }
+    builder
+}
+
+/// Iterate data rows with their numbers: 1-based record numbers by default,
+/// or with `line_numbers` the physical line each record starts on (the header
+/// being line 1), which also counts embedded newlines in quoted cells.
+fn numbered_records<'r, R: Read>(
+    rdr: &'r mut csv::Reader<R>,
+    filename: &'r str,
+    line_numbers: bool,
+) -> impl Iterator<Item = Result<(usize, Vec<String>)>> + 'r {
+    located_records(rdr, filename, line_numbers)
+        .map(|result| result.map(|(row_num, row, _)| (row_num, row)))
+}
+
+/// Like `numbered_records`, with the byte offset each record starts at.
+fn located_records<'r, R: Read>(
+    rdr: &'r mut csv::Reader<R>,
+    filename: &'r str,
+    line_numbers: bool,
+) -> impl Iterator<Item = Result<(usize, Vec<String>, u64)>> + 'r {
+    rdr.records().enumerate().map(move |(idx, result)| {
+        let record =
+            result.map_err(|e| anyhow::anyhow!("CSV parse error in '{}': {}", filename, e))?;
+        let row_num = match record.position() {
+            Some(pos) if line_numbers => pos.line() as usize,
+            _ => idx + 1,
+        };
+        let offset = record.position().map_or(0, |pos| pos.byte());
+        Ok((
+            row_num,
+            record.iter().map(|f| f.to_string()).collect(),
+            offset,
+        ))
+    })
+}
+
+/// --strict-columns and --pad: report a row whose number of fields differs
+/// from the header's `width`, and square it up to that width.
+fn fit_row_to_headers(
+    row: &mut Vec<String>,
+    row_num: usize,
+    width: usize,
+    filename: &str,
+    cli: &Cli,
+) {
+    if row.len() == width {
+        return;
+    }
+    if cli.strict_columns {
+        report!(
+            "Warning: '{}' {} {} has {} fields, expected {}",
+            filename,
+            if cli.line_number { "line" } else { "row" },
+            row_num,
+            row.len(),
+            width
+        );
+    }
+    if cli.pad {
+        row.resize(width, String::new());
+    }
+}
+
+/// Keep the rows --head and --tail select from a stream of them: the first
+/// `head`, then the last `tail` of those. The last rows are only known at the
+/// end, so --tail holds them back in a buffer until then.
+fn head_and_tail<'r, T: 'r>(
+    rows: impl Iterator<Item = Result<T>> + 'r,
+    cli: &Cli,
+) -> Box<dyn Iterator<Item = Result<T>> + 'r> {
+    let rows = rows.take(cli.head.unwrap_or(usize::MAX));
+    let Some(tail) = cli.tail else {
+        return Box::new(rows);
+    };
+    let mut last = VecDeque::new();
+    for row in rows {
+        match row {
+            Ok(row) => last.push_back(row),
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        }
+        if last.len() > tail {
+            last.pop_front();
+        }
+    }
+    Box::new(last.into_iter().map(Ok))
+}
+
+/// The header row, or `col1, col2, ...` (sized from the first record) when the
+/// reader was built without one. Either way `records()` then starts at the
+/// first data row.
+fn read_csv_headers<R: Read>(rdr: &mut csv::Reader<R>) -> Result<Vec<String>> {
+    let has_headers = rdr.has_headers();
+    let first = rdr.headers()?;
+    if has_headers {
+        Ok(first.iter().map(|h| h.to_string()).collect())
+    } else {
+        Ok((1..=first.len()).map(|i| format!("col{}", i)).collect())
+    }
+}
+
+// ── regex-split text ──────────────────────────────────────────────────────────
+
+/// --split-regex: search delimited text a line per row, its fields separated
+/// by matches of `separator`. With no quoting, a separator inside a field
+/// splits it too. Blank lines are skipped, as the CSV reader skips them.
+fn search_split<R: Read>(
+    reader: R,
+    filename: &str,
+    separator: &Regex,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let split = |line: &str| -> Vec<String> {
+        separator
+            .split(line)
+            .map(|field| if cli.trim { field.trim() } else { field }.to_string())
+            .collect()
+    };
+    // Lines numbered from 1, header included
+    let mut lines = BufReader::new(reader)
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| {
+            line.map(|mut line| {
+                if idx == 0 && line.starts_with('\u{feff}') {
+                    line.remove(0);
+                }
+                (idx + 1, line)
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", filename, e))
+        })
+        .filter(|line| !matches!(line, Ok((_, text)) if text.is_empty()));
+
+    let (headers, first_row) = match lines.next().transpose()? {
+        None => (Vec::new(), None),
+        Some((line_num, line)) if cli.no_header_row => {
+            let row = split(&line);
+            let headers = (1..=row.len()).map(|i| format!("col{}", i)).collect();
+            (headers, Some((line_num, row)))
+        }
+        Some((_, line)) => (split(&line), None),
+    };
+    if cli.describes_only() {
+        let count_rows = || {
+            let mut rows = u64::from(first_row.is_some());
+            for line in lines {
+                line?;
+                rows += 1;
+            }
+            Ok(rows)
+        };
+        return describe_table(filename, &headers, None, count_rows, ctx);
+    }
+
+    let col_indices = ctx.resolve_columns(filename, &headers);
+    let width = headers.len();
+    let mut search = FileSearch::new(filename, headers, col_indices, ctx);
+
+    let records = first_row
+        .map(Ok)
+        .into_iter()
+        .chain(lines.map(|line| line.map(|(line_num, text)| (line_num, split(&text)))))
+        .enumerate()
+        .map(|(idx, record)| {
+            record.map(|(line_num, row)| {
+                let row_num = if cli.line_number { line_num } else { idx + 1 };
+                (row_num, row)
+            })
+        });
+    for result in head_and_tail(records, cli) {
+        let (row_num, mut row) = result?;
+        fit_row_to_headers(&mut row, row_num, width, filename, cli);
+        if !search.push_row(row_num, row)? {
+            break;
+        }
+    }
+
+    search.finish()
+}
+
+// ── Parquet ───────────────────────────────────────────────────────────────────
+
+/// Search a Parquet file. `open` yields a fresh handle on it, so row groups
+/// can be read on several threads at once.
+fn search_parquet<T, F>(open: F, filename: &str, ctx: &SearchContext) -> Result<SearchStats>
+where
+    T: parquet::file::reader::ChunkReader + 'static,
+    F: Fn() -> Result<T> + Sync,
+{
+    let cli = ctx.cli;
+    use parquet::arrow::ProjectionMask;
+    use parquet::arrow::arrow_reader::{
+        ArrowReaderMetadata, ParquetRecordBatchReaderBuilder, RowSelection, RowSelector,
+    };
+
+    let reader_metadata = ArrowReaderMetadata::load(&open()?, Default::default())
+        .map_err(|e| anyhow::anyhow!("Failed to read Parquet '{}': {}", filename, e))?;
+
+    let schema = reader_metadata.schema().clone();
+    let mut headers: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+    if cli.describes_only() {
+        let types: Vec<DataType> = schema
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect();
+        // Parquet keeps the row count in its footer
+        let rows = reader_metadata.metadata().file_metadata().num_rows() as u64;
+        return describe_table(filename, &headers, Some(&types), || Ok(rows), ctx);
+    }
+
+    let mut col_indices = ctx.resolve_columns(filename, &headers);
+
+    let metadata = reader_metadata.metadata().clone();
+    let mut row_groups: Vec<usize> = (0..metadata.num_row_groups()).collect();
+    if !cli.row_groups.is_empty() {
+        if let Some(bad) = cli
+            .row_groups
+            .iter()
+            .find(|&&rg| rg >= metadata.num_row_groups())
+        {
+            anyhow::bail!(
+                "--row-groups {} is out of range: '{}' has {} row groups",
+                bad,
+                filename,
+                metadata.num_row_groups()
+            );
+        }
+        row_groups.retain(|rg| cli.row_groups.contains(rg));
+    }
+
+    // 1-based number of the first row in each row group.
+    let mut group_starts = Vec::with_capacity(metadata.num_row_groups());
+    let mut rows_before = 0usize;
+    for rg in metadata.row_groups() {
+        group_starts.push(rows_before + 1);
+        rows_before += rg.num_rows() as usize;
+    }
+    let group_rows =
+        |rg: usize| group_starts[rg]..group_starts[rg] + metadata.row_group(rg).num_rows() as usize;
+
+    // --head and --tail narrow --row-range, counting from the file's ends.
+    let total_rows = metadata.file_metadata().num_rows() as usize;
+    let ends = cli.head_tail_range(total_rows).map(|rows| RowRange {
+        start: Some(*rows.start()),
+        end: Some(*rows.end()),
+    });
+    let row_range = match (cli.row_range, ends) {
+        (Some(range), Some(ends)) => Some(range.intersect(&ends)),
+        (range, ends) => range.or(ends),
+    };
+    if row_range.is_some_and(|range| range.is_empty()) {
+        row_groups.clear();
+    }
+    if let Some(range) = row_range {
+        row_groups.retain(|&rg| {
+            let rows = group_rows(rg);
+            range.start.is_none_or(|start| start < rows.end)
+                && range.end.is_none_or(|end| end >= rows.start)
+        });
+    }
+
+    // For an exact-literal search on one column, skip row groups whose
+    // statistics rule out a match.
+    if !cli.no_stats
+        && let Some(literal) = cli.exact_literal()
+        && let Some(&[col]) = col_indices.as_deref()
+        && let Some(candidates) = stats::candidate_row_groups(
+            &metadata,
+            &headers[col],
+            schema.field(col).data_type(),
+            &literal,
+            &cli.null_text,
+        )
+    {
+        row_groups.retain(|rg| candidates.contains(rg));
+    }
+    // Statistics may even say how many cells equal the literal, which is
+    // all -c needs.
+    let literal_counts = match (cli.exact_literal(), col_indices.as_deref()) {
+        (Some(literal), Some(&[col])) if cli.count && !cli.no_stats && row_range.is_none() => {
+            stats::literal_counts(
+                &metadata,
+                &headers[col],
+                schema.field(col).data_type(),
+                &literal,
+                &cli.null_text,
+            )
+        }
+        _ => None,
+    };
+
+    // 1-based row numbers of the rows read from a row group, in order. These
+    // reflect each row's position in the file even when rows are skipped.
+    let group_row_numbers = |rg: usize| {
+        group_rows(rg).filter(move |&n| row_range.is_none_or(|range| range.contains(n)))
+    };
+
+    // When nothing but the searched columns is ever used, only decode those.
+    // (--match and --unique-by columns may be given by index, which
+    // projection would shift, and --unique compares whole rows.)
+    let output_needs_full_row = cli.output_file.is_some()
+        || !(cli.count || cli.lists_cells() || cli.stops_at_first_match())
+        || !ctx.column_matches.is_empty()
+        || cli.exclude_columns.is_some()
+        || cli.unique
+        || cli.unique_by.is_some();
+    let mut projection = None;
+    if !output_needs_full_row && let Some(indices) = &col_indices {
+        let mut roots = indices.clone();
+        // --filter, set, null-check and --query columns are needed to decide
+        // matches, too
+        roots.extend(
+            cli.filters
+                .iter()
+                .map(|f| &f.column)
+                .chain(&cli.is_null)
+                .chain(&cli.not_null)
+                .map(String::as_str)
+                .chain(ctx.column_sets.iter().map(|set| set.column.as_str()))
+                .chain(cli.query.iter().flat_map(|query| query.columns()))
+                .chain(cli.exprs.iter().flat_map(|expr| expr.columns()))
+                .filter_map(|column| find_column(&headers, column, cli.ignore_case_columns)),
+        );
+        roots.sort_unstable();
+        roots.dedup();
+
+        projection = Some(ProjectionMask::roots(
+            reader_metadata.parquet_schema(),
+            roots.iter().copied(),
+        ));
+
+        // Projected batches contain only the selected columns, in schema order.
+        headers = roots.iter().map(|&i| headers[i].clone()).collect();
+        col_indices = Some(
+            indices
+                .iter()
+                .filter_map(|i| roots.binary_search(i).ok())
+                .collect(),
+        );
+    }
+
+    // A reader over some of the row groups left, skipping the rows outside
+    // --row-range within them.
+    let build_reader = |groups: &[usize]| {
+        let mut builder =
+            ParquetRecordBatchReaderBuilder::new_with_metadata(open()?, reader_metadata.clone())
+                .with_row_groups(groups.to_vec());
+        if let Some(mask) = &projection {
+            builder = builder.with_projection(mask.clone());
+        }
+        if let Some(range) = row_range {
+            let selectors: Vec<RowSelector> = groups
+                .iter()
+                .flat_map(|&rg| {
+                    let rows = group_rows(rg);
+                    let first = range.start.unwrap_or(0).max(rows.start);
+                    let last = range.end.unwrap_or(usize::MAX).min(rows.end - 1);
+                    [
+                        RowSelector::skip(first - rows.start),
+                        RowSelector::select(last + 1 - first),
+                        RowSelector::skip(rows.end - 1 - last),
+                    ]
+                })
+                .collect();
+            builder = builder.with_row_selection(RowSelection::from(selectors));
+        }
+        builder.build().map_err(|e| {
+            anyhow::anyhow!("Failed to build Parquet reader for '{}': {}", filename, e)
+        })
+    };
+
+    let mut search = FileSearch::new(filename, headers, col_indices, ctx);
+    if cli.show_location {
+        search.locator = Some(Locator::RowGroups(group_starts.clone()));
+    }
+
+    // Count the row groups statistics settle without reading them, and with
+    // --estimate, the rest at their most.
+    match literal_counts {
+        Some(counts) if search.counts_alone() => row_groups.retain(|&rg| match counts[rg] {
+            LiteralCount::Exactly(n) => {
+                search.match_count += n;
+                false
+            }
+            LiteralCount::AtMost(n) if cli.estimate => {
+                search.match_count += n;
+                false
+            }
+            LiteralCount::AtMost(_) => true,
+        }),
+        _ if cli.estimate => report!(
+            "Warning: --estimate needs a search for an exact literal in one column, with no other conditions; counting '{}' exactly",
+            filename
+        ),
+        _ => {}
+    }
+
+    let workers = cli.thread_count().min(row_groups.len());
+    if workers > 1 && cli.allows_parallel_scan() {
+        if cli.verbose {
+            report!(
+                "{}: scanning {} row groups on {} threads",
+                filename,
+                row_groups.len(),
+                workers
+            );
+        }
+        let schema = build_reader(&[])?.schema();
+        let mut search = prepare_batch_search(search, &schema, ctx);
+        let predicate = search.predicate.clone();
+        let column_types = search.column_types.clone();
+        let sink_wants_batches = ctx.sink.is_some() && search.sink_takes_batches;
+        let value_format = cli.value_format();
+
+        let scan_group = |position: usize| {
+            let rg = row_groups[position];
+            let mut row_numbers = group_row_numbers(rg);
+            let mut found = ChunkMatches::default();
+            for batch_result in build_reader(&[rg])? {
+                let batch = batch_result
+                    .map_err(|e| anyhow::anyhow!("Parquet batch error in '{}': {}", filename, e))?;
+
+                let mut rows = Vec::new();
+                let mut matched_indices: Vec<u32> = Vec::new();
+                for row_idx in 0..batch.num_rows() {
+                    let row_num = row_numbers.next().unwrap_or_default();
+                    let row = batch_row(&batch, row_idx, &value_format);
+                    let is_null = |col: usize| batch.column(col).is_null(row_idx);
+                    found.rows_scanned += 1;
+                    if predicate.matches(&row, Some(&is_null), column_types.as_deref()) {
+                        let nulls = (0..batch.num_columns()).map(is_null).collect();
+                        rows.push((row_num, row, Some(nulls)));
+                        matched_indices.push(row_idx as u32);
+                    }
+                }
+
+                if !rows.is_empty() {
+                    let matched = if sink_wants_batches {
+                        let indices = arrow::array::UInt32Array::from(matched_indices);
+                        Some(arrow::compute::take_record_batch(&batch, &indices)?)
+                    } else {
+                        None
+                    };
+                    found.batches.push((rows, matched));
+                }
+            }
+            Ok(found)
+        };
+        search_chunks_parallel(&mut search, row_groups.len(), workers, 0, &scan_group)?;
+        return search.finish();
+    }
+
+    let reader = build_reader(&row_groups)?;
+    let row_numbers = row_groups.clone().into_iter().flat_map(group_row_numbers);
+    search_batches(search, reader, row_numbers, "Parquet", ctx)
+}
+
+// ── Arrow IPC ─────────────────────────────────────────────────────────────────
+
+/// Magic bytes opening an Arrow IPC *file*; the streaming format has none.
+const ARROW_FILE_MAGIC: &[u8; 6] = b"ARROW1";
+
+fn search_arrow<R: Read + Seek>(
+    mut reader: R,
+    filename: &str,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    use arrow::ipc::reader::{FileReader, StreamReader};
+
+    let open_err = |e| anyhow::anyhow!("Failed to read Arrow '{}': {}", filename, e);
+    if is_arrow_file(&mut reader)? {
+        let reader = FileReader::try_new_buffered(reader, None).map_err(open_err)?;
+        search_arrow_batches(reader, filename, ctx)
+    } else {
+        let reader = StreamReader::try_new_buffered(reader, None).map_err(open_err)?;
+        search_arrow_batches(reader, filename, ctx)
+    }
+}
+
+/// Whether `reader` holds the Arrow IPC file format (as opposed to the
+/// streaming format). Leaves the reader where it started.
+fn is_arrow_file<R: Read + Seek>(reader: &mut R) -> Result<bool> {
+    let start = reader.stream_position()?;
+    let mut magic = [0u8; 6];
+    let is_file = reader.read_exact(&mut magic).is_ok() && &magic == ARROW_FILE_MAGIC;
+    reader.seek(std::io::SeekFrom::Start(start))?;
+    Ok(is_file)
+}
+
+fn search_arrow_batches<R: RecordBatchReader>(
+    reader: R,
+    filename: &str,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    let schema = reader.schema();
+    let headers: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+    if ctx.cli.describes_only() {
+        let types: Vec<DataType> = schema
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect();
+        let count_rows = || {
+            reader.into_iter().try_fold(0u64, |rows, batch| {
+                let batch = batch
+                    .map_err(|e| anyhow::anyhow!("Failed to read Arrow '{}': {}", filename, e))?;
+                Ok(rows + batch.num_rows() as u64)
+            })
+        };
+        return describe_table(filename, &headers, Some(&types), count_rows, ctx);
+    }
+    let col_indices = ctx.resolve_columns(filename, &headers);
+    let search = FileSearch::new(filename, headers, col_indices, ctx);
+    if ctx.cli.tail.is_some() {
+        // The last rows are only known once every batch has been read.
+        let batches = reader
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Arrow batch error in '{}': {}", filename, e))?;
+        let total = batches.iter().map(|b| b.num_rows()).sum();
+        let rows = ctx.cli.head_tail_range(total).unwrap_or(1..=total);
+        let (first, last) = (*rows.start(), *rows.end());
+        let mut kept = Vec::new();
+        let mut batch_start = 1;
+        for batch in batches {
+            let offset = first.max(batch_start) - batch_start;
+            let end = (last + 1).min(batch_start + batch.num_rows());
+            if end > batch_start + offset {
+                kept.push(Ok(batch.slice(offset, end - batch_start - offset)));
+            }
+            batch_start += batch.num_rows();
+        }
+        let reader = arrow::record_batch::RecordBatchIterator::new(kept, schema);
+        return search_batches(search, reader, first..=last, "Arrow", ctx);
+    }
+    let row_numbers = 1..=ctx.cli.head.unwrap_or(usize::MAX);
+    search_batches(search, reader, row_numbers, "Arrow", ctx)
+}
+
+// ── shared arrow batch loop ───────────────────────────────────────────────────
+
+/// Feed every row of `reader`'s batches through `search`, numbering them from
+/// `row_numbers` and stopping when it runs out. Matches go to the sink as
+/// whole batches so the original types survive.
+fn search_batches<R: RecordBatchReader>(
+    search: FileSearch,
+    reader: R,
+    mut row_numbers: impl Iterator<Item = usize>,
+    format_name: &str,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    let filename = search.filename;
+    let mut search = prepare_batch_search(search, &reader.schema(), ctx);
+    let value_format = ctx.cli.value_format();
+
+    for batch_result in reader {
+        let batch = batch_result
+            .map_err(|e| anyhow::anyhow!("{} batch error in '{}': {}", format_name, filename, e))?;
+
+        let mut matched_indices: Vec<u32> = Vec::new();
+        let mut keep_reading = true;
+        for row_idx in 0..batch.num_rows() {
+            // Running out of row numbers means --head has been reached.
+            let Some(global_row_num) = row_numbers.next() else {
+                keep_reading = false;
+                break;
+            };
+
+            let row = batch_row(&batch, row_idx, &value_format);
+
+            let matches_before = search.match_count;
+            let is_null = |col: usize| batch.column(col).is_null(row_idx);
+            keep_reading = search.push_row_with_nulls(global_row_num, row, Some(&is_null))?;
+            if search.match_count > matches_before {
+                matched_indices.push(row_idx as u32);
+            }
+            if !keep_reading {
+                break;
+            }
+        }
+
+        if let Some(sink) = ctx.sink.as_ref()
+            && search.sink_takes_batches
+            && !matched_indices.is_empty()
+        {
+            let indices = arrow::array::UInt32Array::from(matched_indices);
+            let matched = arrow::compute::take_record_batch(&batch, &indices)?;
+            sink.borrow_mut().write_batch(filename, &matched)?;
+        }
+
+        if !keep_reading {
+            break;
+        }
+    }
+
+    search.finish()
+}
+
+/// Set `search` up for rows read from arrow batches of `schema`.
+fn prepare_batch_search<'a>(
+    mut search: FileSearch<'a>,
+    schema: &arrow::datatypes::Schema,
+    ctx: &SearchContext,
+) -> FileSearch<'a> {
+    search.column_types = Some(
+        schema
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect(),
+    );
+    // Rows rewritten by --replace are no longer the batch's rows.
+    search.sink_takes_batches = ctx.cli.replace.is_none();
+    search
+}
+
+/// The cells of one row of `batch`, as text.
+fn batch_row(
+    batch: &arrow::record_batch::RecordBatch,
+    row_idx: usize,
+    value_format: &ValueFormat,
+) -> Vec<String> {
+    batch
+        .columns()
+        .iter()
+        .map(|col| array_value_to_string(col.as_ref(), row_idx, value_format))
+        .collect()
+}
+
+// ── Excel ─────────────────────────────────────────────────────────────────────
+
+/// Open an Excel workbook held in memory, telling its kind from its contents.
+fn open_workbook<RS: Read + Seek + Clone>(
+    data: RS,
+    filename: &str,
+) -> Result<calamine::Sheets<RS>> {
+    calamine::open_workbook_auto_from_rs(data)
+        .map_err(|e| anyhow::anyhow!("Failed to open Excel '{}': {}", filename, e))
+}
+
+/// Search an Excel workbook: the worksheet picked by --sheet (the first by
+/// default), or with --all-sheets every worksheet, each as its own table.
+fn search_xlsx<RS: Read + Seek>(
+    mut workbook: calamine::Sheets<RS>,
+    filename: &str,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    use calamine::Reader;
+
+    let cli = ctx.cli;
+    let names = workbook.sheet_names();
+    let sheets = if cli.all_sheets {
+        names.clone()
+    } else if let Some(sheet) = &cli.sheet {
+        // A name wins over an index, for sheets named like '2024'
+        let by_index = || sheet.parse::<usize>().ok().and_then(|i| names.get(i));
+        match names.iter().find(|name| *name == sheet).or_else(by_index) {
+            Some(name) => vec![name.clone()],
+            None => anyhow::bail!(
+                "No sheet '{}' in '{}' (it has: {})",
+                sheet,
+                filename,
+                names.join(", ")
+            ),
+        }
+    } else {
+        names.first().cloned().into_iter().collect()
+    };
+
+    let mut stats = SearchStats::default();
+    for sheet in sheets {
+        let range = workbook.worksheet_range(&sheet).map_err(|e| {
+            anyhow::anyhow!("Failed to read sheet '{}' of '{}': {}", sheet, filename, e)
+        })?;
+        let table_name = if cli.all_sheets && names.len() > 1 {
+            format!("{}:{}", filename, sheet)
+        } else {
+            filename.to_string()
+        };
+        stats += search_sheet(&range, &table_name, ctx)?;
+        if cli.quiet && stats.matches > 0 {
+            break;
+        }
+    }
+    // However many sheets it has, a workbook is one file
+    stats.files_searched = 1;
+    Ok(stats)
+}
+
+/// Search one worksheet, treating its first row as headers like CSV.
+fn search_sheet(
+    range: &calamine::Range<calamine::Data>,
+    filename: &str,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let mut rows = range.rows();
+    let headers: Vec<String> = if cli.no_header_row {
+        (1..=range.width()).map(|i| format!("col{}", i)).collect()
+    } else {
+        rows.next()
+            .map(|row| row.iter().map(excel_cell_to_string).collect())
+            .unwrap_or_default()
+    };
+    if cli.describes_only() {
+        let records = rows.len() as u64;
+        return describe_table(filename, &headers, None, || Ok(records), ctx);
+    }
+
+    // With -n, rows are numbered as Excel shows them, from the top of the
+    // sheet rather than of the used range.
+    let first_row = range.start().map_or(0, |(row, _)| row as usize);
+    let first_data_row = first_row + usize::from(!cli.no_header_row);
+
+    let col_indices = ctx.resolve_columns(filename, &headers);
+    let mut search = FileSearch::new(filename, headers, col_indices, ctx);
+    let numbered = rows.enumerate().map(|(idx, row)| {
+        let row_num = if cli.line_number {
+            first_data_row + idx + 1
+        } else {
+            idx + 1
+        };
+        Ok((row_num, row.iter().map(excel_cell_to_string).collect()))
+    });
+    for result in head_and_tail(numbered, cli) {
+        let (row_num, row) = result?;
+        if !search.push_row(row_num, row)? {
+            break;
+        }
+    }
+    search.finish()
+}
+
+/// A cell as Excel would display it with a general format: numbers to 15
+/// significant digits, `TRUE`/`FALSE`, and dates and times in ISO order.
+fn excel_cell_to_string(cell: &calamine::Data) -> String {
+    use calamine::Data;
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(n) => n.to_string(),
+        // Excel rounds away binary noise like 0.30000000000000004
+        Data::Float(f) => format!("{:.14e}", f)
+            .parse::<f64>()
+            .unwrap_or(*f)
+            .to_string(),
+        Data::Bool(true) => "TRUE".to_string(),
+        Data::Bool(false) => "FALSE".to_string(),
+        Data::DateTime(dt) => excel_datetime_to_string(dt),
+        Data::Error(e) => e.to_string(),
+    }
+}
+
+/// A date/time cell as `2024-03-01`, `2024-03-01 12:30:00` or `12:30:00`,
+/// or a duration as `[h]:mm:ss`.
+fn excel_datetime_to_string(dt: &calamine::ExcelDateTime) -> String {
+    if dt.is_duration() {
+        let seconds = (dt.as_f64() * 86_400.0).round() as i64;
+        return format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        );
+    }
+    let (year, month, day, hour, minute, second, milli) = dt.to_ymd_hms_milli();
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    let mut time = format!("{:02}:{:02}:{:02}", hour, minute, second);
+    if milli > 0 {
+        time += &format!(".{:03}", milli);
+    }
+    if dt.as_f64() < 1.0 {
+        // A time of day, with no date
+        time
+    } else if (hour, minute, second, milli) == (0, 0, 0, 0) {
+        date
+    } else {
+        format!("{} {}", date, time)
+    }
+}
+
+// ── JSON Lines ────────────────────────────────────────────────────────────────
+
+fn search_jsonl<R: BufRead + Seek>(
+    mut reader: R,
+    filename: &str,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    use std::collections::HashMap;
+
+    // First pass: the header set is the union of (flattened) keys across all
+    // records, in first-seen order, so it has to be known before matching.
+    let mut headers: Vec<String> = Vec::new();
+    let mut header_index: HashMap<String, usize> = HashMap::new();
+    let mut records = 0u64;
+    for (line_idx, line) in (&mut reader).lines().enumerate() {
+        let line = line.map_err(|e| anyhow::anyhow!("Read error in '{}': {}", filename, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records += 1;
+        for (key, _) in parse_jsonl_record(&line, filename, line_idx + 1)? {
+            if !header_index.contains_key(&key) {
+                header_index.insert(key.clone(), headers.len());
+                headers.push(key);
+            }
+        }
+    }
+
+    if ctx.cli.describes_only() {
+        return describe_table(filename, &headers, None, || Ok(records), ctx);
+    }
+
+    let col_indices = ctx.resolve_columns(filename, &headers);
+
+    let mut search = FileSearch::new(filename, headers, col_indices, ctx);
+    let mut row_num = 0usize;
+    // The first pass counted the records, so --tail needn't buffer.
+    let range = ctx.cli.head_tail_range(records as usize);
+
+    reader.rewind()?;
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| anyhow::anyhow!("Read error in '{}': {}", filename, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        row_num += 1;
+        if let Some(range) = &range {
+            if row_num > *range.end() {
+                break;
+            }
+            if !range.contains(&row_num) {
+                continue;
+            }
+        }
+
+        // Missing keys are null, like explicit JSON nulls
+        let mut row = vec![ctx.cli.null_text.clone(); search.headers.len()];
+        let mut nulls = vec![true; search.headers.len()];
+        for (key, value) in parse_jsonl_record(&line, filename, line_idx + 1)? {
+            let idx = header_index[&key];
+            if let Some(value) = value {
+                row[idx] = value;
+                nulls[idx] = false;
+            }
+        }
+
+        let row_num = if ctx.cli.line_number {
+            line_idx + 1
+        } else {
+            row_num
+        };
+        if !search.push_row_with_nulls(row_num, row, Some(&|col| nulls[col]))? {
+            break;
+        }
+    }
+
+    search.finish()
+}
+
+/// Parse one JSONL line into flattened `(key, value)` pairs, with `None` for
+/// JSON nulls.
+fn parse_jsonl_record(
+    line: &str,
+    filename: &str,
+    line_num: usize,
+) -> Result<Vec<(String, Option<String>)>> {
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|e| {
+        anyhow::anyhow!(
+            "JSON parse error in '{}' on line {}: {}",
+            filename,
+            line_num,
+            e
+        )
+    })?;
+
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut fields = Vec::new();
+            flatten_json_object(&map, "", &mut fields);
+            Ok(fields)
+        }
+        _ => anyhow::bail!(
+            "Expected a JSON object in '{}' on line {}",
+            filename,
+            line_num
+        ),
+    }
+}
+
+/// Flatten nested objects into dotted keys (`address.city`). Arrays are kept
+/// as their JSON text so patterns can still match inside them.
+fn flatten_json_object(
+    map: &serde_json::Map<String, serde_json::Value>,
+    prefix: &str,
+    out: &mut Vec<(String, Option<String>)>,
+) {
+    use serde_json::Value;
+
+    for (key, value) in map {
+        let full_key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Object(inner) if !inner.is_empty() => {
+                flatten_json_object(inner, &full_key, out);
+            }
+            Value::Null => out.push((full_key, None)),
+            Value::String(s) => out.push((full_key, Some(s.clone()))),
+            other => out.push((full_key, Some(other.to_string()))),
+        }
+    }
+}
+
+// ── rewriting ─────────────────────────────────────────────────────────────────
+
+/// --in-place and --replace-output-dir: write a copy of a CSV/TSV or Parquet
+/// file to `out`, with --replace applied to the searched cells of its
+/// matching rows and every other row as it was. The stats count the rows
+/// rewritten as matches.
+pub fn rewrite_table(path: &Path, out: File, ctx: &SearchContext) -> Result<SearchStats> {
+    let cli = ctx.cli;
+    let filename = path.display().to_string();
+    let input_format = cli.input_format.or_else(|| InputFormat::from_path(path));
+    match input_format {
+        Some(format @ (InputFormat::Csv | InputFormat::Tsv | InputFormat::Psv))
+            if Compression::from_path(path).is_none() && cli.split_regex.is_none() =>
+        {
+            let delimiter = resolve_delimiter(format, cli);
+            rewrite_csv(path, &filename, delimiter, out, ctx)
+        }
+        Some(InputFormat::Parquet) => rewrite_parquet(path, &filename, out, ctx),
+        _ => anyhow::bail!(
+            "Can't rewrite '{}': only uncompressed CSV/TSV/PSV and Parquet files can be rewritten",
+            filename
+        ),
+    }
+}
+
+/// Copy delimited text with --replace applied to its matching rows. Only
+/// those rows are re-serialized; everything else, from the BOM and header to
+/// each unmatched row and its line ending, is copied byte for byte. Text in
+/// another --encoding is decoded to search it and encoded back as written.
+fn rewrite_csv(
+    path: &Path,
+    filename: &str,
+    delimiter: Option<u8>,
+    out: File,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    use std::io::Write;
+
+    let cli = ctx.cli;
+    let file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open CSV '{}': {}", filename, e))?;
+    // SAFETY: the map is only read, and the copy goes to another file.
+    let data = unsafe { memmap2::Mmap::map(&file) }
+        .map_err(|e| anyhow::anyhow!("Failed to map CSV '{}': {}", filename, e))?;
+    // As when searching, a BOM only picks the encoding under --encoding;
+    // otherwise a UTF-8 BOM is all the CSV reader would skip.
+    const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+    let (encoding, bom_len) = match cli.encoding {
+        Some(encoding) => Encoding::for_bom(&data).unwrap_or((encoding, 0)),
+        None if data.starts_with(UTF8_BOM) => (encoding_rs::UTF_8, UTF8_BOM.len()),
+        None => (encoding_rs::UTF_8, 0),
+    };
+    let (bom, data) = data.split_at(bom_len);
+    let decoded;
+    let (text, encoding) = if encoding == encoding_rs::UTF_8 {
+        (data, None)
+    } else {
+        decoded = encoding.decode_without_bom_handling(data).0;
+        (decoded.as_bytes(), Some(encoding))
+    };
+
+    let delimiter = delimiter.unwrap_or_else(|| {
+        sniffed_delimiter(&text[..text.len().min(SNIFF_BYTES as usize)], filename, cli)
+    });
+    let mut rdr = csv_reader_builder(cli, delimiter).from_reader(text);
+    let headers = read_csv_headers(&mut rdr)?;
+    let col_indices = ctx.resolve_columns(filename, &headers);
+    let mut predicate = RowPredicate::new(filename, &headers, col_indices.clone(), ctx);
+    predicate.set_delimiter(delimiter);
+
+    let mut writer = std::io::BufWriter::new(out);
+    let write_error =
+        |e: std::io::Error| anyhow::anyhow!("Failed to write the copy of '{}': {}", filename, e);
+    writer.write_all(bom).map_err(write_error)?;
+    let mut write = |piece: &[u8]| -> Result<()> {
+        match encoding {
+            Some(encoding) => {
+                let piece = std::str::from_utf8(piece).expect("decoded text is UTF-8");
+                writer.write_all(&encode_text(piece, encoding, filename)?)
+            }
+            None => writer.write_all(piece),
+        }
+        .map_err(write_error)
+    };
+
+    let rewritten = |record| rewrite_record(record, delimiter, &col_indices, ctx);
+
+    let mut stats = SearchStats {
+        files_searched: 1,
+        ..SearchStats::default()
+    };
+    // Text up to `copied` is written; a matched record starting at `pending`
+    // is rewritten once the next one says where it ends.
+    let (mut copied, mut pending) = (0, None);
+    for result in located_records(&mut rdr, filename, false) {
+        let (_, row, offset) = result?;
+        let offset = offset as usize;
+        if let Some(start) = pending.take() {
+            write(&rewritten(&text[start..offset])?)?;
+            copied = offset;
+        }
+        stats.rows_scanned += 1;
+        if predicate.matches(&row, None, None) {
+            stats.matches += 1;
+            write(&text[copied..offset])?;
+            pending = Some(offset);
+        }
+    }
+    match pending {
+        Some(start) => write(&rewritten(&text[start..])?)?,
+        None => write(&text[copied..])?,
+    }
+    writer.flush().map_err(write_error)?;
+    stats.files_matched = usize::from(stats.matches > 0);
+    Ok(stats)
+}
+
+/// A matched record's text, with --replace applied to its searched cells and
+/// the line breaks around it kept. Under --trim, cells were matched trimmed,
+/// so only that part of each is replaced in and its padding is kept.
+fn rewrite_record(
+    record: &[u8],
+    delimiter: u8,
+    col_indices: &Option<Vec<usize>>,
+    ctx: &SearchContext,
+) -> Result<Vec<u8>> {
+    let cli = ctx.cli;
+    let is_break = |b: &u8| matches!(b, b'\r' | b'\n');
+    let start = record
+        .iter()
+        .position(|b| !is_break(b))
+        .unwrap_or(record.len());
+    let end = record
+        .iter()
+        .rposition(|b| !is_break(b))
+        .map_or(start, |i| i + 1);
+
+    let mut rdr = csv_reader_builder(cli, delimiter)
+        .has_headers(false)
+        .trim(csv::Trim::None)
+        .from_reader(&record[start..end]);
+    let mut record_fields = csv::StringRecord::new();
+    rdr.read_record(&mut record_fields)?;
+    let padded: Vec<String> = record_fields.iter().map(str::to_string).collect();
+    let mut row = padded.clone();
+    if cli.trim {
+        for cell in &mut row {
+            *cell = cell.trim().to_string();
+        }
+    }
+    let replacement = cli.replace.as_deref().unwrap_or_default();
+    replace_matches(&mut row, ctx.pattern, col_indices, replacement);
+    if cli.trim {
+        for (cell, original) in row.iter_mut().zip(&padded) {
+            let lead = original.len() - original.trim_start().len();
+            let trail = original.trim_end().len();
+            *cell = format!("{}{}{}", &original[..lead], cell, &original[trail..]);
+        }
+    }
+
+    // Written the way the file is read: unquoted under --no-quoting, and
+    // escaping quotes with --escape rather than doubling them
+    let mut builder = csv::WriterBuilder::new();
+    builder
+        .delimiter(delimiter)
+        .quote(cli.quote.unwrap_or(b'"'))
+        .flexible(true)
+        .terminator(csv::Terminator::Any(b'\n'));
+    if cli.no_quoting {
+        builder.quote_style(csv::QuoteStyle::Never);
+    }
+    if let Some(escape) = cli.escape {
+        builder.escape(escape).double_quote(false);
+    }
+    let mut writer = builder.from_writer(record[..start].to_vec());
+    writer.write_record(&row)?;
+    let mut rewritten = writer.into_inner().map_err(|e| e.into_error())?;
+    rewritten.pop();
+    rewritten.extend_from_slice(&record[end..]);
+    Ok(rewritten)
+}
+
+/// Encode rewritten text back into a file's `encoding`. encoding_rs only
+/// decodes UTF-16, so that's encoded here.
+fn encode_text(text: &str, encoding: &'static Encoding, filename: &str) -> Result<Vec<u8>> {
+    if encoding == encoding_rs::UTF_16LE {
+        return Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+    }
+    if encoding == encoding_rs::UTF_16BE {
+        return Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        anyhow::bail!(
+            "Can't write the copy of '{}': the replacement has characters {} can't encode",
+            filename,
+            encoding.name()
+        );
+    }
+    Ok(bytes.into_owned())
+}
+
+/// Rewrite a Parquet file batch by batch, keeping its schema and the
+/// compression of its first column. Only string columns are rewritten.
+fn rewrite_parquet(
+    path: &Path,
+    filename: &str,
+    out: File,
+    ctx: &SearchContext,
+) -> Result<SearchStats> {
+    use arrow::array::{Array, AsArray, StringArray};
+    use parquet::arrow::ArrowWriter;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use parquet::file::properties::WriterProperties;
+
+    let cli = ctx.cli;
+    let replacement = cli.replace.as_deref().unwrap_or_default();
+    let file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open Parquet '{}': {}", filename, e))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .map_err(|e| anyhow::anyhow!("Failed to read Parquet '{}': {}", filename, e))?;
+    let schema = builder.schema().clone();
+    let headers: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+    let types: Vec<DataType> = schema
+        .fields()
+        .iter()
+        .map(|f| f.data_type().clone())
+        .collect();
+    let compression = builder
+        .metadata()
+        .row_groups()
+        .first()
+        .and_then(|rg| rg.columns().first())
+        .map(|column| column.compression());
+
+    let col_indices = ctx.resolve_columns(filename, &headers);
+    let predicate = RowPredicate::new(filename, &headers, col_indices.clone(), ctx);
+    let rewritten: Vec<usize> = (0..types.len())
+        .filter(|&idx| {
+            matches!(
+                types[idx],
+                DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+            ) && col_indices
+                .as_ref()
+                .is_none_or(|indices| indices.contains(&idx))
+        })
+        .collect();
+    // Searched columns that aren't text can match but can't be rewritten; a
+    // match left in one would survive into the "redacted" copy.
+    let kept: Vec<usize> = (0..types.len())
+        .filter(|idx| {
+            !rewritten.contains(idx)
+                && col_indices
+                    .as_ref()
+                    .is_none_or(|indices| indices.contains(idx))
+        })
+        .collect();
+
+    let mut props = WriterProperties::builder();
+    if let Some(compression) = compression {
+        props = props.set_compression(compression);
+    }
+    let mut writer = ArrowWriter::try_new(out, schema.clone(), Some(props.build()))?;
+    let value_format = cli.value_format();
+    let mut stats = SearchStats {
+        files_searched: 1,
+        ..SearchStats::default()
+    };
+    let reader = builder
+        .build()
+        .map_err(|e| anyhow::anyhow!("Failed to build Parquet reader for '{}': {}", filename, e))?;
+    for batch_result in reader {
+        let batch = batch_result
+            .map_err(|e| anyhow::anyhow!("Parquet batch error in '{}': {}", filename, e))?;
+        let mut matched = Vec::with_capacity(batch.num_rows());
+        for row_idx in 0..batch.num_rows() {
+            let row = batch_row(&batch, row_idx, &value_format);
+            let is_null = |col: usize| batch.column(col).is_null(row_idx);
+            let is_match = predicate.matches(&row, Some(&is_null), Some(&types));
+            if is_match
+                && !ctx.pattern.as_str().is_empty()
+                && let Some(&idx) = kept.iter().find(|&&idx| ctx.pattern.is_match(&row[idx]))
+            {
+                anyhow::bail!(
+                    "'{}' has a match in column '{}' of type {}, which --replace can't rewrite; leave it out with --columns",
+                    filename,
+                    headers[idx],
+                    types[idx]
+                );
+            }
+            matched.push(is_match);
+        }
+        let count = matched.iter().filter(|&&m| m).count();
+        stats.rows_scanned += batch.num_rows();
+        stats.matches += count;
+        if count == 0 {
+            writer.write(&batch)?;
+            continue;
+        }
+
+        let mut columns = batch.columns().to_vec();
+        for &idx in &rewritten {
+            let text = arrow::compute::cast(&columns[idx], &DataType::Utf8)?;
+            let text = text.as_string::<i32>();
+            let replaced: StringArray = (0..text.len())
+                .map(|row_idx| {
+                    (!text.is_null(row_idx)).then(|| {
+                        let value = text.value(row_idx);
+                        if matched[row_idx] {
+                            ctx.pattern.replace_all(value, replacement)
+                        } else {
+                            Cow::Borrowed(value)
+                        }
+                    })
+                })
+                .collect();
+            columns[idx] = arrow::compute::cast(&replaced, &types[idx])?;
+        }
+        writer.write(&arrow::record_batch::RecordBatch::try_new(
+            schema.clone(),
+            columns,
+        )?)?;
+    }
+    writer.close()?;
+    stats.files_matched = usize::from(stats.matches > 0);
+    Ok(stats)
+}
+
+// ── helpers ───────────────────────────────────────────────────────────────────
+
+/// Resolve `--columns` tokens to column indices.
+///
+/// Each token is a header name, a zero-based index, or an index range (`1-4`,
+/// open-ended `3-`, or reversed `4-1`). Header names always win over the other
+/// interpretations. A token prefixed with `!` or `-` excludes those columns;
+/// exclusions take precedence over inclusions, and a list of only exclusions
+/// starts from all columns.
+pub fn resolve_column_indices(
+    headers: &[String],
+    filter: &Option<Vec<String>>,
+    ignore_case: bool,
+) -> Option<Vec<usize>> {
+    filter.as_ref().map(|cols| {
+        let mut included: Vec<usize> = Vec::new();
+        let mut excluded: Vec<usize> = Vec::new();
+        let mut has_inclusions = false;
+
+        for token in cols {
+            let exclusion = token
+                .strip_prefix('!')
+                .or_else(|| token.strip_prefix('-'))
+                .filter(|_| !headers.contains(token));
+            match exclusion {
+                Some(inner) => excluded.extend(resolve_column_token(headers, inner, ignore_case)),
+                None => {
+                    has_inclusions = true;
+                    included.extend(resolve_column_token(headers, token, ignore_case));
+                }
+            }
+        }
+
+        if !has_inclusions {
+            included = (0..headers.len()).collect();
+        }
+        included.retain(|idx| !excluded.contains(idx));
+        included
+    })
+}
+
+/// Resolve a single (non-excluding) `--columns` token to column indices.
+fn resolve_column_token(headers: &[String], token: &str, ignore_case: bool) -> Vec<usize> {
+    if let Some(idx) = find_column(headers, token, ignore_case) {
+        return vec![idx];
+    }
+
+    if let Some((start, end)) = token.split_once('-')
+        && let Ok(start) = start.parse::<usize>()
+    {
+        let end = if end.is_empty() {
+            Ok(headers.len().saturating_sub(1))
+        } else {
+            end.parse::<usize>()
+        };
+        if let Ok(end) = end {
+            let in_range = |idx: &usize| *idx < headers.len();
+            let indices: Vec<usize> = if start <= end {
+                (start..=end).filter(in_range).collect()
+            } else {
+                (end..=start).rev().filter(in_range).collect()
+            };
+            if indices.is_empty() {
+                report!(
+                    "Warning: column range '{}' out of range ({} columns)",
+                    token,
+                    headers.len()
+                );
+            }
+            return indices;
+        }
+    }
+
+    match token.parse::<usize>() {
+        Ok(idx) if idx < headers.len() => vec![idx],
+        Ok(idx) => {
+            report!(
+                "Warning: column index {} out of range ({} columns)",
+                idx,
+                headers.len()
+            );
+            Vec::new()
+        }
+        Err(_) => {
+            report!("Warning: column '{}' not found", token);
+            Vec::new()
+        }
+    }
+}
+
+/// Find the header named `name`. With `ignore_case` (--ignore-case-columns),
+/// a header differing only in case or surrounding whitespace also counts,
+/// unless an exact match exists or several headers qualify.
+pub fn find_column(headers: &[String], name: &str, ignore_case: bool) -> Option<usize> {
+    if let Some(idx) = headers.iter().position(|h| h == name) {
+        return Some(idx);
+    }
+    if !ignore_case {
+        return None;
+    }
+
+    let fold = |s: &str| s.trim().to_lowercase();
+    let wanted = fold(name);
+    let mut candidates = (0..headers.len()).filter(|&idx| fold(&headers[idx]) == wanted);
+    let first = candidates.next()?;
+    if let Some(second) = candidates.next() {
+        report!(
+            "Warning: column '{}' is ambiguous: matches '{}' and '{}'",
+            name,
+            headers[first],
+            headers[second]
+        );
+        return None;
+    }
+    Some(first)
+}
+
+/// The cells `col_indices` selects (all of them when `None`), in that order,
+/// joined with `separator`: what --whole-row matches against.
+pub fn joined_row(row: &[String], col_indices: &Option<Vec<usize>>, separator: &str) -> String {
+    match col_indices {
+        Some(indices) => indices
+            .iter()
+            .filter_map(|&i| row.get(i).map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(separator),
+        None => row.join(separator),
+    }
+}
+
+pub fn row_matches(
+    row: &[String],
+    pattern: &Regex,
+    col_indices: &Option<Vec<usize>>,
+    invert: bool,
+) -> bool {
+    let mut cells_to_check: Box<dyn Iterator<Item = &String>> = match col_indices {
+        Some(indices) => Box::new(indices.iter().filter_map(|&i| row.get(i))),
+        None => Box::new(row.iter()),
+    };
+
+    let any_match = cells_to_check.any(|cell| pattern.is_match(cell));
+    if invert { !any_match } else { any_match }
+}
+
+/// The --group-by buckets in --group-sort order, cut to --top.
+fn histogram<'c>(counts: &'c BTreeMap<String, usize>, cli: &Cli) -> Vec<(&'c String, usize)> {
+    let mut buckets: Vec<(&String, usize)> = counts.iter().map(|(v, &n)| (v, n)).collect();
+    if cli.group_sort == GroupSort::Count {
+        // Stable, so equally frequent values stay in lexical order
+        buckets.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    }
+    buckets.truncate(cli.top.unwrap_or(usize::MAX));
+    buckets
+}
+
+/// The --count-by-column tally: the columns with any matching cells, most
+/// first and otherwise in file order.
+fn column_tally<'h>(headers: &'h [String], hits: &[usize]) -> Vec<(&'h String, usize)> {
+    let mut tally: Vec<(&String, usize)> = headers
+        .iter()
+        .zip(hits.iter().copied())
+        .filter(|&(_, n)| n > 0)
+        .collect();
+    tally.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    tally
+}
+
+/// Substitute `replacement` for every match of `pattern` in the searched
+/// cells of `row`.
+fn replace_matches(
+    row: &mut [String],
+    pattern: &Regex,
+    col_indices: &Option<Vec<usize>>,
+    replacement: &str,
+) {
+    for (idx, cell) in row.iter_mut().enumerate() {
+        if col_indices
+            .as_ref()
+            .is_none_or(|indices| indices.contains(&idx))
+            && let Cow::Owned(replaced) = pattern.replace_all(cell, replacement)
+        {
+            *cell = replaced;
+        }
+    }
+}
+
+fn print_only_matching(
+    row: &[String],
+    headers: &[String],
+    pattern: &Regex,
+    col_indices: &Option<Vec<usize>>,
+    invert: bool,
+) {
+    for idx in only_matching_cells(row, pattern, col_indices, invert) {
+        let col_name = headers.get(idx).map(|s| s.as_str()).unwrap_or("?");
+        println!("  [{}] {}", col_name, row[idx]);
+    }
+}
+
+/// Indices of the searched cells that `-o` shows: those matching the pattern,
+/// or with `invert` those that don't. That's `--only-non-matching`, and also
+/// `-o -v`, whose rows usually have no matching cell, so every searched cell
+/// shows.
+/// Which of `row`'s cells are null, for formats that say.
+fn null_mask(row: &[String], is_null: Option<&dyn Fn(usize) -> bool>) -> Option<Vec<bool>> {
+    is_null.map(|is_null| (0..row.len()).map(is_null).collect())
+}
+
+fn only_matching_cells(
+    row: &[String],
+    pattern: &Regex,
+    col_indices: &Option<Vec<usize>>,
+    invert: bool,
+) -> Vec<usize> {
+    let indices_to_check: Vec<usize> = match col_indices {
+        Some(indices) => indices.clone(),
+        None => (0..row.len()).collect(),
+    };
+
+    indices_to_check
+        .into_iter()
+        .filter(|&idx| {
+            row.get(idx)
+                .is_some_and(|cell| pattern.is_match(cell) != invert)
+        })
+        .collect()
+}
+
+/// Options controlling how arrow values are rendered as text.
+#[derive(Debug, Clone)]
+pub struct ValueFormat {
+    /// Zone that timezone-aware timestamps are displayed in; UTC when unset.
+    pub timezone: Option<Tz>,
+    pub binary_encoding: BinaryEncoding,
+    /// Text for null values (`--null-text`).
+    pub null_text: String,
+}
+
+impl Default for ValueFormat {
+    fn default() -> Self {
+        Self {
+            timezone: None,
+            binary_encoding: BinaryEncoding::default(),
+            null_text: String::from("NULL"),
+        }
+    }
+}
+
+/// Naive (zone-less) timestamps: ISO-8601 with only as many fractional digits
+/// as the value needs.
+const NAIVE_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+pub fn array_value_to_string(
+    array: &dyn arrow::array::Array,
+    index: usize,
+    format: &ValueFormat,
+) -> String {
+    use arrow::array::*;
+    use arrow::datatypes::*;
+
+    if array.is_null(index) {
+        return format.null_text.clone();
+    }
+
+    match array.data_type() {
+        DataType::Utf8 => array
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .map(|a| a.value(index).to_string())
+            .unwrap_or_default(),
+        DataType::LargeUtf8 => array
+            .as_any()
+            .downcast_ref::<LargeStringArray>()
+            .map(|a| a.value(index).to_string())
+            .unwrap_or_default(),
+        DataType::Int8 => array
+            .as_any()
+            .downcast_ref::<Int8Array>()
+            .map(|a| a.value(index).to_string())
+            .unwrap_or_default(),
+        DataType::Int16 => array
+            .as_any()
+            .downcast_ref::<Int16Array>()
+            .map(|a| a.value(index).to_string())
+            .unwrap_or_default(),
+        DataType::Int32 => array
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .map(|a| a.value(index).to_string())
+            .unwrap_or_default(),
+        DataType::Int64 => array
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .map(|a| a.value(index).to_string())
+            .unwrap_or_default(),
         DataType::UInt8 => array
             .as_any()
             .downcast_ref::<UInt8Array>()
@@ -324,62 +3804,1739 @@ fn array_value_to_string(array: &dyn arrow::array::Array, index: usize) -> Strin
                     .unwrap_or_else(|| a.value(index).to_string())
             })
             .unwrap_or_default(),
+        // Milliseconds, but of a whole day: shown as a date like Date32, so
+        // the same date reads the same in either type.
         DataType::Date64 => array
             .as_any()
             .downcast_ref::<Date64Array>()
             .map(|a| {
-                a.value_as_datetime(index)
+                a.value_as_date(index)
                     .map(|d| d.to_string())
                     .unwrap_or_else(|| a.value(index).to_string())
             })
-            .unwrap_or_default(),
-        dt => format!("<{}>", dt),
+            .unwrap_or_default(),
+        // Formatted with the column's scale, keeping trailing zeros as
+        // DuckDB and pandas show them: 1234 at scale 2 is "12.34".
+        DataType::Decimal128(_, _) => array
+            .as_primitive::<Decimal128Type>()
+            .value_as_string(index),
+        DataType::Decimal256(_, _) => array
+            .as_primitive::<Decimal256Type>()
+            .value_as_string(index),
+        DataType::Timestamp(TimeUnit::Second, tz) => {
+            timestamp_to_string::<TimestampSecondType>(array, index, tz.as_deref(), format)
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, tz) => {
+            timestamp_to_string::<TimestampMillisecondType>(array, index, tz.as_deref(), format)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, tz) => {
+            timestamp_to_string::<TimestampMicrosecondType>(array, index, tz.as_deref(), format)
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, tz) => {
+            timestamp_to_string::<TimestampNanosecondType>(array, index, tz.as_deref(), format)
+        }
+        DataType::Time32(TimeUnit::Second) => {
+            time_to_string(array.as_primitive::<Time32SecondType>(), index)
+        }
+        DataType::Time32(TimeUnit::Millisecond) => {
+            time_to_string(array.as_primitive::<Time32MillisecondType>(), index)
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            time_to_string(array.as_primitive::<Time64MicrosecondType>(), index)
+        }
+        DataType::Time64(TimeUnit::Nanosecond) => {
+            time_to_string(array.as_primitive::<Time64NanosecondType>(), index)
+        }
+        DataType::Duration(TimeUnit::Second) => {
+            duration_to_string(array.as_primitive::<DurationSecondType>(), index)
+        }
+        DataType::Duration(TimeUnit::Millisecond) => {
+            duration_to_string(array.as_primitive::<DurationMillisecondType>(), index)
+        }
+        DataType::Duration(TimeUnit::Microsecond) => {
+            duration_to_string(array.as_primitive::<DurationMicrosecondType>(), index)
+        }
+        DataType::Duration(TimeUnit::Nanosecond) => {
+            duration_to_string(array.as_primitive::<DurationNanosecondType>(), index)
+        }
+        DataType::Binary => encode_binary(array.as_binary::<i32>().value(index), format),
+        DataType::LargeBinary => encode_binary(array.as_binary::<i64>().value(index), format),
+        DataType::FixedSizeBinary(_) => {
+            encode_binary(array.as_fixed_size_binary().value(index), format)
+        }
+        // Nested cells are searched as compact JSON, e.g. ["a","b"] or
+        // {"city":"NYC"}.
+        DataType::List(_)
+        | DataType::LargeList(_)
+        | DataType::FixedSizeList(_, _)
+        | DataType::Struct(_)
+        | DataType::Map(_, _) => nested_to_json(array, index, format).to_string(),
+        dt => format!("<{}>", dt),
+    }
+}
+
+fn encode_binary(bytes: &[u8], format: &ValueFormat) -> String {
+    use base64::Engine;
+    use std::fmt::Write;
+
+    match format.binary_encoding {
+        BinaryEncoding::Hex => bytes.iter().fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        }),
+        BinaryEncoding::Base64 => base64::engine::general_purpose::STANDARD.encode(bytes),
+    }
+}
+
+/// Convert one cell to JSON, recursing into list, struct and map children.
+/// Leaf values are rendered by `array_value_to_string` and typed like
+/// `--format json` output.
+fn nested_to_json(
+    array: &dyn arrow::array::Array,
+    index: usize,
+    format: &ValueFormat,
+) -> serde_json::Value {
+    use arrow::array::{Array, AsArray};
+    use serde_json::Value;
+
+    if array.is_null(index) {
+        return Value::Null;
+    }
+
+    let list = |values: arrow::array::ArrayRef| {
+        Value::Array(
+            (0..values.len())
+                .map(|i| nested_to_json(values.as_ref(), i, format))
+                .collect(),
+        )
+    };
+
+    match array.data_type() {
+        DataType::List(_) => list(array.as_list::<i32>().value(index)),
+        DataType::LargeList(_) => list(array.as_list::<i64>().value(index)),
+        DataType::FixedSizeList(_, _) => list(array.as_fixed_size_list().value(index)),
+        DataType::Struct(_) => {
+            let s = array.as_struct();
+            Value::Object(
+                s.column_names()
+                    .iter()
+                    .zip(s.columns())
+                    .map(|(name, col)| {
+                        (
+                            name.to_string(),
+                            nested_to_json(col.as_ref(), index, format),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+        DataType::Map(_, _) => {
+            let entries = array.as_map().value(index);
+            let (keys, values) = (entries.column(0), entries.column(1));
+            Value::Object(
+                (0..entries.len())
+                    .map(|i| {
+                        (
+                            array_value_to_string(keys.as_ref(), i, format),
+                            nested_to_json(values.as_ref(), i, format),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+        dt => json_value(
+            &array_value_to_string(array, index, format),
+            Some(dt),
+            array.is_null(index),
+        ),
+    }
+}
+
+/// Render a timestamp as ISO-8601. Timestamps with a zone are shown in
+/// `--timezone` (UTC by default) with their offset; naive ones as stored.
+fn timestamp_to_string<T: arrow::datatypes::ArrowTimestampType>(
+    array: &dyn arrow::array::Array,
+    index: usize,
+    tz: Option<&str>,
+    format: &ValueFormat,
+) -> String {
+    use arrow::array::AsArray;
+
+    let a = array.as_primitive::<T>();
+    let rendered = match tz {
+        None => a
+            .value_as_datetime(index)
+            .map(|dt| dt.format(NAIVE_TIMESTAMP_FORMAT).to_string()),
+        Some(tz) => tz.parse::<Tz>().ok().and_then(|tz| {
+            let display_tz = format.timezone.unwrap_or_else(utc);
+            a.value_as_datetime_with_tz(index, tz).map(|dt| {
+                dt.with_timezone(&display_tz)
+                    .format("%Y-%m-%dT%H:%M:%S%.f%:z")
+                    .to_string()
+            })
+        }),
+    };
+    rendered.unwrap_or_else(|| a.value(index).to_string())
+}
+
+fn time_to_string<T: arrow::datatypes::ArrowTemporalType>(
+    a: &arrow::array::PrimitiveArray<T>,
+    index: usize,
+) -> String
+where
+    i64: From<T::Native>,
+    T::Native: std::fmt::Display,
+{
+    a.value_as_time(index)
+        .map(|t| t.to_string())
+        .unwrap_or_else(|| a.value(index).to_string())
+}
+
+/// Durations use the ISO-8601 form, e.g. `PT90S` or `PT0.25S`.
+fn duration_to_string<T: arrow::datatypes::ArrowTemporalType>(
+    a: &arrow::array::PrimitiveArray<T>,
+    index: usize,
+) -> String
+where
+    i64: From<T::Native>,
+    T::Native: std::fmt::Display,
+{
+    a.value_as_duration(index)
+        .map(|d| d.to_string())
+        .unwrap_or_else(|| a.value(index).to_string())
+}
+
+fn utc() -> Tz {
+    "+00:00".parse().expect("UTC offset parses")
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn re(pattern: &str) -> Regex {
+        Regex::new(pattern).unwrap()
+    }
+
+    /// Parse `args` (PATTERN and PATH included) and run `f` with a search
+    /// context for them, printing without color.
+    fn with_context<I, T, R>(args: I, f: impl FnOnce(&SearchContext) -> R) -> R
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        use clap::Parser;
+
+        let cli = Cli::parse_from(
+            std::iter::once("table-grep".into()).chain(args.into_iter().map(Into::into)),
+        );
+        let pattern = cli.build_regex().unwrap();
+        let printer = Printer::new(false, false, cli.format, false, false);
+        f(&SearchContext::new(&pattern, &cli, &printer))
+    }
+
+    /// Search `path` with `args` (PATTERN included, PATH left out).
+    fn search_with<I, T>(args: I, path: impl AsRef<Path>) -> Result<SearchStats>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let path = path.as_ref();
+        let args = args.into_iter().map(Into::into);
+        with_context(args.chain([path.into()]), |ctx| search_file(path, ctx))
+    }
+
+    /// Write `batch` to a Parquet file in the temp dir, in row groups of
+    /// `row_group_size` rows if given, and return its path.
+    fn write_temp_parquet(
+        name: &str,
+        batch: &arrow::array::RecordBatch,
+        row_group_size: Option<usize>,
+    ) -> std::path::PathBuf {
+        use parquet::arrow::ArrowWriter;
+        use parquet::file::properties::WriterProperties;
+
+        let path = std::env::temp_dir().join(format!(
+            "table-grep-{}-{}.parquet",
+            name,
+            std::process::id()
+        ));
+        let props = row_group_size.map(|size| {
+            WriterProperties::builder()
+                .set_max_row_group_size(size)
+                .build()
+        });
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), props).unwrap();
+        writer.write(batch).unwrap();
+        writer.close().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_row_matches_basic() {
+        let row = vec![
+            "Alice".to_string(),
+            "30".to_string(),
+            "Engineer".to_string(),
+        ];
+
+        // Positive match — pattern present in row
+        assert!(row_matches(&row, &re("Alice"), &None, false));
+
+        // No match — pattern absent
+        assert!(!row_matches(&row, &re("Bob"), &None, false));
+
+        // Inverted: absent pattern → true
+        assert!(row_matches(&row, &re("Bob"), &None, true));
+
+        // Inverted: present pattern → false
+        assert!(!row_matches(&row, &re("Alice"), &None, true));
+
+        // Column filter: match only in column 1 (age), pattern matches
+        assert!(row_matches(&row, &re("30"), &Some(vec![1]), false));
+
+        // Column filter: restrict to column 0 (name), numeric pattern should not match
+        assert!(!row_matches(&row, &re("30"), &Some(vec![0]), false));
+
+        // Regex: match on partial word
+        assert!(row_matches(&row, &re("Eng.*"), &None, false));
+
+        // Case sensitivity: lowercase should not match "Alice" by default
+        assert!(!row_matches(&row, &re("alice"), &None, false));
+
+        // Case insensitive via regex flag
+        assert!(row_matches(&row, &re("(?i)alice"), &None, false));
+    }
+
+    /// Feed single-cell rows through a `FileSearch` and return the buffered
+    /// `(row_num, is_context)` pairs.
+    fn buffered_rows(args: &[&str], cells: &[&str]) -> Vec<(usize, bool)> {
+        // Use -f table: CSV output streams rows, so nothing would be buffered
+        let args = ["-f", "table"].iter().chain(args).chain(&["-"]);
+        with_context(args, |ctx| {
+            let mut search = FileSearch::new("test.csv", vec!["col".to_string()], None, ctx);
+            for (i, cell) in cells.iter().enumerate() {
+                if !search.push_row(i + 1, vec![cell.to_string()]).unwrap() {
+                    break;
+                }
+            }
+            search
+                .output_rows
+                .iter()
+                .map(|r| (r.row_num, r.is_context))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_output_rows_keep_nulls() {
+        // A Utf8 column holding the string "NULL", then a real null: only
+        // the latter is marked, so JSON output keeps the string
+        with_context(["-f", "table", "-B", "1", "-x", "x", "-"], |ctx| {
+            let headers = vec!["v".to_string(), "w".to_string()];
+            let mut search = FileSearch::new("test.parquet", headers, None, ctx);
+            let rows = [
+                (["NULL", "x"], [false, false]),
+                (["NULL", "x"], [true, false]),
+            ];
+            for (i, (cells, nulls)) in rows.iter().enumerate() {
+                let is_null = |col: usize| nulls[col];
+                let row = cells.iter().map(|c| c.to_string()).collect();
+                search
+                    .push_row_with_nulls(i + 1, row, Some(&is_null))
+                    .unwrap();
+            }
+            let nulls: Vec<_> = search.output_rows.iter().map(|r| r.nulls.clone()).collect();
+            assert_eq!(nulls, [Some(vec![false, false]), Some(vec![true, false])]);
+            // Delimited text says nothing about nulls
+            assert!(
+                search
+                    .push_row(3, vec!["".to_string(), "x".to_string()])
+                    .unwrap()
+            );
+            assert_eq!(search.output_rows[2].nulls, None);
+        });
+    }
+
+    #[test]
+    fn test_schema_difference() {
+        let cols =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|s| s.to_string()).collect() };
+        let first = cols(&["id", "name", "age"]);
+        assert_eq!(schema_difference(&first, &first), None);
+        assert_eq!(
+            schema_difference(&first, &cols(&["id", "full_name", "age", "email"])).as_deref(),
+            Some("added 'full_name', 'email'; removed 'name'")
+        );
+        assert_eq!(
+            schema_difference(&first, &cols(&["name", "id", "age"])).as_deref(),
+            Some("same columns in a different order")
+        );
+    }
+
+    #[test]
+    fn test_list_columns_without_pattern() {
+        let stats = search_with(["--list-columns"], "test_data/test_file_1.csv").unwrap();
+        assert_eq!(stats.files_searched, 1);
+        assert_eq!(stats.rows_scanned, 0);
+    }
+
+    #[test]
+    fn test_search_headers() {
+        use clap::Parser;
+
+        let search = |args: &[&str]| {
+            let args = ["--search-headers", "-q"].iter().chain(args);
+            let stats = search_with(args, "test_data/test_file_1.csv").unwrap();
+            assert_eq!(stats.rows_scanned, 0);
+            (stats.files_matched, stats.matches)
+        };
+
+        // Columns are name, age and position
+        assert_eq!(search(&["n"]), (1, 2));
+        assert_eq!(search(&["^(name|age)$"]), (1, 2));
+        assert_eq!(search(&["-v", "^(name|age)$"]), (1, 1));
+        assert_eq!(search(&["email"]), (0, 0));
+        // Unlike --list-columns, it needs a pattern
+        assert!(Cli::try_parse_from(["table-grep", "--search-headers", "f.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_search_xlsx_sheets() {
+        let matches = |args: &[&str]| {
+            let args = ["-c"].iter().chain(args);
+            search_with(args, "test_data/test_workbook.xlsx")
+                .unwrap()
+                .matches
+        };
+        // The first sheet by default, else by name or index, or all of them
+        assert_eq!(matches(&["Alice"]), 1);
+        assert_eq!(matches(&["--sheet", "Orders", "Dana"]), 1);
+        assert_eq!(matches(&["--sheet", "0", "Dana"]), 0);
+        assert_eq!(matches(&["--all-sheets", "Alice"]), 2);
+        // Cells read as Excel shows them
+        assert_eq!(matches(&["-x", "-k", "score", "0.3"]), 1);
+        assert_eq!(matches(&["-x", "-k", "joined", "2025-10-13 12:00:00"]), 1);
+        assert_eq!(matches(&["-x", "-k", "active", "TRUE"]), 2);
+    }
+
+    #[test]
+    fn test_inverted_count_with_zero() {
+        let count = |args: &[&str], cells: &[&str]| {
+            let args = ["-c", "-v"].iter().chain(args).chain(&["x", "-"]);
+            with_context(args, |ctx| {
+                let mut search = FileSearch::new("test.csv", vec!["col".to_string()], None, ctx);
+                for (i, cell) in cells.iter().enumerate() {
+                    search.push_row(i + 1, vec![cell.to_string()]).unwrap();
+                }
+                search.printed_count()
+            })
+        };
+
+        // Every row matches 'x', so no row matches -v
+        assert_eq!(count(&[], &["x", "xx"]), None);
+        assert_eq!(count(&["--include-zero"], &["x", "xx"]), Some(0));
+        // No row matches 'x', so every row matches -v
+        assert_eq!(count(&[], &["a", "b"]), Some(2));
+        assert_eq!(count(&["--include-zero"], &["a", "b"]), Some(2));
+    }
+
+    #[test]
+    fn test_parallel_chunks_keep_row_order() {
+        // Later row groups finish first; each has one match out of 3 rows
+        let scan_group = |position: usize| {
+            std::thread::sleep(std::time::Duration::from_millis(20 * (4 - position as u64)));
+            let row_num = position * 3 + 2;
+            Ok(ChunkMatches {
+                rows_scanned: 3,
+                numbering_span: 0,
+                batches: vec![(vec![(row_num, vec!["x".to_string()], None)], None)],
+            })
+        };
+        with_context(["-f", "table", "x", "-"], |ctx| {
+            let mut search = FileSearch::new("test.parquet", vec!["col".to_string()], None, ctx);
+            search_chunks_parallel(&mut search, 4, 4, 0, &scan_group).unwrap();
+            let row_nums: Vec<usize> = search.output_rows.iter().map(|r| r.row_num).collect();
+            assert_eq!(row_nums, [2, 5, 8, 11]);
+            assert_eq!((search.rows_scanned, search.match_count), (12, 4));
+
+            let failing = |position: usize| {
+                if position == 1 {
+                    anyhow::bail!("bad row group");
+                }
+                Ok(ChunkMatches::default())
+            };
+            let mut search = FileSearch::new("test.parquet", vec!["col".to_string()], None, ctx);
+            assert!(search_chunks_parallel(&mut search, 3, 2, 0, &failing).is_err());
+        });
+
+        // Once the --max-total budget is spent, workers stop taking chunks
+        with_context(["-f", "table", "--max-total", "1", "x", "-"], |ctx| {
+            let mut search = FileSearch::new("test.parquet", vec!["col".to_string()], None, ctx);
+            let scanned = AtomicUsize::new(0);
+            let counting = |position: usize| {
+                scanned.fetch_add(1, AtomicOrdering::Relaxed);
+                scan_group(position % 4)
+            };
+            search_chunks_parallel(&mut search, 40, 2, 0, &counting).unwrap();
+            assert_eq!(search.match_count, 1);
+            assert!(scanned.load(AtomicOrdering::Relaxed) < 10);
+        });
     }
-}
 
-// ── tests ─────────────────────────────────────────────────────────────────────
+    #[test]
+    fn test_context_rows() {
+        let cells = ["a", "x", "b", "c", "x", "d", "e", "f", "x"];
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // Overlapping windows around rows 2 and 5 merge without duplicates
+        assert_eq!(
+            buffered_rows(&["-C", "1", "x"], &cells),
+            vec![
+                (1, true),
+                (2, false),
+                (3, true),
+                (4, true),
+                (5, false),
+                (6, true),
+                (8, true),
+                (9, false),
+            ]
+        );
 
-    fn re(pattern: &str) -> Regex {
-        Regex::new(pattern).unwrap()
+        // -A overrides -C for the trailing side
+        assert_eq!(
+            buffered_rows(&["-C", "1", "-A", "0", "x"], &cells[..5]),
+            vec![(1, true), (2, false), (4, true), (5, false)]
+        );
+
+        // Trailing context is still printed after the max-count is reached
+        assert_eq!(
+            buffered_rows(&["-m", "1", "-A", "2", "x"], &cells),
+            vec![(2, false), (3, true), (4, true)]
+        );
+
+        // --count ignores context entirely
+        assert!(buffered_rows(&["-c", "-C", "2", "x"], &cells).is_empty());
     }
 
     #[test]
-    fn test_row_matches_basic() {
-        let row = vec![
-            "Alice".to_string(),
-            "30".to_string(),
-            "Engineer".to_string(),
+    fn test_unique_rows() {
+        let kept = |args: &[&str]| {
+            let args = ["-f", "table"].iter().chain(args).chain(&["a", "-"]);
+            with_context(args, |ctx| {
+                let headers = vec!["k".to_string(), "v".to_string()];
+                let mut search = FileSearch::new("test.csv", headers, None, ctx);
+                for (i, row) in [["a", "1"], ["a", "2"], ["a", "1"], ["b", "x"], ["a", "3"]]
+                    .iter()
+                    .enumerate()
+                {
+                    let row = row.iter().map(|c| c.to_string()).collect();
+                    search.push_row(i + 1, row).unwrap();
+                }
+                let rows: Vec<usize> = search.output_rows.iter().map(|r| r.row_num).collect();
+                (search.match_count, rows)
+            })
+        };
+
+        assert_eq!(kept(&[]), (4, vec![1, 2, 3, 5]));
+        // Whole-row duplicates collapse onto the first occurrence
+        assert_eq!(kept(&["--unique"]), (3, vec![1, 2, 5]));
+        // Keyed on 'k' alone, later rows differing only in 'v' are dropped too
+        assert_eq!(kept(&["--unique-by", "k"]), (1, vec![1]));
+        // ... and counted once
+        assert_eq!(kept(&["-c", "--unique-by", "k"]), (1, vec![]));
+        // A column the file doesn't have dedups nothing, rather than all
+        assert_eq!(kept(&["--unique-by", "nope"]), (4, vec![1, 2, 3, 5]));
+    }
+
+    #[test]
+    fn test_replace_matches() {
+        let mut row: Vec<String> = vec!["alice@x.org".into(), "bob@y.com".into(), "n/a".into()];
+        replace_matches(&mut row, &re(r"(\w+)@\w+"), &None, "$1@REDACTED");
+        assert_eq!(row, ["alice@REDACTED.org", "bob@REDACTED.com", "n/a"]);
+
+        // Only the searched columns are rewritten
+        let mut row: Vec<String> = vec!["a1".into(), "b1".into()];
+        replace_matches(&mut row, &re(r"\d"), &Some(vec![1]), "#");
+        assert_eq!(row, ["a1", "b#"]);
+    }
+
+    #[test]
+    fn test_count_distinct_values() {
+        let distinct = |args: &[&str]| {
+            let args = ["--count-distinct", "id"]
+                .iter()
+                .chain(args)
+                .chain(&["a", "-"]);
+            with_context(args, |ctx| {
+                let headers = vec!["k".to_string(), "id".to_string()];
+                let mut search = FileSearch::new("test.csv", headers, None, ctx);
+                for (i, row) in [["a", "7"], ["b", "8"], ["a", "7"], ["a", ""], ["a", "9"]]
+                    .iter()
+                    .enumerate()
+                {
+                    let row = row.iter().map(|c| c.to_string()).collect();
+                    search.push_row(i + 1, row).unwrap();
+                }
+                assert!(search.output_rows.is_empty());
+                let (_, counts) = search.value_counts.unwrap();
+                counts.into_iter().collect::<Vec<_>>()
+            })
+        };
+
+        let counted = |v: &str, n| (v.to_string(), n);
+        assert_eq!(
+            distinct(&[]),
+            [counted("", 1), counted("7", 2), counted("9", 1)]
+        );
+        assert_eq!(
+            distinct(&["--skip-null"]),
+            [counted("7", 2), counted("9", 1)]
+        );
+    }
+
+    #[test]
+    fn test_show_columns() {
+        let args = [
+            "-k",
+            "id",
+            "--show-columns",
+            "email,name,missing",
+            "-f",
+            "table",
         ];
+        with_context(args.iter().chain(&["^7$", "-"]), |ctx| {
+            let headers: Vec<String> = ["id", "name", "email"].map(String::from).to_vec();
+            let col_indices = resolve_column_indices(&headers, &ctx.cli.columns, false);
+            let mut search = FileSearch::new("test.csv", headers, col_indices, ctx);
+            // The last row is short a field
+            let rows: [&[&str]; 3] = [&["7", "Ann", "ann@x"], &["8", "7", "7"], &["7", "Bo"]];
+            for (i, row) in rows.iter().enumerate() {
+                let row = row.iter().map(|c| c.to_string()).collect();
+                search.push_row(i + 1, row).unwrap();
+            }
 
-        // Positive match — pattern present in row
-        assert!(row_matches(&row, &re("Alice"), &None, false));
+            // Matched on id alone, shown without it
+            let (headers, rows) = search.display_rows();
+            assert_eq!(headers.as_ref(), ["email", "name"]);
+            let shown: Vec<(usize, &[String])> = rows
+                .iter()
+                .map(|r| (r.row_num, r.cells.as_slice()))
+                .collect();
+            assert_eq!(
+                shown,
+                [
+                    (1, &["ann@x".to_string(), "Ann".to_string()][..]),
+                    (3, &[String::new(), "Bo".to_string()][..]),
+                ]
+            );
+        });
+    }
 
-        // No match — pattern absent
-        assert!(!row_matches(&row, &re("Bob"), &None, false));
+    #[test]
+    fn test_count_by_column() {
+        let tally = |args: &[&str]| {
+            let args = ["--count-by-column"]
+                .iter()
+                .chain(args)
+                .chain(&["tok", "-"]);
+            with_context(args, |ctx| {
+                let headers: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+                let col_indices = resolve_column_indices(&headers, &ctx.cli.columns, false);
+                let mut search = FileSearch::new("test.csv", headers, col_indices, ctx);
+                let rows = [
+                    ["tok", "x", "tok"],
+                    ["y", "tok", "tok"],
+                    ["z", "z", "z"],
+                    ["y", "y", "tok"],
+                ];
+                for (i, row) in rows.iter().enumerate() {
+                    let row = row.iter().map(|c| c.to_string()).collect();
+                    search.push_row(i + 1, row).unwrap();
+                }
+                assert!(search.output_rows.is_empty());
+                let hits = search.column_hits.clone().unwrap();
+                column_tally(&search.headers, &hits)
+                    .into_iter()
+                    .map(|(column, n)| (column.clone(), n))
+                    .collect::<Vec<_>>()
+            })
+        };
 
-        // Inverted: absent pattern → true
-        assert!(row_matches(&row, &re("Bob"), &None, true));
+        let counted = |c: &str, n| (c.to_string(), n);
+        assert_eq!(
+            tally(&[]),
+            [counted("c", 3), counted("a", 1), counted("b", 1)]
+        );
+        assert_eq!(
+            tally(&["--columns", "a,b"]),
+            [counted("a", 1), counted("b", 1)]
+        );
+    }
 
-        // Inverted: present pattern → false
-        assert!(!row_matches(&row, &re("Alice"), &None, true));
+    #[test]
+    fn test_null_checks() {
+        with_context(["--is-null", "b", "--not-null", "c", "x", "-"], |ctx| {
+            let headers = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+            let search = FileSearch::new("test.csv", headers, None, ctx);
+            let row = |cells: [&str; 3]| cells.map(String::from).to_vec();
 
-        // Column filter: match only in column 1 (age), pattern matches
-        assert!(row_matches(&row, &re("30"), &Some(vec![1]), false));
+            // Text formats: empty cells are null
+            assert!(
+                search
+                    .predicate
+                    .passes_filters(&row(["x", "", "1"]), None, None)
+            );
+            assert!(
+                !search
+                    .predicate
+                    .passes_filters(&row(["x", "NULL", "1"]), None, None)
+            );
+            assert!(
+                !search
+                    .predicate
+                    .passes_filters(&row(["x", "", ""]), None, None)
+            );
 
-        // Column filter: restrict to column 0 (name), numeric pattern should not match
-        assert!(!row_matches(&row, &re("30"), &Some(vec![0]), false));
+            // Typed formats decide for themselves, whatever the rendering
+            let b_is_null = |col: usize| col == 1;
+            assert!(search.predicate.passes_filters(
+                &row(["x", "NULL", "1"]),
+                Some(&b_is_null),
+                None
+            ));
+            assert!(
+                search
+                    .predicate
+                    .passes_filters(&row(["x", "", ""]), Some(&b_is_null), None)
+            );
+        });
+    }
 
-        // Regex: match on partial word
-        assert!(row_matches(&row, &re("Eng.*"), &None, false));
+    #[test]
+    fn test_histogram() {
+        use clap::Parser;
 
-        // Case sensitivity: lowercase should not match "Alice" by default
-        assert!(!row_matches(&row, &re("alice"), &None, false));
+        let counts: BTreeMap<String, usize> = [("b", 1), ("c", 5), ("a", 1), ("d", 2)]
+            .into_iter()
+            .map(|(v, n)| (v.to_string(), n))
+            .collect();
+        let buckets = |args: &[&str]| {
+            let cli = Cli::parse_from(
+                ["table-grep", "--group-by", "k"]
+                    .iter()
+                    .chain(args)
+                    .chain(&["x", "-"]),
+            );
+            histogram(&counts, &cli)
+                .into_iter()
+                .map(|(v, n)| format!("{}:{}", v, n))
+                .collect::<Vec<_>>()
+        };
 
-        // Case insensitive via regex flag
-        assert!(row_matches(&row, &re("(?i)alice"), &None, false));
+        assert_eq!(buckets(&[]), ["c:5", "d:2", "a:1", "b:1"]);
+        assert_eq!(buckets(&["--top", "2"]), ["c:5", "d:2"]);
+        assert_eq!(
+            buckets(&["--group-sort", "value"]),
+            ["a:1", "b:1", "c:5", "d:2"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_column_indices() {
+        let headers: Vec<String> = ["name", "age", "", "2024"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let resolve = |cols: &[&str]| {
+            resolve_column_indices(
+                &headers,
+                &Some(cols.iter().map(|s| s.to_string()).collect()),
+                false,
+            )
+        };
+
+        assert_eq!(resolve(&["age", "name"]), Some(vec![1, 0]));
+        // Indices address blank headers, and mix with names
+        assert_eq!(resolve(&["2", "name", "1"]), Some(vec![2, 0, 1]));
+        // A header that looks like a number is matched by name first
+        assert_eq!(resolve(&["2024"]), Some(vec![3]));
+        // Out-of-range tokens are skipped with a warning
+        assert_eq!(resolve(&["4", "age", "nope"]), Some(vec![1]));
+        assert_eq!(resolve_column_indices(&headers, &None, false), None);
+    }
+
+    #[test]
+    fn test_find_column_ignoring_case() {
+        let headers: Vec<String> = ["Email", "email", " Name ", "AGE", "Age2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        // Exact matches always win, with or without the flag
+        assert_eq!(find_column(&headers, "email", false), Some(1));
+        assert_eq!(find_column(&headers, "Email", true), Some(0));
+        // Case and surrounding whitespace only count with the flag
+        assert_eq!(find_column(&headers, "age", false), None);
+        assert_eq!(find_column(&headers, "age", true), Some(3));
+        assert_eq!(find_column(&headers, "name", true), Some(2));
+        // Several case-insensitive candidates and no exact one: ambiguous
+        assert_eq!(find_column(&headers, "EMAIL", true), None);
+
+        assert_eq!(
+            resolve_column_indices(&headers, &Some(vec!["-age".to_string()]), true),
+            Some(vec![0, 1, 2, 4])
+        );
+    }
+
+    #[test]
+    fn test_resolve_column_ranges_and_exclusions() {
+        let headers: Vec<String> = ["id", "name", "password", "email", "-odd"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let resolve = |cols: &[&str]| {
+            resolve_column_indices(
+                &headers,
+                &Some(cols.iter().map(|s| s.to_string()).collect()),
+                false,
+            )
+        };
+
+        assert_eq!(resolve(&["1-3"]), Some(vec![1, 2, 3]));
+        assert_eq!(resolve(&["3-1"]), Some(vec![3, 2, 1]));
+        assert_eq!(resolve(&["3-"]), Some(vec![3, 4]));
+        // Ranges are clipped to the header count
+        assert_eq!(resolve(&["3-9"]), Some(vec![3, 4]));
+        assert_eq!(resolve(&["7-9"]), Some(vec![]));
+
+        // Exclusions alone start from every column
+        assert_eq!(resolve(&["!password"]), Some(vec![0, 1, 3, 4]));
+        assert_eq!(resolve(&["-password", "-0"]), Some(vec![1, 3, 4]));
+        assert_eq!(resolve(&["!1-3"]), Some(vec![0, 4]));
+        // Exclusions win over inclusions
+        assert_eq!(resolve(&["0-3", "!password"]), Some(vec![0, 1, 3]));
+        // A header that starts with '-' is included by name, not excluded
+        assert_eq!(resolve(&["-odd"]), Some(vec![4]));
+    }
+
+    #[test]
+    fn test_flatten_json_object() {
+        let fields = parse_jsonl_record(
+            r#"{"name":"Alice","address":{"city":"NYC","zip":null},"tags":["a","b"],"n":3}"#,
+            "test.jsonl",
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fields,
+            vec![
+                ("name".to_string(), Some("Alice".to_string())),
+                ("address.city".to_string(), Some("NYC".to_string())),
+                ("address.zip".to_string(), None),
+                ("tags".to_string(), Some(r#"["a","b"]"#.to_string())),
+                ("n".to_string(), Some("3".to_string())),
+            ]
+        );
+
+        // Top-level values must be objects
+        assert!(parse_jsonl_record("[1, 2]", "test.jsonl", 1).is_err());
+    }
+
+    #[test]
+    fn test_record_chunks() {
+        let data = b"h\na\n\"b\nc\"\nd\n\"\"\"e\n\"\"\"\nf";
+        let pieces = |n, quote| {
+            record_chunks(data, 2, n, quote)
+                .into_iter()
+                .map(|r| std::str::from_utf8(&data[r]).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Newlines inside quotes (even next to doubled quotes) never split
+        assert_eq!(
+            pieces(20, Some(b'"')),
+            ["a\n", "\"b\nc\"\n", "d\n", "\"\"\"e\n\"\"\"\n", "f"]
+        );
+        assert_eq!(
+            pieces(2, Some(b'"')),
+            ["a\n\"b\nc\"\nd\n", "\"\"\"e\n\"\"\"\nf"]
+        );
+        assert_eq!(
+            pieces(1, Some(b'"')).concat(),
+            std::str::from_utf8(&data[2..]).unwrap()
+        );
+        // Without quoting every newline ends a record
+        assert_eq!(pieces(20, None)[1], "\"b\n");
+    }
+
+    #[test]
+    fn test_sniff_delimiter() {
+        assert_eq!(sniff_delimiter(b"a,b,c\n1,2,3\n", Some(b'"')), b',');
+        assert_eq!(
+            sniff_delimiter(b"name;price\nTea;2,50\nCake;3,10\n", Some(b'"')),
+            b';'
+        );
+        assert_eq!(sniff_delimiter(b"a\tb\n1\t2\n", Some(b'"')), b'\t');
+        assert_eq!(sniff_delimiter(b"a|b|c\nx, y|2|3\n", Some(b'"')), b'|');
+        // Quoted delimiters don't count
+        assert_eq!(
+            sniff_delimiter(b"a;b\n\"1;5\";2\n\"x;y;z\";3\n", Some(b'"')),
+            b';'
+        );
+        assert_eq!(sniff_delimiter(b"\"a,b\";c\n\"d,e\";f\n", Some(b'"')), b';');
+        // No candidate at all: a single column
+        assert_eq!(sniff_delimiter(b"name\nBob\n", Some(b'"')), b',');
+        assert_eq!(sniff_delimiter(b"", Some(b'"')), b',');
+    }
+
+    #[test]
+    fn test_open_maybe_compressed() {
+        let path = Path::new("test_data/test_file_2.csv.gz");
+        let mut text = String::new();
+        open_maybe_compressed(path, "test_file_2.csv.gz")
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.starts_with("city,country,population\n"));
+        assert_eq!(text.lines().count(), 4);
+
+        // Every codec, told by extension or else by magic bytes
+        let csv = b"a,b\n1,2\n";
+        let zstd = zstd::encode_all(&csv[..], 0).unwrap();
+        let mut bzip2 = Vec::new();
+        bzip2::read::BzEncoder::new(&csv[..], bzip2::Compression::fast())
+            .read_to_end(&mut bzip2)
+            .unwrap();
+        let mut xz = Vec::new();
+        xz2::read::XzEncoder::new(&csv[..], 6)
+            .read_to_end(&mut xz)
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("table-grep-codecs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (ext, data) in [("zst", &zstd), ("bz2", &bzip2), ("xz", &xz)] {
+            for name in [format!("t.csv.{}", ext), format!("{}.csv", ext)] {
+                let path = dir.join(name);
+                std::fs::write(&path, data).unwrap();
+                let mut text = Vec::new();
+                open_maybe_compressed(&path, ext)
+                    .unwrap()
+                    .read_to_end(&mut text)
+                    .unwrap();
+                assert_eq!(text, csv);
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_arrow_file() {
+        use arrow::array::{ArrayRef, Int32Array, RecordBatch};
+        use arrow::ipc::writer::{FileWriter, StreamWriter};
+        use std::sync::Arc;
+
+        let ids: ArrayRef = Arc::new(Int32Array::from(vec![1, 2]));
+        let batch = RecordBatch::try_from_iter([("id", ids)]).unwrap();
+
+        let mut file_buf = Vec::new();
+        let mut writer = FileWriter::try_new(&mut file_buf, &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let mut stream_buf = Vec::new();
+        let mut writer = StreamWriter::try_new(&mut stream_buf, &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let mut file = Cursor::new(file_buf);
+        assert!(is_arrow_file(&mut file).unwrap());
+        assert_eq!(file.position(), 0);
+        assert!(!is_arrow_file(&mut Cursor::new(stream_buf)).unwrap());
+        assert!(!is_arrow_file(&mut Cursor::new(b"AR".to_vec())).unwrap());
+    }
+
+    #[test]
+    fn test_files_with_matches_stops_at_first_match() {
+        with_context(["-l", "x", "-"], |ctx| {
+            let mut search = FileSearch::new("test.csv", vec!["col".to_string()], None, ctx);
+            assert!(search.push_row(1, vec!["a".to_string()]).unwrap());
+            assert!(!search.push_row(2, vec!["x".to_string()]).unwrap());
+            assert_eq!(search.match_count, 1);
+            assert!(search.output_rows.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_csv_output_streams() {
+        let kept = |args: &[&str]| {
+            with_context(args.iter().chain(&["x", "-"]), |ctx| {
+                let mut search = FileSearch::new("test.csv", vec!["col".to_string()], None, ctx);
+                for (i, cell) in ["x", "a", "x"].iter().enumerate() {
+                    assert!(search.push_row(i + 1, vec![cell.to_string()]).unwrap());
+                }
+                assert_eq!(search.match_count, 2);
+                (search.streaming, search.output_rows.len())
+            })
+        };
+
+        // CSV rows are printed as they match, context included
+        assert_eq!(kept(&[]), (true, 0));
+        assert_eq!(kept(&["-C", "1"]), (true, 0));
+        // Other formats, sorting and sampling hold every row until the end
+        assert_eq!(kept(&["-f", "table"]), (false, 2));
+        assert_eq!(kept(&["--sort", "col"]), (false, 2));
+        assert_eq!(kept(&["--sample", "5"]), (false, 2));
+    }
+
+    #[test]
+    fn test_read_csv_headers_without_header_row() {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader("a,1,x\nb,2,y\n".as_bytes());
+        assert_eq!(
+            read_csv_headers(&mut rdr).unwrap(),
+            ["col1", "col2", "col3"]
+        );
+        // The first line is still a data row
+        let first = rdr.records().next().unwrap().unwrap();
+        assert_eq!(&first[0], "a");
+
+        let mut rdr = csv::Reader::from_reader("name,age\nb,2\n".as_bytes());
+        assert_eq!(read_csv_headers(&mut rdr).unwrap(), ["name", "age"]);
+    }
+
+    #[test]
+    fn test_numbered_records() {
+        let numbers = |line_numbers: bool| -> Vec<usize> {
+            let mut rdr = csv::Reader::from_path("test_data/test_file_1.csv").unwrap();
+            numbered_records(&mut rdr, "test_file_1.csv", line_numbers)
+                .map(Result::unwrap)
+                .filter(|(_, row)| row[0] == "Alice")
+                .map(|(row_num, _)| row_num)
+                .collect()
+        };
+        // Records 3 and 5 sit on lines 4 and 6, below the header line
+        assert_eq!(numbers(false), [3, 5]);
+        assert_eq!(numbers(true), [4, 6]);
+
+        // A quoted cell spanning lines pushes later records down
+        let mut rdr = csv::Reader::from_reader("a\n\"x\ny\"\nz\n".as_bytes());
+        let lines: Vec<usize> = numbered_records(&mut rdr, "-", true)
+            .map(|r| r.unwrap().0)
+            .collect();
+        assert_eq!(lines, [2, 4]);
+    }
+
+    #[test]
+    fn test_show_location() {
+        // Records start at byte 2 and 8, after the header and a quoted
+        // two-line cell
+        let mut rdr = csv::Reader::from_reader("a\n\"x\ny\"\nz\n".as_bytes());
+        let offsets: Vec<u64> = located_records(&mut rdr, "-", false)
+            .map(|r| r.unwrap().2)
+            .collect();
+        assert_eq!(offsets, [2, 8]);
+
+        // Row groups of 3, 3 and 1 rows
+        let locator = Locator::RowGroups(vec![1, 4, 7]);
+        assert_eq!(locator.headers(), ["_row_group", "_row_in_group"]);
+        assert_eq!(locator.cells(1), ["0", "0"]);
+        assert_eq!(locator.cells(3), ["0", "2"]);
+        assert_eq!(locator.cells(4), ["1", "0"]);
+        assert_eq!(locator.cells(7), ["2", "0"]);
+
+        let locator = Locator::ByteOffsets {
+            recent: VecDeque::new(),
+            printed: HashMap::from([(2, 51)]),
+        };
+        assert_eq!(locator.cells(2), ["51"]);
+        assert_eq!(locator.cells(3), [""]);
+    }
+
+    #[test]
+    fn test_fit_row_to_headers() {
+        use clap::Parser;
+
+        let data = "a,b,c\n1,2,3\nshort,row\n4,5,6,extra\n7,8,9\n";
+        let rows = |args: &[&str]| {
+            let cli = Cli::parse_from(["table-grep"].iter().chain(args).chain(&["x", "-"]));
+            let mut rdr = csv_reader_builder(&cli, b',').from_reader(data.as_bytes());
+            let width = read_csv_headers(&mut rdr).unwrap().len();
+            numbered_records(&mut rdr, "-", false)
+                .map(|result| {
+                    let (row_num, mut row) = result.unwrap();
+                    fit_row_to_headers(&mut row, row_num, width, "-", &cli);
+                    row
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            rows(&["--strict-columns"]),
+            [
+                vec!["1", "2", "3"],
+                vec!["short", "row"],
+                vec!["4", "5", "6", "extra"],
+                vec!["7", "8", "9"],
+            ]
+        );
+        assert_eq!(
+            rows(&["--pad"]),
+            [
+                vec!["1", "2", "3"],
+                vec!["short", "row", ""],
+                vec!["4", "5", "6"],
+                vec!["7", "8", "9"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encoding() {
+        let search = |args: &[&str], path: &str| {
+            search_with(["-q"].iter().chain(args), path).map(|stats| stats.matches)
+        };
+
+        let latin1 = "test_data/latin1.csv";
+        assert_eq!(
+            search(&["--encoding", "latin1", "Zürich"], latin1).unwrap(),
+            1
+        );
+        assert_eq!(
+            search(&["--encoding", "latin1", "-x", "Besançon"], latin1).unwrap(),
+            1
+        );
+        // Not UTF-8
+        assert!(search(&["Zürich"], latin1).is_err());
+
+        // A UTF-8 BOM is dropped from the first header, and wins over --encoding
+        let dir = std::env::temp_dir().join(format!("table-grep-bom-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bom = dir.join("bom.csv");
+        std::fs::write(&bom, "\u{feff}id,city\n1,Zürich\n").unwrap();
+        let bom = bom.to_str().unwrap();
+        for args in [
+            &["-k", "id", "-x", "1"][..],
+            &["--split-regex", ",", "-k", "id", "-x", "1"],
+            &["--encoding", "latin1", "-k", "id", "-x", "1"],
+            &["--encoding", "latin1", "-k", "city", "-x", "Zürich"],
+        ] {
+            assert_eq!(search(args, bom).unwrap(), 1, "{:?}", args);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_split() {
+        use clap::Parser;
+
+        let data = "id || name || note\n1 || Ann || a,\"b\"\n\n2 || Bob\n3||Cy||c\n";
+        let search = |args: &[&str]| {
+            let args = ["-c", "--split-regex", r"\s*\|\|\s*"].iter().chain(args);
+            let stats = with_context(args.chain(&["-"]), |ctx| {
+                search_csv(data.as_bytes(), "-", None, ctx).unwrap()
+            });
+            (stats.rows_scanned, stats.matches)
+        };
+
+        // Commas and quotes are just text; the blank line is skipped
+        assert_eq!(search(&["-k", "note", "-x", r#"a,"b""#]), (3, 1));
+        assert_eq!(search(&["-k", "name", "-x", "Cy"]), (3, 1));
+        assert_eq!(search(&["--no-header-row", "-k", "col2", "name"]), (4, 1));
+        assert!(Cli::try_parse_from(["table-grep", "--split-regex", r"\s*", "x", "-"]).is_err());
+    }
+
+    #[test]
+    fn test_head_and_tail() {
+        use clap::Parser;
+
+        let kept = |args: &[&str]| -> Vec<usize> {
+            let cli = Cli::parse_from(["table-grep"].iter().chain(args).chain(&["x", "f.csv"]));
+            head_and_tail((1..=10).map(Ok), &cli)
+                .map(Result::unwrap)
+                .collect()
+        };
+        assert_eq!(kept(&["--head", "3"]), [1, 2, 3]);
+        assert_eq!(kept(&["--tail", "3"]), [8, 9, 10]);
+        assert_eq!(kept(&["--head", "5", "--tail", "2"]), [4, 5]);
+        assert_eq!(kept(&["--tail", "20"]).len(), 10);
+
+        // A bad row still surfaces from behind --tail's buffer
+        let rows = (1..=3).map(|n| {
+            if n == 2 {
+                Err(anyhow::anyhow!("bad"))
+            } else {
+                Ok(n)
+            }
+        });
+        let cli = Cli::parse_from(["table-grep", "--tail", "1", "x", "f.csv"]);
+        assert!(head_and_tail(rows, &cli).any(|r| r.is_err()));
+    }
+
+    #[test]
+    fn test_csv_quoting_options() {
+        use clap::Parser;
+
+        let records = |args: &[&str], data: &[u8]| -> Vec<Vec<String>> {
+            let cli = Cli::parse_from(["table-grep"].iter().chain(args).chain(&["x", "-"]));
+            let mut rdr = csv_reader_builder(&cli, b',').from_reader(data);
+            numbered_records(&mut rdr, "-", false)
+                .map(|r| r.unwrap().1)
+                .collect()
+        };
+
+        // A single-quoted field spanning lines is one record
+        let data = std::fs::read("test_data/single_quoted.csv").unwrap();
+        let rows = records(&["--quote", "'"], &data);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], ["1", "multi\nline, with comma"]);
+        assert_eq!(rows[2], ["3", "it's quoted"]);
+        // ... but with the default quote it splits into misaligned rows
+        assert_eq!(records(&[], &data).len(), 4);
+
+        let data = b"a,b\n\"x,y\",2\n";
+        assert_eq!(records(&[], data), [["x,y", "2"]]);
+        assert_eq!(records(&["--no-quoting"], data), [["\"x", "y\"", "2"]]);
+
+        let data = b"a,b\n  active , x\n";
+        assert_eq!(records(&[], data), [["  active ", " x"]]);
+        assert_eq!(records(&["--trim"], data), [["active", "x"]]);
+
+        let data = b"a,b\n\"say \\\"hi\\\"\",2\n";
+        assert_eq!(records(&["--escape", "\\"], data), [["say \"hi\"", "2"]]);
+    }
+
+    #[test]
+    fn test_temporal_values_to_string() {
+        use arrow::array::*;
+
+        let utc = ValueFormat::default();
+        // 2024-03-01T12:30:00.5 UTC
+        let micros = 1_709_296_200_500_000i64;
+
+        let naive = TimestampMicrosecondArray::from(vec![micros]);
+        assert_eq!(
+            array_value_to_string(&naive, 0, &utc),
+            "2024-03-01T12:30:00.500"
+        );
+
+        let zoned = TimestampMicrosecondArray::from(vec![micros]).with_timezone("+02:00");
+        assert_eq!(
+            array_value_to_string(&zoned, 0, &utc),
+            "2024-03-01T12:30:00.500+00:00"
+        );
+        let tokyo = ValueFormat {
+            timezone: Some("Asia/Tokyo".parse().unwrap()),
+            ..ValueFormat::default()
+        };
+        assert_eq!(
+            array_value_to_string(&zoned, 0, &tokyo),
+            "2024-03-01T21:30:00.500+09:00"
+        );
+
+        let seconds = TimestampSecondArray::from(vec![0]);
+        assert_eq!(
+            array_value_to_string(&seconds, 0, &utc),
+            "1970-01-01T00:00:00"
+        );
+
+        let time = Time64MicrosecondArray::from(vec![45_296_000_001]);
+        assert_eq!(array_value_to_string(&time, 0, &utc), "12:34:56.000001");
+        let time = Time32SecondArray::from(vec![60]);
+        assert_eq!(array_value_to_string(&time, 0, &utc), "00:01:00");
+
+        let duration = DurationMillisecondArray::from(vec![90_250]);
+        assert_eq!(array_value_to_string(&duration, 0, &utc), "PT90.25S");
+
+        // 2024-01-01 as days and as milliseconds
+        let date32 = Date32Array::from(vec![19_723]);
+        let date64 = Date64Array::from(vec![19_723 * 86_400_000]);
+        assert_eq!(array_value_to_string(&date32, 0, &utc), "2024-01-01");
+        assert_eq!(array_value_to_string(&date64, 0, &utc), "2024-01-01");
+    }
+
+    #[test]
+    fn test_decimal_values_to_string() {
+        use arrow::array::*;
+        use arrow::datatypes::i256;
+
+        let fmt = ValueFormat::default();
+        let render = |array: &dyn Array| -> Vec<String> {
+            (0..array.len())
+                .map(|i| array_value_to_string(array, i, &fmt))
+                .collect()
+        };
+
+        let money = Decimal128Array::from(vec![1234, -5, 100, 0])
+            .with_precision_and_scale(10, 2)
+            .unwrap();
+        assert_eq!(render(&money), ["12.34", "-0.05", "1.00", "0.00"]);
+
+        let whole = Decimal128Array::from(vec![-42])
+            .with_precision_and_scale(5, 0)
+            .unwrap();
+        assert_eq!(render(&whole), ["-42"]);
+
+        let wide = Decimal256Array::from(vec![i256::from_i128(-123_456_789)])
+            .with_precision_and_scale(40, 4)
+            .unwrap();
+        assert_eq!(render(&wide), ["-12345.6789"]);
+    }
+
+    #[test]
+    fn test_nested_values_to_json() {
+        use arrow::array::*;
+        use arrow::datatypes::{Field, Int32Type};
+        use std::sync::Arc;
+
+        let fmt = ValueFormat::default();
+
+        let tags = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), None, Some(3)]),
+            None,
+            Some(vec![]),
+        ]);
+        assert_eq!(array_value_to_string(&tags, 0, &fmt), "[1,null,3]");
+        assert_eq!(array_value_to_string(&tags, 1, &fmt), "NULL");
+        let blank = ValueFormat {
+            null_text: String::new(),
+            ..ValueFormat::default()
+        };
+        assert_eq!(array_value_to_string(&tags, 1, &blank), "");
+        assert_eq!(array_value_to_string(&tags, 2, &fmt), "[]");
+
+        let city: ArrayRef = Arc::new(StringArray::from(vec![Some("NYC"), None]));
+        let zip: ArrayRef = Arc::new(Int32Array::from(vec![10001, 2]));
+        let address = StructArray::from(vec![
+            (Arc::new(Field::new("city", DataType::Utf8, true)), city),
+            (Arc::new(Field::new("zip", DataType::Int32, false)), zip),
+        ]);
+        assert_eq!(
+            array_value_to_string(&address, 0, &fmt),
+            r#"{"city":"NYC","zip":10001}"#
+        );
+        assert_eq!(
+            array_value_to_string(&address, 1, &fmt),
+            r#"{"city":null,"zip":2}"#
+        );
+    }
+
+    #[test]
+    fn test_binary_values_to_string() {
+        use arrow::array::*;
+
+        let hex = ValueFormat::default();
+        let base64 = ValueFormat {
+            binary_encoding: BinaryEncoding::Base64,
+            ..ValueFormat::default()
+        };
+
+        let blobs = BinaryArray::from(vec![&b"\x00\xffab"[..]]);
+        assert_eq!(array_value_to_string(&blobs, 0, &hex), "00ff6162");
+        assert_eq!(array_value_to_string(&blobs, 0, &base64), "AP9hYg==");
+
+        let hashes = FixedSizeBinaryArray::try_from_iter(
+            vec![vec![0xde, 0xad], vec![0xbe, 0xef]].into_iter(),
+        )
+        .unwrap();
+        assert_eq!(array_value_to_string(&hashes, 1, &hex), "beef");
+    }
+
+    #[test]
+    fn test_only_matching_cells_with_invert() {
+        use clap::Parser;
+
+        let row: Vec<String> = ["apple", "banana", "cherry", "mango"]
+            .map(String::from)
+            .to_vec();
+        let pattern = re("an");
+
+        assert_eq!(only_matching_cells(&row, &pattern, &None, false), [1, 3]);
+        assert_eq!(
+            only_matching_cells(&row, &pattern, &Some(vec![3, 2]), false),
+            [3]
+        );
+        // Inverted: the cells that don't match
+        assert_eq!(only_matching_cells(&row, &pattern, &None, true), [0, 2]);
+        assert_eq!(
+            only_matching_cells(&row, &pattern, &Some(vec![3, 2, 1]), true),
+            [2]
+        );
+
+        let cli = Cli::parse_from(["tg", "--only-non-matching", "an", "f.csv"]);
+        assert!(cli.lists_cells() && !cli.invert);
+        // Cell-level and row-level inversion don't mix
+        for other in ["-o", "-v"] {
+            assert!(
+                Cli::try_parse_from(["tg", "--only-non-matching", other, "an", "f.csv"]).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_total_budget() {
+        let path = Path::new("test_data/test_file_1.csv");
+
+        // Two Alices fit in the budget of three; only one more can follow
+        with_context(["-c", "--max-total", "3", "Alice", "-"], |ctx| {
+            let first = search_file(path, ctx).unwrap().matches;
+            assert_eq!(first, 2);
+            assert!(!ctx.spend_budget(first));
+            let second = search_file(path, ctx).unwrap().matches;
+            assert_eq!(second, 1);
+            assert!(ctx.spend_budget(second));
+            assert_eq!(ctx.match_budget.get(), Some(0));
+        });
+    }
+
+    #[test]
+    fn test_rewrite_csv() {
+        let dir = std::env::temp_dir().join(format!("table-grep-rewrite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out.csv");
+
+        let stats = with_context(["-k", "name", "-r", "X", "Ali", "-"], |ctx| {
+            let file = std::fs::File::create(&out).unwrap();
+            rewrite_table(Path::new("test_data/test_file_1.csv"), file, ctx).unwrap()
+        });
+        assert_eq!(stats.matches, 2);
+        // Only the matched cells change; every other row is copied as-is
+        let original = std::fs::read_to_string("test_data/test_file_1.csv").unwrap();
+        let rewritten = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(rewritten, original.replace("Alice", "Xce"));
+
+        let rewrite = |args: &[&str], input: &[u8]| {
+            let path = dir.join("in.csv");
+            std::fs::write(&path, input).unwrap();
+            with_context(["-r", "X"].iter().chain(args).chain(&["-"]), |ctx| {
+                let file = std::fs::File::create(&out).unwrap();
+                rewrite_table(&path, file, ctx).unwrap();
+            });
+            std::fs::read(&out).unwrap()
+        };
+        // Line endings, a BOM and unmatched cells' padding are all kept
+        assert_eq!(
+            rewrite(&["b"], b"id,v\r\n1,a\r\n2,b\r\n3,c"),
+            b"id,v\r\n1,a\r\n2,X\r\n3,c"
+        );
+        assert_eq!(
+            rewrite(&["-k", "v", "c"], b"id,v\n1,a\n\n2,c"),
+            b"id,v\n1,a\n\n2,X"
+        );
+        assert_eq!(
+            rewrite(&["b"], b"\xef\xbb\xbfid,v\n1,b\n"),
+            b"\xef\xbb\xbfid,v\n1,X\n"
+        );
+        assert_eq!(
+            rewrite(&["--trim", "-x", "b"], b"id , v\n 1 , a \n 2 ,  b \n"),
+            b"id , v\n 1 , a \n 2 ,  X \n"
+        );
+        // Quotes are written back as the file has them
+        assert_eq!(
+            rewrite(
+                &["--no-quoting", "-k", "v", "b"],
+                b"id,v\n1,\"a\"\n2,b\"c\n"
+            ),
+            b"id,v\n1,\"a\"\n2,X\"c\n"
+        );
+        assert_eq!(
+            rewrite(
+                &["--escape", "\\", "-k", "v", "b"],
+                b"id,v\n1,\"a\\\"q\"\n2,\"b\\\"c\"\n"
+            ),
+            b"id,v\n1,\"a\\\"q\"\n2,\"X\\\"c\"\n"
+        );
+        // Text in another encoding is written back in it
+        let latin1 = std::fs::read("test_data/latin1.csv").unwrap();
+        assert_eq!(
+            rewrite(&["--encoding", "latin1", "-k", "id", "2"], &latin1),
+            b"id,city\n1,Z\xfcrich\nX,S\xe3o Paulo\n3,Besan\xe7on\n"
+        );
+        assert_eq!(
+            rewrite(&["--encoding", "utf-16le", "b"], b"\xff\xfev\0\n\0b\0\n\0"),
+            b"\xff\xfev\0\n\0X\0\n\0"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_parquet() {
+        use arrow::array::{ArrayRef, ListBuilder, RecordBatch, StringArray, StringBuilder};
+
+        let name: ArrayRef = Arc::new(StringArray::from(vec!["alice", "bob"]));
+        let mut tags = ListBuilder::new(StringBuilder::new());
+        tags.values().append_value("alice");
+        tags.append(true);
+        tags.values().append_value("x");
+        tags.append(true);
+        let tags: ArrayRef = Arc::new(tags.finish());
+        let batch = RecordBatch::try_from_iter([("name", name), ("tags", tags)]).unwrap();
+        let input = write_temp_parquet("rewrite-in", &batch, None);
+        let output = input.with_file_name(format!(
+            "table-grep-rewrite-out-{}.parquet",
+            std::process::id()
+        ));
+
+        let rewrite = |args: &[&str]| {
+            with_context(["-r", "X"].iter().chain(args).chain(&["."]), |ctx| {
+                rewrite_table(&input, File::create(&output).unwrap(), ctx)
+            })
+        };
+        // 'alice' in the list column would be left in the copy
+        let err = rewrite(&["alice"]).unwrap_err();
+        assert!(err.to_string().contains("'tags'"), "{}", err);
+        // ... unless it isn't searched
+        assert_eq!(rewrite(&["-k", "name", "alice"]).unwrap().matches, 1);
+        let rewritten = search_with(["-k", "name", "X"], &output).unwrap();
+        assert_eq!(rewritten.matches, 1);
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+
+    #[test]
+    fn test_count_from_stats() {
+        use arrow::array::{ArrayRef, RecordBatch, StringArray};
+
+        // Row groups of two: all "ok", mixed, none "ok", and "ok" with a null
+        let status: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("ok"),
+            Some("ok"),
+            Some("ok"),
+            Some("fail"),
+            Some("fail"),
+            Some("fail"),
+            Some("ok"),
+            None,
+        ]));
+        let batch = RecordBatch::try_from_iter([("status", status)]).unwrap();
+        let path = write_temp_parquet("stats", &batch, Some(2));
+
+        let count = |args: &[&str]| {
+            let args = ["-c", "-k", "status", "-x"].iter().chain(args);
+            let stats = search_with(args.chain(&["ok"]), &path).unwrap();
+            (stats.matches, stats.rows_scanned)
+        };
+
+        // Only the mixed row group is read
+        assert_eq!(count(&[]), (4, 2));
+        assert_eq!(count(&["--no-stats"]), (4, 8));
+        // ...and with --estimate, none is, counting the mixed one in full
+        assert_eq!(count(&["--estimate"]), (5, 0));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_format_numbers_skips_stats() {
+        use arrow::array::{ArrayRef, Int64Array, RecordBatch};
+
+        let amount: ArrayRef = Arc::new(Int64Array::from(vec![999, 1234, 5000]));
+        let batch = RecordBatch::try_from_iter([("amount", amount)]).unwrap();
+        let path = write_temp_parquet("format-numbers", &batch, None);
+
+        // "1,234" is no integer rendering, so statistics would rule the row
+        // group out, yet the grouped form of 1234 matches it
+        let matches = |args: &[&str]| {
+            let args = ["--format-numbers", "-k", "amount", "-x"]
+                .iter()
+                .chain(args);
+            search_with(args.chain(&["1,234"]), &path).unwrap().matches
+        };
+        assert_eq!(matches(&[]), 1);
+        assert_eq!(matches(&["-c"]), 1);
+        assert_eq!(matches(&["--no-stats"]), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stats_respect_null_text() {
+        use arrow::array::{ArrayRef, RecordBatch, StringArray};
+
+        let v: ArrayRef = Arc::new(StringArray::from(vec![Some("a"), None, Some("b")]));
+        let batch = RecordBatch::try_from_iter([("v", v)]).unwrap();
+        let path = write_temp_parquet("null-text", &batch, None);
+
+        // The null cell renders as N/A, which the min/max of "a" and "b" rule
+        // out, so statistics mustn't be used for it
+        let matches = |args: &[&str]| {
+            let args = ["--null-text", "N/A", "-k", "v", "-x"].iter().chain(args);
+            search_with(args.chain(&["N/A"]), &path).unwrap().matches
+        };
+        assert_eq!(matches(&[]), 1);
+        assert_eq!(matches(&["-c"]), 1);
+        assert_eq!(matches(&["--no-stats"]), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let stats =
+            |pattern: &str| search_with(["-c", pattern], "test_data/test_file_1.csv").unwrap();
+
+        let hit = stats("Alice");
+        let miss = stats("Zed");
+        assert_eq!(
+            hit,
+            SearchStats {
+                files_searched: 1,
+                files_matched: 1,
+                rows_scanned: 6,
+                matches: 2,
+                files_failed: 0,
+            }
+        );
+        assert_eq!(
+            (miss.files_matched, miss.rows_scanned, miss.matches),
+            (0, 6, 0)
+        );
+
+        let mut total = hit;
+        total += miss;
+        assert_eq!(
+            total,
+            SearchStats {
+                files_searched: 2,
+                files_matched: 1,
+                rows_scanned: 12,
+                matches: 2,
+                files_failed: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_whole_row() {
+        use crate::searcher::Searcher;
+        use clap::Parser;
+
+        let row: Vec<String> = ["Alice", "30", "Engineer"].map(String::from).into();
+        assert_eq!(joined_row(&row, &None, ","), "Alice,30,Engineer");
+        assert_eq!(joined_row(&row, &Some(vec![2, 0]), "\t"), "Engineer\tAlice");
+
+        let matches = |args: &[&str]| {
+            let cli = Cli::parse_from(["table-grep"].iter().chain(args).chain(&["."]));
+            Searcher::new(cli)
+                .unwrap()
+                .search_file("test_data/test_file_1.csv")
+                .unwrap()
+                .map(|row| row.row_num)
+                .collect::<Vec<_>>()
+        };
+        // A pattern crossing the delimiter only matches the joined row
+        assert_eq!(matches(&["Alice,30"]), Vec::<usize>::new());
+        assert_eq!(matches(&["--whole-row", "Alice,30"]), [3]);
+        assert_eq!(matches(&["--whole-row", "-x", "Bob,45,Manager"]), [1]);
+        // Only the selected columns are joined, in the order given
+        assert_eq!(
+            matches(&["--whole-row", "-k", "position,name", "Engineer,Alice"]),
+            [3]
+        );
+        assert_eq!(
+            matches(&["--whole-row", "-k", "position,name", "Alice,30"]),
+            Vec::<usize>::new()
+        );
+
+        // A TSV's cells are joined with tabs, as its rows are printed
+        let tsv =
+            std::env::temp_dir().join(format!("table-grep-whole-row-{}.tsv", std::process::id()));
+        std::fs::write(&tsv, "name\tage\nAlice\t30\n").unwrap();
+        let cli = Cli::parse_from(["table-grep", "--whole-row", "Alice\t30", "."]);
+        let rows = Searcher::new(cli).unwrap().search_file(&tsv).unwrap();
+        assert_eq!(rows.count(), 1);
+        let cli = Cli::parse_from(["table-grep", "--whole-row", "Alice,30", "."]);
+        let rows = Searcher::new(cli).unwrap().search_file(&tsv).unwrap();
+        assert_eq!(rows.count(), 0);
+        std::fs::remove_file(&tsv).unwrap();
+
+        assert!(Cli::try_parse_from(["table-grep", "--whole-row", "-o", "a", "."]).is_err());
+        // --replace works cell by cell, so it would miss a match across cells
+        let args = ["table-grep", "--whole-row", "-r", "X", "Alice,30", "."];
+        assert!(Cli::try_parse_from(args).is_err());
+        let args = [
+            "table-grep",
+            "--whole-row",
+            "-r",
+            "X",
+            "--in-place",
+            "Alice,30",
+            ".",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_sampler() {
+        // Keep one of ten matches, many times over: each should be kept
+        // about a tenth of the time
+        let mut sampler = Sampler::new(1, Some(7));
+        let mut kept = [0usize; 10];
+        for _ in 0..10_000 {
+            let mut reservoir = None;
+            for seen in 1..=10 {
+                if sampler.slot(seen).is_some() {
+                    reservoir = Some(seen);
+                }
+            }
+            kept[reservoir.unwrap() - 1] += 1;
+        }
+        assert!(kept.iter().all(|&n| (800..1200).contains(&n)), "{:?}", kept);
+
+        // The first matches fill the reservoir; a seed repeats the draw
+        let draw = |seed| {
+            let mut sampler = Sampler::new(3, Some(seed));
+            (1..=100).map(|seen| sampler.slot(seen)).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(1)[..3], [Some(0), Some(1), Some(2)]);
+        assert_eq!(draw(1), draw(1));
+        assert_ne!(draw(1), draw(2));
+
+        // A match limit would cap the matches drawn from, not the sample
+        use clap::Parser;
+        let args = ["table-grep", "--sample", "3", "--max-total", "3", "x", "."];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_exclude_pattern() {
+        use crate::searcher::Searcher;
+        use clap::Parser;
+
+        let matches = |args: &[&str]| {
+            let cli = Cli::parse_from(["table-grep"].iter().chain(args).chain(&["."]));
+            Searcher::new(cli)
+                .unwrap()
+                .search_file("test_data/test_file_1.csv")
+                .unwrap()
+                .map(|row| row.row_num)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches(&["Alice"]), [3, 5]);
+        // Alice, but not the designer
+        assert_eq!(matches(&["--exclude-pattern", "Design", "Alice"]), [3]);
+        assert_eq!(
+            matches(&["-i", "--exclude-pattern", "design", "alice"]),
+            [3]
+        );
+        assert_eq!(
+            matches(&[
+                "--exclude-pattern",
+                "Design",
+                "--exclude-pattern",
+                "30",
+                "Alice"
+            ]),
+            Vec::<usize>::new()
+        );
+        // Only the searched columns are tested, unless --exclude-columns says
+        let args = ["-k", "name", "--exclude-pattern", "Design", "Alice"];
+        assert_eq!(matches(&args), [3, 5]);
+        let args = ["-k", "name", "--exclude-columns", "position"];
+        assert_eq!(
+            matches(&[&args[..], &["--exclude-pattern", "Design", "Alice"]].concat()),
+            [3]
+        );
     }
 }
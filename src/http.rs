@@ -0,0 +1,171 @@
+//! Reading tables from http:// and https:// URLs (the `http` feature).
+//! Parquet is read with ranged requests, so only the footer and the column
+//! chunks searched are downloaded.
+
+/// Whether `path` names an HTTP(S) URL rather than a local path.
+pub fn is_url(path: &str) -> bool {
+    let scheme = path.split_once("://").map(|(scheme, _)| scheme);
+    scheme.is_some_and(|s| s.eq_ignore_ascii_case("http") || s.eq_ignore_ascii_case("https"))
+}
+
+/// The path part of a URL, without its query string or fragment, for
+/// telling its format from the extension (`data.parquet?X-Amz-...`).
+#[cfg(feature = "http")]
+pub fn url_path(url: &str) -> &str {
+    let end = url.find(['?', '#']).unwrap_or(url.len());
+    &url[..end]
+}
+
+#[cfg(feature = "http")]
+pub use remote::*;
+
+#[cfg(feature = "http")]
+mod remote {
+    use crate::ranged::{RangeSource, RangedFile};
+    use anyhow::Result;
+    use bytes::Bytes;
+    use std::io::Read;
+    use std::sync::LazyLock;
+
+    static AGENT: LazyLock<ureq::Agent> = LazyLock::new(|| {
+        ureq::AgentBuilder::new()
+            .try_proxy_from_env(true)
+            .user_agent(concat!("table-grep/", env!("CARGO_PKG_VERSION")))
+            .build()
+    });
+
+    /// Start downloading `url`, optionally just the bytes in `range`.
+    fn get(url: &str, range: Option<(u64, u64)>) -> Result<ureq::Response> {
+        let mut request = AGENT.get(url);
+        if let Some((first, last)) = range {
+            request = request.set("Range", &format!("bytes={}-{}", first, last));
+        }
+        request.call().map_err(|e| match e {
+            ureq::Error::Status(code, response) => anyhow::anyhow!(
+                "Failed to fetch '{}': HTTP {} {}",
+                url,
+                code,
+                response.status_text()
+            ),
+            e => anyhow::anyhow!("Failed to fetch '{}': {}", url, e),
+        })
+    }
+
+    /// The body of `url`, streamed.
+    pub fn open(url: &str) -> Result<Box<dyn Read + Send + Sync>> {
+        Ok(get(url, None)?.into_reader())
+    }
+
+    /// The whole body of `url`, in memory.
+    pub fn download(url: &str) -> Result<Bytes> {
+        let mut body = Vec::new();
+        open(url)?
+            .read_to_end(&mut body)
+            .map_err(|e| anyhow::anyhow!("Failed to download '{}': {}", url, e))?;
+        Ok(Bytes::from(body))
+    }
+
+    /// A remote Parquet file: either read piecemeal with range requests, or,
+    /// when the server doesn't support them, downloaded whole.
+    pub enum RemoteParquet {
+        Ranged(RangedFile<HttpFile>),
+        Downloaded(Bytes),
+    }
+
+    impl RemoteParquet {
+        /// Probe `url` with a one-byte range request, which also tells the
+        /// file's size.
+        pub fn open(url: &str) -> Result<Self> {
+            let response = get(url, Some((0, 0)))?;
+            if response.status() == 206
+                && let Some(len) = response.header("Content-Range").and_then(content_length)
+            {
+                return Ok(Self::Ranged(RangedFile::new(HttpFile {
+                    url: url.to_string(),
+                    len,
+                })));
+            }
+
+            // The server sent the whole file instead.
+            let mut body = Vec::new();
+            response
+                .into_reader()
+                .read_to_end(&mut body)
+                .map_err(|e| anyhow::anyhow!("Failed to download '{}': {}", url, e))?;
+            Ok(Self::Downloaded(Bytes::from(body)))
+        }
+    }
+
+    /// The total size in a `Content-Range: bytes 0-0/12345` header.
+    pub(super) fn content_length(content_range: &str) -> Option<u64> {
+        let (_, total) = content_range.strip_prefix("bytes ")?.split_once('/')?;
+        total.trim().parse().ok()
+    }
+
+    /// A file on a server that supports range requests.
+    pub struct HttpFile {
+        url: String,
+        len: u64,
+    }
+
+    impl RangeSource for HttpFile {
+        fn name(&self) -> &str {
+            &self.url
+        }
+
+        fn size(&self) -> u64 {
+            self.len
+        }
+
+        fn get_range(&self, start: u64, length: u64) -> Result<Bytes> {
+            let response = get(&self.url, Some((start, start + length - 1)))?;
+            if response.status() != 206 {
+                anyhow::bail!(
+                    "'{}' ignored a range request (HTTP {})",
+                    self.url,
+                    response.status()
+                );
+            }
+            let mut body = Vec::with_capacity(length as usize);
+            response
+                .into_reader()
+                .read_to_end(&mut body)
+                .map_err(|e| anyhow::anyhow!("Failed to download '{}': {}", self.url, e))?;
+            Ok(Bytes::from(body))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://host/data.parquet"));
+        assert!(is_url("HTTP://host/data.csv"));
+        assert!(!is_url("data/https.csv"));
+        assert!(!is_url("s3://bucket/key.parquet"));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_url_path() {
+        assert_eq!(
+            url_path("https://host/a/data.parquet?X-Amz-Signature=abc#frag"),
+            "https://host/a/data.parquet"
+        );
+        assert_eq!(
+            url_path("https://host/data.csv.gz"),
+            "https://host/data.csv.gz"
+        );
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_content_length() {
+        assert_eq!(content_length("bytes 0-0/12345"), Some(12345));
+        assert_eq!(content_length("bytes 0-0/*"), None);
+        assert_eq!(content_length("items 0-0/5"), None);
+    }
+}
@@ -0,0 +1,50 @@
+//! Search CSV, Parquet and other table files like grep, row by row.
+//!
+//! The `table-grep` binary is a thin wrapper over this library. To embed the
+//! search, build a [`Searcher`] from the same options the command line takes
+//! and collect the matching rows of each file as [`MatchRow`]s:
+//!
+//! ```
+//! use clap::Parser;
+//! use table_grep::{Cli, Searcher};
+//!
+//! let options = Cli::try_parse_from(["table-grep", "-k", "name", "Alice", "."])?;
+//! let searcher = Searcher::new(options)?;
+//! for row in searcher.search_file("test_data/test_file_1.csv")? {
+//!     println!("{}:{}: {}", row.file, row.row_num, row.cells.join(","));
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The modules that aren't documented here are shared with the binary and
+//! aren't a stable API.
+
+mod arith;
+pub mod cli;
+#[doc(hidden)]
+pub mod config;
+mod filter;
+#[doc(hidden)]
+pub mod grep;
+#[doc(hidden)]
+pub mod http;
+pub mod output;
+#[doc(hidden)]
+pub mod progress;
+mod query;
+#[cfg(any(feature = "http", feature = "object-store"))]
+mod ranged;
+#[doc(hidden)]
+pub mod rewrite;
+mod searcher;
+#[doc(hidden)]
+pub mod sink;
+mod sort;
+mod stats;
+#[doc(hidden)]
+pub mod store;
+
+pub use cli::Cli;
+pub use grep::{SearchStats, resolve_column_indices, row_matches};
+pub use output::Printer;
+pub use searcher::{MatchRow, Searcher};
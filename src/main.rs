@@ -1,54 +1,500 @@
-mod cli;
-mod grep;
-mod output;
-
 use anyhow::Result;
-use clap::Parser;
-use cli::Cli;
+use ignore::{DirEntry, WalkBuilder};
+use std::cell::RefCell;
 use std::path::Path;
-use walkdir::WalkDir;
+use std::time::{Duration, Instant};
+use table_grep::cli::{Cli, FileGlobs, FileOrder, InputFormat, OutputFormat};
+use table_grep::grep::{self, SearchContext, SearchStats};
+use table_grep::output::Printer;
+use table_grep::progress::Progress;
+use table_grep::rewrite::Rewriter;
+use table_grep::sink::OutputSink;
+use table_grep::{config, http, report, store};
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+/// Exit with grep's conventions: 0 if anything matched, 1 if nothing did, and
+/// 2 on error (including a file skipped after an error, unless -q found a
+/// match).
+fn main() {
+    let code = match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            2
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Run the search. Returns the exit status.
+fn run() -> Result<i32> {
+    let cli = config::parse_cli()?;
+    if cli.merge_tables && cli.format != OutputFormat::Table {
+        anyhow::bail!("--merge-tables needs --format table");
+    }
+    let started = Instant::now();
     let pattern = cli.build_regex()?;
+    let column_pattern = cli.build_column_regex()?;
+    let column_matches = cli.build_column_matches()?;
+    let column_sets = cli.build_column_sets()?;
+    let exclude_pattern = cli.build_exclude_regex()?;
+    let file_globs = cli.build_file_globs()?;
 
-    let path = Path::new(&cli.path);
+    let path = Path::new(cli.path());
 
-    if path.is_file() {
-        grep::search_file(path, &pattern, &cli)?;
+    let use_color = cli.use_color();
+    // `colored` checks for a terminal itself, which --color always overrides.
+    colored::control::set_override(use_color);
+    let mut printer = Printer::new(
+        use_color,
+        !cli.no_filename,
+        cli.format,
+        cli.json_array && !cli.quiet && !cli.writes_matches_to_stdout(),
+        cli.null,
+    );
+    printer.palette = cli.palette();
+    printer.column_colors = cli.color_by_column.clone().unwrap_or_default();
+    printer.max_matches_per_cell = cli.max_matches_per_cell.map(|n| n as usize);
+    printer.highlight_whole_row = cli.whole_row;
+    printer.pretty_json = cli.pretty_json;
+    printer.show_types = cli.show_types;
+    printer.begin();
+
+    // Nothing else may be printed in among the rows written to stdout.
+    if cli.writes_matches_to_stdout()
+        && (cli.also_print
+            || cli.count
+            || cli.aggregates()
+            || cli.describes_only()
+            || cli.lists_files())
+    {
+        anyhow::bail!(
+            "--output-file - writes the matching rows to stdout, so it can't be combined with options that print there"
+        );
+    }
+    // A dry run mustn't create (or empty) the output file.
+    let sink = cli
+        .output_file
+        .as_deref()
+        .filter(|_| !cli.dry_run)
+        .map(|path| OutputSink::create(path, cli.output_format, cli.value_format()))
+        .transpose()?;
+    let rewriter = Rewriter::new(&cli)?;
+    // Never read back the file we're writing matches into.
+    let output_path = cli
+        .output_file
+        .as_deref()
+        .and_then(|p| Path::new(p).canonicalize().ok());
+
+    let ctx = SearchContext {
+        column_pattern,
+        column_matches,
+        column_sets,
+        exclude_pattern,
+        sink: sink.map(RefCell::new),
+        merged: cli.merge_tables.then(RefCell::default),
+        ..SearchContext::new(&pattern, &cli, &printer)
+    };
+
+    let mut stats = SearchStats::default();
+    if cli.path() == "-" {
+        stats = grep::search_stdin(&ctx)?;
+    } else if http::is_url(cli.path()) {
+        stats = grep::search_url(cli.path(), &ctx)?;
+    } else if path.is_file() {
+        stats = match &rewriter {
+            Some(rewriter) => rewriter.rewrite_file(path, &ctx)?,
+            None => grep::search_file(path, &ctx)?,
+        };
     } else if path.is_dir() {
         let mut found_any = false;
-        for entry in WalkDir::new(path)
-            .follow_links(true)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-        {
+        let mut skipped_by_globs = false;
+        let progress = Progress::new(&cli);
+        let entries = sorted_files(path, &cli);
+        progress.set_total(
+            entries
+                .iter()
+                .filter(|e| is_supported(e.path()) && file_globs.allows(e.path()))
+                .count(),
+        );
+        for entry in entries {
             let file_path = entry.path();
             if is_supported(file_path) {
+                if !file_globs.allows(file_path) {
+                    skipped_by_globs = true;
+                    continue;
+                }
+                if output_path.is_some() && file_path.canonicalize().ok() == output_path {
+                    continue;
+                }
+                if rewriter.as_ref().is_some_and(|r| r.is_output(file_path)) {
+                    continue;
+                }
                 found_any = true;
-                grep::search_file(file_path, &pattern, &cli)?;
+                let name = file_path.display().to_string();
+                progress.start(&name);
+                let result = match &rewriter {
+                    Some(rewriter) => rewriter.rewrite_file(file_path, &ctx),
+                    None => grep::search_file(file_path, &ctx),
+                };
+                progress.done();
+                let file_stats = skip_failed(result, &name, &cli)?;
+                let budget_spent = ctx.spend_budget(file_stats.matches);
+                stats += file_stats;
+                if (cli.quiet && stats.matches > 0) || budget_spent {
+                    // The exit status, or --max-total, is settled; skip the
+                    // rest of the tree.
+                    break;
+                }
             }
         }
         if !found_any {
-            eprintln!(
-                "No supported table files (.csv, .parquet, .pq, .parq) found in '{}'",
-                cli.path
-            );
+            report_no_tables(cli.path(), skipped_by_globs);
         }
+    } else if store::is_store_url(cli.path()) {
+        stats = search_store(cli.path(), &ctx, &file_globs)?;
     } else {
-        anyhow::bail!("'{}' is not a valid file or directory", cli.path);
+        anyhow::bail!("'{}' is not a valid file or directory", cli.path());
+    }
+
+    ctx.print_merged();
+    if (cli.total || cli.count_only_total) && !cli.quiet {
+        if cli.count_distinct.is_some() {
+            printer.print_total(ctx.distinct_values.borrow().len());
+        } else {
+            printer.print_total(stats.matches);
+        }
+    }
+    printer.finish();
+    if let Some(sink) = ctx.sink {
+        sink.into_inner().close()?;
     }
 
-    Ok(())
+    if cli.stats {
+        print_stats(&stats, started.elapsed());
+    }
+
+    // Checking schemas succeeds if every file matched the first; describing
+    // files succeeds if there was anything to describe, and searching their
+    // headers if any matched.
+    let found = if cli.checks_schema_only() {
+        usize::from(stats.files_searched > 0 && ctx.schema_drifts.get() == 0)
+    } else if cli.describes_only() && !cli.search_headers {
+        stats.files_searched
+    } else {
+        stats.matches
+    };
+    Ok(match (found > 0, stats.files_failed > 0) {
+        (true, _) if cli.quiet => 0,
+        (_, true) => 2,
+        (true, false) => 0,
+        (false, false) => 1,
+    })
+}
+
+/// The result of searching one file of a directory or prefix. Unless
+/// --strict, an error is reported and the file counted as failed, so the
+/// rest can still be searched.
+fn skip_failed(result: Result<SearchStats>, name: &str, cli: &Cli) -> Result<SearchStats> {
+    match result {
+        Err(e) if !cli.strict => {
+            report!("Error: skipping '{}': {:#}", name, e);
+            Ok(SearchStats {
+                files_failed: 1,
+                ..SearchStats::default()
+            })
+        }
+        result => result,
+    }
+}
+
+/// --stats: summarize the run on stderr, keeping stdout clean for piping.
+fn print_stats(stats: &SearchStats, elapsed: Duration) {
+    let count =
+        |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    eprintln!();
+    eprintln!("{} searched", count(stats.files_searched, "file", "files"));
+    eprintln!("{} matched", count(stats.files_matched, "file", "files"));
+    eprintln!("{} scanned", count(stats.rows_scanned, "row", "rows"));
+    eprintln!("{}", count(stats.matches, "match", "matches"));
+    if stats.files_failed > 0 {
+        eprintln!("{} failed", count(stats.files_failed, "file", "files"));
+    }
+    eprintln!("{:.3}s elapsed", elapsed.as_secs_f64());
+}
+
+/// Walk the regular files under a directory.
+///
+/// Like ripgrep, this skips hidden files and anything .gitignore/.ignore
+/// exclude, unless asked not to.
+fn walk_files(path: &Path, cli: &Cli) -> impl Iterator<Item = DirEntry> {
+    WalkBuilder::new(path)
+        .standard_filters(!cli.no_ignore)
+        .hidden(!cli.hidden)
+        .max_depth(cli.walk_depth())
+        .follow_links(cli.follow_symlinks)
+        .build()
+        .filter_map(|e| match e {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                if let Some((ancestor, child)) = symlink_loop(&err) {
+                    report!(
+                        "Warning: skipping '{}': symlink loop back to '{}'",
+                        child.display(),
+                        ancestor.display()
+                    );
+                }
+                None
+            }
+        })
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+}
+
+/// Every regular file under a directory, in `--sort-files` order, so the
+/// same search always reports files in the same order. Ties, and files whose
+/// metadata can't be read, fall back to path order.
+fn sorted_files(path: &Path, cli: &Cli) -> Vec<DirEntry> {
+    let mut entries: Vec<DirEntry> = walk_files(path, cli).collect();
+    entries.sort_by(|a, b| a.path().cmp(b.path()));
+    match cli.sort_files {
+        FileOrder::Name => {}
+        FileOrder::Size => entries
+            .sort_by_cached_key(|e| std::cmp::Reverse(e.metadata().map(|m| m.len()).unwrap_or(0))),
+        FileOrder::Mtime => entries.sort_by_cached_key(|e| {
+            std::cmp::Reverse(e.metadata().ok().and_then(|m| m.modified().ok()))
+        }),
+    }
+    entries
+}
+
+/// The `(ancestor, child)` paths of a symlink loop the walk ran into, if that
+/// is what `err` reports.
+fn symlink_loop(err: &ignore::Error) -> Option<(&Path, &Path)> {
+    match err {
+        ignore::Error::Loop { ancestor, child } => Some((ancestor, child)),
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => symlink_loop(err),
+        _ => None,
+    }
+}
+
+/// Say why a directory or prefix search found nothing to search.
+fn report_no_tables(path: &str, skipped_by_globs: bool) {
+    if skipped_by_globs {
+        eprintln!(
+            "No supported table files matching --include/--exclude found in '{}'",
+            path
+        );
+    } else {
+        eprintln!(
+            "No supported table files ({}) found in '{}'",
+            InputFormat::supported_extensions(),
+            path
+        );
+    }
+}
+
+/// Search an object store URL: one object, or every table under a prefix,
+/// filtered like a directory walk.
+#[cfg(feature = "object-store")]
+fn search_store(url: &str, ctx: &SearchContext, file_globs: &FileGlobs) -> Result<SearchStats> {
+    let (objects, single) = store::resolve(url)?;
+    if single {
+        return objects
+            .into_iter()
+            .try_fold(SearchStats::default(), |mut stats, object| {
+                stats += grep::search_object(object, ctx)?;
+                Ok(stats)
+            });
+    }
+
+    let mut stats = SearchStats::default();
+    let mut found_any = false;
+    let mut skipped_by_globs = false;
+    let progress = Progress::new(ctx.cli);
+    progress.set_total(
+        objects
+            .iter()
+            .filter(|o| is_supported(o.key()) && file_globs.allows(o.key()))
+            .count(),
+    );
+    for object in objects {
+        if !is_supported(object.key()) {
+            continue;
+        }
+        if !file_globs.allows(object.key()) {
+            skipped_by_globs = true;
+            continue;
+        }
+        found_any = true;
+        let url = object.url().to_string();
+        progress.start(&url);
+        let result = grep::search_object(object, ctx);
+        progress.done();
+        let object_stats = skip_failed(result, &url, ctx.cli)?;
+        let budget_spent = ctx.spend_budget(object_stats.matches);
+        stats += object_stats;
+        if (ctx.cli.quiet && stats.matches > 0) || budget_spent {
+            break;
+        }
+    }
+    if !found_any {
+        report_no_tables(url, skipped_by_globs);
+    }
+    Ok(stats)
+}
+
+#[cfg(not(feature = "object-store"))]
+fn search_store(url: &str, _ctx: &SearchContext, _file_globs: &FileGlobs) -> Result<SearchStats> {
+    anyhow::bail!(
+        "Can't read '{}': table-grep was built without object store support (rebuild with --features object-store)",
+        url
+    )
 }
 
 /// Check if a file path is a supported file type, based on its extension.
 fn is_supported(path: &Path) -> bool {
     // TODO: Could detect the file header, especially for parquet files.
 
-    matches!(
-        path.extension().and_then(|e| e.to_str()),
-        Some("csv") | Some("parquet") | Some("pq") | Some("parq")
-    )
+    InputFormat::from_path(path).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_skip_failed_files() {
+        let failed = || Err(anyhow::anyhow!("corrupt footer"));
+
+        let cli = Cli::parse_from(["table-grep", "x", "dir"]);
+        let stats = skip_failed(failed(), "bad.parquet", &cli).unwrap();
+        assert_eq!(stats.files_failed, 1);
+        assert_eq!(stats.files_searched, 0);
+
+        let cli = Cli::parse_from(["table-grep", "--strict", "x", "dir"]);
+        assert!(skip_failed(failed(), "bad.parquet", &cli).is_err());
+    }
+
+    /// Names of the table files a walk of `test_data/nested` finds with `args`.
+    fn walked(args: &[&str]) -> Vec<String> {
+        let cli = Cli::parse_from(
+            ["table-grep"]
+                .iter()
+                .chain(args)
+                .chain(&["x", "test_data/nested"]),
+        );
+        let mut names: Vec<String> = walk_files(Path::new(cli.path()), &cli)
+            .filter(|e| is_supported(e.path()))
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_walk_depth() {
+        assert_eq!(walked(&[]), ["deep.csv", "mid.csv", "top.csv"]);
+        assert_eq!(walked(&["--max-depth", "2"]), ["mid.csv", "top.csv"]);
+        assert_eq!(walked(&["--max-depth", "1"]), ["top.csv"]);
+        assert_eq!(walked(&["--no-recursive"]), ["top.csv"]);
+        assert_eq!(walked(&["-d", "nonrecursive"]), ["top.csv"]);
+        assert_eq!(walked(&["--no-recursive", "--max-depth", "3"]), ["top.csv"]);
+        assert!(walked(&["--max-depth", "0"]).is_empty());
+    }
+
+    #[test]
+    fn test_walk_skips_ignored_and_hidden() {
+        let dir =
+            std::env::temp_dir().join(format!("table-grep-walk-ignore-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(".cache")).unwrap();
+        for name in ["a.csv", "skipped.csv", ".hidden.csv", ".cache/b.csv"] {
+            std::fs::write(dir.join(name), "x\n1\n").unwrap();
+        }
+        std::fs::write(dir.join(".ignore"), "skipped.csv\n").unwrap();
+
+        let found = |args: &[&str]| {
+            let path = dir.to_string_lossy();
+            let cli = Cli::parse_from(
+                ["table-grep"]
+                    .iter()
+                    .chain(args)
+                    .chain(&["x", path.as_ref()]),
+            );
+            let mut names: Vec<String> = walk_files(&dir, &cli)
+                .filter(|e| is_supported(e.path()))
+                .map(|e| e.path().strip_prefix(&dir).unwrap().display().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(found(&[]), ["a.csv"]);
+        assert_eq!(found(&["--no-ignore"]), ["a.csv", "skipped.csv"]);
+        assert_eq!(
+            found(&["--hidden"]),
+            [".cache/b.csv", ".hidden.csv", "a.csv"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sorted_files() {
+        let order = |args: &[&str], dir: &Path| {
+            let path = dir.to_string_lossy();
+            let cli = Cli::parse_from(
+                ["table-grep"]
+                    .iter()
+                    .chain(args)
+                    .chain(&["x", path.as_ref()]),
+            );
+            sorted_files(dir, &cli)
+                .iter()
+                .map(|e| e.path().strip_prefix(dir).unwrap().display().to_string())
+                .collect::<Vec<_>>()
+        };
+        let nested = Path::new("test_data/nested");
+        let by_path = ["2024/mid.csv", "2024/q1/deep.csv", "top.csv"];
+        assert_eq!(order(&[], nested), by_path);
+        assert_eq!(order(&["--sort-files", "name"], nested), by_path);
+
+        let dir = std::env::temp_dir().join(format!("table-grep-order-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.csv"), "x\n1\n").unwrap();
+        std::fs::write(dir.join("a.csv"), "x\n1\n2\n3\n").unwrap();
+        std::fs::write(dir.join("c.csv"), "x\n1\n2\n").unwrap();
+        assert_eq!(order(&[], &dir), ["a.csv", "b.csv", "c.csv"]);
+        assert_eq!(
+            order(&["--sort-files", "size"], &dir),
+            ["a.csv", "c.csv", "b.csv"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_terminates_on_symlink_loop() {
+        let dir = std::env::temp_dir().join(format!("table-grep-loop-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/a.csv"), "x\n1\n").unwrap();
+        // sub/back -> .. makes the tree infinitely deep when followed
+        let _ = std::fs::remove_file(dir.join("sub/back"));
+        std::os::unix::fs::symlink("..", dir.join("sub/back")).unwrap();
+
+        let files = |args: &[&str]| {
+            let path = dir.to_string_lossy();
+            let cli = Cli::parse_from(
+                ["table-grep"]
+                    .iter()
+                    .chain(args)
+                    .chain(&["x", path.as_ref()]),
+            );
+            walk_files(&dir, &cli).count()
+        };
+        assert_eq!(files(&[]), 1);
+        assert_eq!(files(&["--follow-symlinks"]), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
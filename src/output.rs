@@ -1,36 +1,417 @@
 use crate::cli::OutputFormat;
+use arrow::datatypes::DataType;
 use colored::Colorize;
 use comfy_table::{Attribute, Cell, CellAlignment, Color, ContentArrangement, Table, presets};
 use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::io::Write;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A color and style for one kind of highlighted output, parsed from a
+/// comma-separated list such as `blue,underline` (or `none` for plain text).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorSpec {
+    color: Option<colored::Color>,
+    bold: bool,
+    underline: bool,
+    italic: bool,
+}
+
+impl ColorSpec {
+    const fn new(color: Option<colored::Color>, bold: bool) -> Self {
+        Self {
+            color,
+            bold,
+            underline: false,
+            italic: false,
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        use colored::Color::*;
+
+        let mut spec = Self::new(None, false);
+        for word in s.split(',').map(|w| w.trim().to_ascii_lowercase()) {
+            let color = match word.as_str() {
+                "none" => continue,
+                "bold" => {
+                    spec.bold = true;
+                    continue;
+                }
+                "underline" => {
+                    spec.underline = true;
+                    continue;
+                }
+                "italic" => {
+                    spec.italic = true;
+                    continue;
+                }
+                "black" => Black,
+                "red" => Red,
+                "green" => Green,
+                "yellow" => Yellow,
+                "blue" => Blue,
+                "magenta" | "purple" => Magenta,
+                "cyan" => Cyan,
+                "white" => White,
+                "bright-black" | "gray" | "grey" => BrightBlack,
+                "bright-red" => BrightRed,
+                "bright-green" => BrightGreen,
+                "bright-yellow" => BrightYellow,
+                "bright-blue" => BrightBlue,
+                "bright-magenta" => BrightMagenta,
+                "bright-cyan" => BrightCyan,
+                "bright-white" => BrightWhite,
+                _ => {
+                    return Err(format!(
+                        "unknown color or style '{}' (expected a color such as red, blue or \
+                         bright-green, bold, underline, italic, or none)",
+                        word
+                    ));
+                }
+            };
+            spec.color = Some(color);
+        }
+        Ok(spec)
+    }
+
+    fn paint(&self, text: &str) -> colored::ColoredString {
+        let mut painted = text.normal();
+        if let Some(color) = self.color {
+            painted = painted.color(color);
+        }
+        if self.bold {
+            painted = painted.bold();
+        }
+        if self.underline {
+            painted = painted.underline();
+        }
+        if self.italic {
+            painted = painted.italic();
+        }
+        painted
+    }
+
+    /// The nearest comfy-table color, for table cells.
+    fn table_color(&self) -> Color {
+        use colored::Color as C;
+
+        match self.color {
+            Some(C::Black | C::BrightBlack) => Color::Black,
+            Some(C::Red | C::BrightRed) => Color::Red,
+            Some(C::Green | C::BrightGreen) => Color::Green,
+            Some(C::Yellow | C::BrightYellow) => Color::Yellow,
+            Some(C::Blue | C::BrightBlue) => Color::Blue,
+            Some(C::Magenta | C::BrightMagenta) => Color::Magenta,
+            Some(C::Cyan | C::BrightCyan) => Color::Cyan,
+            Some(C::White | C::BrightWhite) => Color::White,
+            _ => Color::Reset,
+        }
+    }
+}
+
+/// The colors of matches, file names and row numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub highlight: ColorSpec,
+    pub filename: ColorSpec,
+    pub row_number: ColorSpec,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            highlight: ColorSpec::new(Some(colored::Color::Red), true),
+            filename: ColorSpec::new(Some(colored::Color::Cyan), false),
+            row_number: ColorSpec::new(Some(colored::Color::Yellow), false),
+        }
+    }
+}
+
+/// Highlight styles for matches in particular columns, parsed from
+/// `COLUMN=STYLE,...` such as `error=red,warning=yellow,underline`: style
+/// words without an `=` add to the column before them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnColors(Vec<(String, ColorSpec)>);
+
+impl ColumnColors {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut columns: Vec<(String, Vec<&str>)> = Vec::new();
+        for word in s.split(',') {
+            match (word.split_once('='), columns.last_mut()) {
+                (Some((column, style)), _) => {
+                    if column.trim().is_empty() {
+                        return Err(format!("missing column name in '{}'", word));
+                    }
+                    columns.push((column.trim().to_string(), vec![style]));
+                }
+                (None, Some((_, styles))) => styles.push(word),
+                (None, None) => {
+                    return Err(format!(
+                        "expected COLUMN=STYLE, e.g. error=red,warning=yellow; got '{}'",
+                        word
+                    ));
+                }
+            }
+        }
+        columns
+            .into_iter()
+            .map(|(column, styles)| Ok((column, ColorSpec::parse(&styles.join(","))?)))
+            .collect::<Result<_, String>>()
+            .map(Self)
+    }
+
+    /// The style for matches in the column named `column`, if it has one.
+    fn get(&self, column: &str) -> Option<ColorSpec> {
+        self.0
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|&(_, spec)| spec)
+    }
+}
+
+/// Whether a cell holds no value: empty, or a typed null rendered as
+/// `null_text`.
+pub fn is_null_cell(cell: &str, null_text: &str) -> bool {
+    cell.is_empty() || cell == null_text
+}
+
+/// Short name for an arrow type in table headers, e.g. `f64` or `timestamp[ms]`.
+fn type_label(data_type: &DataType) -> String {
+    use arrow::datatypes::TimeUnit;
+
+    let unit = |unit: &TimeUnit| match unit {
+        TimeUnit::Second => "s",
+        TimeUnit::Millisecond => "ms",
+        TimeUnit::Microsecond => "us",
+        TimeUnit::Nanosecond => "ns",
+    };
+    match data_type {
+        DataType::Boolean => "bool".to_string(),
+        DataType::Int8 => "i8".to_string(),
+        DataType::Int16 => "i16".to_string(),
+        DataType::Int32 => "i32".to_string(),
+        DataType::Int64 => "i64".to_string(),
+        DataType::UInt8 => "u8".to_string(),
+        DataType::UInt16 => "u16".to_string(),
+        DataType::UInt32 => "u32".to_string(),
+        DataType::UInt64 => "u64".to_string(),
+        DataType::Float16 => "f16".to_string(),
+        DataType::Float32 => "f32".to_string(),
+        DataType::Float64 => "f64".to_string(),
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => "str".to_string(),
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView => "bytes".to_string(),
+        DataType::FixedSizeBinary(n) => format!("bytes[{}]", n),
+        DataType::Date32 | DataType::Date64 => "date".to_string(),
+        DataType::Time32(u) | DataType::Time64(u) => format!("time[{}]", unit(u)),
+        DataType::Timestamp(u, _) => format!("timestamp[{}]", unit(u)),
+        DataType::Duration(u) => format!("duration[{}]", unit(u)),
+        DataType::Decimal128(p, s) | DataType::Decimal256(p, s) => {
+            format!("decimal({},{})", p, s)
+        }
+        DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(..) => {
+            "list".to_string()
+        }
+        DataType::Struct(_) => "struct".to_string(),
+        DataType::Map(..) => "map".to_string(),
+        DataType::Dictionary(_, value) => type_label(value),
+        other => other.to_string().to_lowercase(),
+    }
+}
+
+/// A row buffered for output: either a match or a context row around one.
+#[derive(Clone)]
+pub struct OutputRow {
+    pub row_num: usize,
+    pub cells: Vec<String>,
+    pub is_context: bool,
+    /// Which cells are null, for formats that know; `None` for delimited
+    /// text, where a null is just an empty cell.
+    pub nulls: Option<Vec<bool>>,
+}
+
+/// With --merge-tables, the matches of every file gathered into one table,
+/// with a leading `file` column. Columns are the union of the files' headers,
+/// in the order first seen; a file without one leaves its cells blank.
+#[derive(Default)]
+pub struct MergedTable {
+    pub headers: Vec<String>,
+    /// Each column's type, or `None` where no file has said; columns whose
+    /// type differs between files are treated as text.
+    types: Vec<Option<DataType>>,
+    pub rows: Vec<OutputRow>,
+}
+
+impl MergedTable {
+    /// Add one file's rows, as they'd have been printed on their own.
+    pub fn add(
+        &mut self,
+        filename: &str,
+        headers: &[String],
+        column_types: Option<&[DataType]>,
+        rows: &[OutputRow],
+    ) {
+        if self.headers.is_empty() {
+            self.headers.push("file".to_string());
+            self.types.push(Some(DataType::Utf8));
+        }
+        let positions: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .map(|(idx, header)| {
+                let column_type = column_types.and_then(|types| types.get(idx)).cloned();
+                // Never the leading file column, even if a file has its own.
+                match self.headers[1..].iter().position(|h| h == header) {
+                    Some(pos) => {
+                        let merged = &mut self.types[pos + 1];
+                        if *merged != column_type {
+                            *merged = Some(DataType::Utf8);
+                        }
+                        pos + 1
+                    }
+                    None => {
+                        self.headers.push(header.clone());
+                        self.types.push(column_type);
+                        self.headers.len() - 1
+                    }
+                }
+            })
+            .collect();
+
+        for row in rows {
+            let mut cells = vec![String::new(); self.headers.len()];
+            cells[0] = filename.to_string();
+            for (cell, &pos) in row.cells.iter().zip(&positions) {
+                cells[pos] = cell.clone();
+            }
+            self.rows.push(OutputRow {
+                cells,
+                nulls: None,
+                ..row.clone()
+            });
+        }
+    }
+
+    /// The column types to print with, if any file had them.
+    pub fn column_types(&self) -> Option<Vec<DataType>> {
+        self.types.iter().skip(1).any(Option::is_some).then(|| {
+            self.types
+                .iter()
+                .map(|t| t.clone().unwrap_or(DataType::Utf8))
+                .collect()
+        })
+    }
+
+    /// Fill out rows added before later files brought more columns.
+    pub fn pad_rows(&mut self) {
+        let width = self.headers.len();
+        for row in &mut self.rows {
+            row.cells.resize(width, String::new());
+        }
+    }
+}
 
 pub struct Printer {
     pub use_color: bool,
     pub show_filename: bool,
     pub format: OutputFormat,
+    /// JSON mode: wrap all rows in a single array instead of one object per line.
+    pub json_array: bool,
+    /// Number of JSON rows printed so far, across all files.
+    json_rows_printed: std::cell::Cell<usize>,
+    /// -Z: end file names with NUL instead of a newline or ':'.
+    pub null_after_filename: bool,
+    pub palette: Palette,
+    /// --color-by-column: highlight styles that replace the palette's in
+    /// the columns named.
+    pub column_colors: ColumnColors,
+    /// --max-matches-per-cell: how many matches to highlight in a cell;
+    /// `None` highlights them all.
+    pub max_matches_per_cell: Option<usize>,
+    /// --whole-row: in CSV output, highlight matches in the joined row, so
+    /// they may span cells.
+    pub highlight_whole_row: bool,
+    /// --pretty-json: print cells holding a JSON object or array indented
+    /// over several lines.
+    pub pretty_json: bool,
+    /// --show-types: in table output, add each column's type to its header.
+    pub show_types: bool,
 }
 
 impl Printer {
-    pub fn new(use_color: bool, show_filename: bool, format: OutputFormat) -> Self {
+    pub fn new(
+        use_color: bool,
+        show_filename: bool,
+        format: OutputFormat,
+        json_array: bool,
+        null_after_filename: bool,
+    ) -> Self {
         Self {
             use_color,
             show_filename,
             format,
+            json_array,
+            json_rows_printed: std::cell::Cell::new(0),
+            null_after_filename,
+            palette: Palette::default(),
+            column_colors: ColumnColors::default(),
+            max_matches_per_cell: None,
+            highlight_whole_row: false,
+            pretty_json: false,
+            show_types: false,
+        }
+    }
+
+    /// Print anything that must precede all per-file output.
+    pub fn begin(&self) {
+        if self.format == OutputFormat::Json && self.json_array {
+            println!("[");
+        }
+    }
+
+    /// Print anything that must follow all per-file output.
+    pub fn finish(&self) {
+        if self.format == OutputFormat::Json && self.json_array {
+            if self.json_rows_printed.get() > 0 {
+                println!();
+            }
+            println!("]");
         }
     }
 
     pub fn print_file_header(&self, filename: &str) {
         if self.show_filename {
+            let header = format!("==> {} <==", filename);
             if self.use_color {
-                println!("{}", format!("==> {} <==", filename).cyan().bold());
+                print!("{}", self.palette.filename.paint(&header).bold());
             } else {
-                println!("==> {} <==", filename);
+                print!("{}", header);
             }
+            print!("{}", self.filename_terminator('\n'));
+        }
+    }
+
+    /// --no-heading: start a CSV row with its file name, followed by
+    /// `separator` (':' for matches and headers, '-' for context, as in grep).
+    pub fn print_row_prefix(&self, filename: &str, separator: char) {
+        if self.show_filename {
+            let _ = self.write_filename(&mut std::io::stdout().lock(), filename, separator);
+        }
+    }
+
+    /// What follows a printed file name: `usual`, or NUL under -Z.
+    fn filename_terminator(&self, usual: char) -> char {
+        if self.null_after_filename {
+            '\0'
+        } else {
+            usual
         }
     }
 
     /// CSV mode: print a dimmed header row.
-    pub fn print_headers(&self, headers: &[String]) {
-        let line = headers.join(",");
+    pub fn print_headers(&self, headers: &[String], delimiter: char) {
+        let line = headers.join(&delimiter.to_string());
         if self.use_color {
             println!("{}", line.dimmed());
         } else {
@@ -39,36 +420,116 @@ impl Printer {
     }
 
     /// CSV mode: print a single matching row with the row number prefix.
-    pub fn print_match(&self, row_num: usize, row: &[String], pattern: &Regex) {
-        let highlighted: Vec<String> = row
-            .iter()
-            .map(|cell| self.highlight_cell(cell, pattern))
-            .collect();
+    /// `headers` name its columns, for --color-by-column.
+    pub fn print_match(
+        &self,
+        row_num: usize,
+        row: &[String],
+        headers: &[String],
+        pattern: &Regex,
+        delimiter: char,
+    ) {
+        let line = if self.highlight_whole_row {
+            self.highlight_cell(&row.join(&delimiter.to_string()), pattern, None)
+        } else {
+            row.iter()
+                .enumerate()
+                .map(|(idx, cell)| {
+                    self.highlight_cell(&self.display_cell(cell), pattern, headers.get(idx))
+                })
+                .collect::<Vec<_>>()
+                .join(&delimiter.to_string())
+        };
 
         if self.use_color {
-            print!("{} ", format!("{}:", row_num).yellow());
+            let row_num = format!("{}:", row_num);
+            print!("{} ", self.palette.row_number.paint(&row_num));
         } else {
             print!("{}: ", row_num);
         }
-        println!("{}", highlighted.join(","));
+        println!("{}", line);
+    }
+
+    /// CSV mode: print a context row (no highlighting, grep-style `-` marker).
+    pub fn print_context(&self, row_num: usize, row: &[String], delimiter: char) {
+        let cells: Vec<Cow<str>> = row.iter().map(|cell| self.display_cell(cell)).collect();
+        let line = cells.join(&delimiter.to_string());
+        if self.use_color {
+            let row_num = format!("{}-", row_num);
+            print!("{} ", self.palette.row_number.paint(&row_num));
+            println!("{}", line.dimmed());
+        } else {
+            println!("{}- {}", row_num, line);
+        }
+    }
+
+    /// CSV mode: separate disjoint groups of context rows, like grep's `--`.
+    pub fn print_group_separator(&self) {
+        if self.use_color {
+            println!("{}", "--".cyan());
+        } else {
+            println!("--");
+        }
+    }
+
+    /// An empty bordered table, styled whenever color is on (comfy-table
+    /// would otherwise leave styling out when stdout isn't a terminal).
+    fn new_table(&self) -> Table {
+        let mut table = Table::new();
+        table
+            .load_preset(presets::UTF8_FULL)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+        if self.use_color {
+            table.enforce_styling();
+        }
+        table
     }
 
     /// Table mode: render all buffered rows (+ optional headers) as a pretty table.
+    /// Columns with a numeric or temporal type in `column_types` are
+    /// right-aligned, and --show-types adds each type to its header. The
+    /// first `plain_columns` columns, added rather than searched, are never
+    /// highlighted.
     pub fn print_table(
         &self,
         headers: &[String],
-        rows: &[(usize, Vec<String>)], // (row_num, fields)
+        column_types: Option<&[DataType]>,
+        rows: &[OutputRow],
         pattern: &Regex,
         with_headers: bool,
+        plain_columns: usize,
     ) {
         if rows.is_empty() {
             return;
         }
+        let table = self.table(
+            headers,
+            column_types,
+            rows,
+            pattern,
+            with_headers,
+            plain_columns,
+        );
+        println!("{table}");
+    }
 
-        let mut table = Table::new();
-        table
-            .load_preset(presets::UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic);
+    /// The table `print_table` prints.
+    fn table(
+        &self,
+        headers: &[String],
+        column_types: Option<&[DataType]>,
+        rows: &[OutputRow],
+        pattern: &Regex,
+        with_headers: bool,
+        plain_columns: usize,
+    ) -> Table {
+        let column_type = |idx: usize| column_types.and_then(|types| types.get(idx));
+        let alignment = |idx: usize| match column_type(idx) {
+            Some(dt) if dt.is_numeric() || dt.is_temporal() => CellAlignment::Right,
+            _ => CellAlignment::Left,
+        };
+
+        let mut table = self.new_table();
 
         // Header row
         if with_headers {
@@ -83,60 +544,408 @@ impl Printer {
                         Color::Reset
                     }),
             ];
-            for h in headers {
-                header_cells.push(Cell::new(h).add_attribute(Attribute::Bold).fg(
-                    if self.use_color {
-                        Color::DarkCyan
-                    } else {
-                        Color::Reset
-                    },
-                ));
+            for (idx, h) in headers.iter().enumerate() {
+                let label = match column_type(idx) {
+                    Some(dt) if self.show_types => format!("{} ({})", h, type_label(dt)),
+                    _ => h.clone(),
+                };
+                header_cells.push(
+                    Cell::new(label)
+                        .add_attribute(Attribute::Bold)
+                        .set_alignment(alignment(idx))
+                        .fg(if self.use_color {
+                            Color::DarkCyan
+                        } else {
+                            Color::Reset
+                        }),
+                );
             }
             table.set_header(header_cells);
         }
 
-        for (row_num, row) in rows {
+        for row in rows {
             let mut cells: Vec<Cell> = vec![
-                Cell::new(row_num.to_string())
+                Cell::new(row.row_num.to_string())
                     .set_alignment(CellAlignment::Right)
                     .fg(if self.use_color {
-                        Color::Yellow
+                        self.palette.row_number.table_color()
                     } else {
                         Color::Reset
                     }),
             ];
-            for cell_str in row {
-                let cell = if self.use_color && pattern.is_match(cell_str) {
-                    // Mark matching cells in the table
+            for (idx, cell_str) in row.cells.iter().enumerate() {
+                // Color just the matched text, as in CSV mode. comfy-table's
+                // custom_styling feature keeps the ANSI codes out of its
+                // width calculations.
+                let cell_str = self.display_cell(cell_str);
+                let cell = if row.is_context || idx < plain_columns {
                     Cell::new(cell_str)
-                        .add_attribute(Attribute::Bold)
-                        .fg(Color::Red)
                 } else {
-                    Cell::new(cell_str)
+                    Cell::new(self.highlight_cell(&cell_str, pattern, headers.get(idx)))
                 };
-                cells.push(cell);
+                cells.push(cell.set_alignment(alignment(idx)));
             }
             table.add_row(cells);
         }
+        table
+    }
 
-        println!("{table}");
+    /// Vertical mode: print each row as a block of `column: value` lines
+    /// under a `-[ row N ]-` header, with column names right-aligned. Values
+    /// are never wrapped.
+    pub fn print_vertical(&self, headers: &[String], rows: &[OutputRow], pattern: &Regex) {
+        println!("{}", self.vertical(headers, rows, pattern));
+    }
+
+    fn vertical(&self, headers: &[String], rows: &[OutputRow], pattern: &Regex) -> String {
+        let name_width = headers.iter().map(|h| h.chars().count()).max().unwrap_or(0);
+        let mut out = String::new();
+
+        for row in rows {
+            let label = if row.is_context {
+                format!("-[ row {} (context) ]-", row.row_num)
+            } else {
+                format!("-[ row {} ]-", row.row_num)
+            };
+            if self.use_color {
+                out.push_str(&format!("{}\n", self.palette.row_number.paint(&label)));
+            } else {
+                out.push_str(&format!("{}\n", label));
+            }
+
+            for (idx, cell) in row.cells.iter().enumerate() {
+                let name = headers.get(idx).map(String::as_str).unwrap_or("?");
+                let name = format!("{:>width$}", name, width = name_width);
+                // Line up the rest of a pretty-printed value under its start.
+                let cell = self
+                    .display_cell(cell)
+                    .replace('\n', &format!("\n{:width$}", "", width = name_width + 2));
+                let cell = &cell;
+                let value = if row.is_context {
+                    if self.use_color {
+                        cell.dimmed().to_string()
+                    } else {
+                        cell.clone()
+                    }
+                } else {
+                    self.highlight_cell(cell, pattern, headers.get(idx))
+                };
+                if self.use_color {
+                    out.push_str(&format!("{}: {}\n", name.cyan(), value));
+                } else {
+                    out.push_str(&format!("{}: {}\n", name, value));
+                }
+            }
+        }
+        out
+    }
+
+    /// Markdown mode: render rows as a GitHub-flavored markdown table. Cells
+    /// that matched are wrapped in `**bold**` so highlighting survives pasting.
+    pub fn print_markdown(
+        &self,
+        headers: &[String],
+        rows: &[OutputRow],
+        pattern: &Regex,
+        with_headers: bool,
+    ) {
+        if with_headers {
+            let header_cells: Vec<String> = headers.iter().map(|h| markdown_escape(h)).collect();
+            println!("| # | {} |", header_cells.join(" | "));
+            println!("|--:|{}", "---|".repeat(headers.len()));
+        }
+
+        for row in rows {
+            let cells: Vec<String> = row
+                .cells
+                .iter()
+                .map(|cell| {
+                    let escaped = markdown_escape(cell);
+                    if !row.is_context && !cell.is_empty() && pattern.is_match(cell) {
+                        format!("**{}**", escaped)
+                    } else {
+                        escaped
+                    }
+                })
+                .collect();
+            println!("| {} | {} |", row.row_num, cells.join(" | "));
+        }
+        println!();
+    }
+
+    /// JSON mode: print each row as an object keyed by header name, plus
+    /// `_file` and `_row`. Typed numeric/boolean columns become JSON numbers
+    /// and bools, and null cells JSON null; everything else stays a string.
+    pub fn print_json_rows(
+        &self,
+        filename: &str,
+        headers: &[String],
+        column_types: Option<&[DataType]>,
+        rows: &[OutputRow],
+    ) {
+        for row in rows {
+            let mut obj = serde_json::Map::new();
+            obj.insert("_file".to_string(), Value::from(filename));
+            obj.insert("_row".to_string(), Value::from(row.row_num));
+            if row.is_context {
+                obj.insert("_context".to_string(), Value::Bool(true));
+            }
+            for (idx, (header, cell)) in headers.iter().zip(&row.cells).enumerate() {
+                let data_type = column_types.and_then(|types| types.get(idx));
+                let is_null = row
+                    .nulls
+                    .as_ref()
+                    .is_some_and(|nulls| nulls.get(idx) == Some(&true));
+                obj.insert(header.clone(), json_value(cell, data_type, is_null));
+            }
+            self.print_json_object(obj);
+        }
+    }
+
+    /// JSON mode: print one object, as a line or an element of the array.
+    fn print_json_object(&self, obj: serde_json::Map<String, Value>) {
+        let line = Value::Object(obj).to_string();
+        if self.json_array {
+            if self.json_rows_printed.get() > 0 {
+                println!(",");
+            }
+            print!("  {}", line);
+        } else {
+            println!("{}", line);
+        }
+        self.json_rows_printed.set(self.json_rows_printed.get() + 1);
     }
 
     pub fn print_count(&self, filename: &str, count: usize) {
+        // With -Z the NUL replaces the ':', as in grep
+        let (sep, space) = match self.filename_terminator(':') {
+            ':' => (":", " "),
+            _ => ("\0", ""),
+        };
+        if self.use_color {
+            println!(
+                "{}{}{}{}",
+                self.palette.filename.paint(filename),
+                sep,
+                space,
+                count.to_string().green().bold()
+            );
+        } else {
+            println!("{}{}{}{}", filename, sep, space, count);
+        }
+    }
+
+    /// --list-distinct: print a file's distinct values, one per line.
+    pub fn print_values<'v>(&self, filename: &str, values: impl Iterator<Item = &'v String>) {
+        self.print_file_header(filename);
+        for value in values {
+            println!("{}", value);
+        }
+        self.print_separator();
+    }
+
+    /// --search-headers: print a file's matching column names, one per line.
+    pub fn print_matching_headers(&self, filename: &str, names: &[&String], pattern: &Regex) {
+        self.print_file_header(filename);
+        for name in names {
+            println!("{}", self.highlight_cell(name, pattern, None));
+        }
+        self.print_separator();
+    }
+
+    /// --group-by: print `value: count` lines for a file.
+    pub fn print_histogram(&self, filename: &str, counts: &[(&String, usize)]) {
+        self.print_file_header(filename);
+        for (value, count) in counts {
+            if self.use_color {
+                println!("{}: {}", value, count.to_string().green().bold());
+            } else {
+                println!("{}: {}", value, count);
+            }
+        }
+        self.print_separator();
+    }
+
+    /// --list-columns: print a file's columns with their zero-based indices
+    /// (as --columns takes them) and, for typed formats, their Arrow types.
+    pub fn print_columns(
+        &self,
+        filename: &str,
+        headers: &[String],
+        column_types: Option<&[DataType]>,
+    ) {
+        self.print_file_header(filename);
+        let index_width = headers.len().saturating_sub(1).to_string().len();
+        let width = |name: &String| name.graphemes(true).count();
+        let name_width = headers.iter().map(width).max().unwrap_or(0);
+        for (i, name) in headers.iter().enumerate() {
+            let index = format!("{:>width$}", i, width = index_width);
+            let index = if self.use_color {
+                index.green().to_string()
+            } else {
+                index
+            };
+            match column_types.and_then(|types| types.get(i)) {
+                Some(data_type) => {
+                    let padding = " ".repeat(name_width - width(name));
+                    let data_type = if self.use_color {
+                        data_type.to_string().dimmed().to_string()
+                    } else {
+                        data_type.to_string()
+                    };
+                    println!("{}  {}{}  {}", index, name, padding, data_type);
+                }
+                None => println!("{}  {}", index, name),
+            }
+        }
+        self.print_separator();
+    }
+
+    /// --schema: print a file's row count, size and columns, as a JSON
+    /// object with --format json and a small table otherwise.
+    pub fn print_schema(
+        &self,
+        filename: &str,
+        headers: &[String],
+        column_types: Option<&[DataType]>,
+        rows: u64,
+        size: Option<u64>,
+    ) {
+        let column_type = |idx: usize| column_types.and_then(|types| types.get(idx));
+
+        if self.format == OutputFormat::Json {
+            let columns = headers
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| {
+                    let data_type = column_type(idx).map(|dt| dt.to_string());
+                    serde_json::json!({ "name": name, "type": data_type })
+                })
+                .collect();
+            let mut obj = serde_json::Map::new();
+            obj.insert("file".to_string(), Value::from(filename));
+            obj.insert("size_bytes".to_string(), Value::from(size));
+            obj.insert("num_rows".to_string(), Value::from(rows));
+            obj.insert("num_columns".to_string(), Value::from(headers.len()));
+            obj.insert("columns".to_string(), Value::Array(columns));
+            self.print_json_object(obj);
+            return;
+        }
+
+        self.print_file_header(filename);
+        let size = size.map_or_else(|| "size unknown".to_string(), format_size);
+        println!("{} rows, {} columns, {}", rows, headers.len(), size);
+
+        let mut table = self.new_table();
+        let header_color = if self.use_color {
+            Color::DarkCyan
+        } else {
+            Color::Reset
+        };
+        let mut header_cells = vec![
+            Cell::new("#").set_alignment(CellAlignment::Right),
+            Cell::new("column"),
+        ];
+        if column_types.is_some() {
+            header_cells.push(Cell::new("type"));
+        }
+        table.set_header(
+            header_cells
+                .into_iter()
+                .map(|cell| cell.add_attribute(Attribute::Bold).fg(header_color)),
+        );
+        for (idx, name) in headers.iter().enumerate() {
+            let mut cells = vec![
+                Cell::new(idx).set_alignment(CellAlignment::Right),
+                Cell::new(name),
+            ];
+            if let Some(data_type) = column_type(idx) {
+                cells.push(Cell::new(data_type));
+            }
+            table.add_row(cells);
+        }
+        println!("{table}");
+    }
+
+    /// --dry-run: print a file that would be searched and the columns that
+    /// would be (`None` for all of them), tab-separated, or as a JSON object
+    /// with --format json.
+    pub fn print_plan(&self, filename: &str, columns: Option<Vec<&str>>) {
+        if self.format == OutputFormat::Json {
+            let mut obj = serde_json::Map::new();
+            obj.insert("file".to_string(), Value::from(filename));
+            obj.insert("columns".to_string(), Value::from(columns));
+            self.print_json_object(obj);
+            return;
+        }
+        let columns = columns.map_or_else(|| "*".to_string(), |names| names.join(","));
+        println!("{}\t{}", filename, columns);
+    }
+
+    /// --total: print the count summed over every file.
+    pub fn print_total(&self, count: usize) {
         if self.use_color {
-            println!("{}: {}", filename.cyan(), count.to_string().green().bold());
+            println!("{}: {}", "total".cyan(), count.to_string().green().bold());
         } else {
-            println!("{}: {}", filename, count);
+            println!("total: {}", count);
         }
     }
 
-    fn highlight_cell(&self, cell: &str, pattern: &Regex) -> String {
+    /// -l/-L: print just the file's name.
+    pub fn print_filename(&self, filename: &str) {
+        // A closed stdout (e.g. `| head`) isn't worth a panic here
+        let _ = self.write_filename(&mut std::io::stdout().lock(), filename, '\n');
+    }
+
+    /// Write a file name followed by `usual` (or NUL under -Z).
+    fn write_filename(
+        &self,
+        out: &mut impl Write,
+        filename: &str,
+        usual: char,
+    ) -> std::io::Result<()> {
+        if self.use_color {
+            write!(out, "{}", self.palette.filename.paint(filename))?;
+        } else {
+            write!(out, "{}", filename)?;
+        }
+        write!(out, "{}", self.filename_terminator(usual))
+    }
+
+    /// A cell as printed: with --pretty-json, reformatted if it holds a JSON
+    /// object or array.
+    fn display_cell<'c>(&self, cell: &'c str) -> Cow<'c, str> {
+        match self.pretty_json.then(|| pretty_json(cell)).flatten() {
+            Some(pretty) => Cow::Owned(pretty),
+            None => Cow::Borrowed(cell),
+        }
+    }
+
+    /// Color what `pattern` matches in a cell of the column named `column`
+    /// (just the first --max-matches-per-cell matches, if set), in its
+    /// `highlight_style`.
+    fn highlight_cell(&self, cell: &str, pattern: &Regex, column: Option<&String>) -> String {
         if !self.use_color {
             return cell.to_string();
         }
-        let result = pattern.replace_all(cell, |caps: &regex::Captures| {
-            caps[0].red().bold().to_string()
-        });
+        let style = self.highlight_style(column);
+        self.mark_matches(cell, pattern, |text| style.paint(text).to_string())
+    }
+
+    /// How matches in the column named `column` are highlighted: in its
+    /// --color-by-column style if it has one, else the usual one.
+    fn highlight_style(&self, column: Option<&String>) -> ColorSpec {
+        column
+            .and_then(|column| self.column_colors.get(column))
+            .unwrap_or(self.palette.highlight)
+    }
+
+    /// `cell` with what `pattern` matches passed through `mark`: every
+    /// match, or just the first --max-matches-per-cell.
+    fn mark_matches(&self, cell: &str, pattern: &Regex, mark: impl Fn(&str) -> String) -> String {
+        // replacen with 0 replaces every match
+        let limit = self.max_matches_per_cell.unwrap_or(0);
+        let result = pattern.replacen(cell, limit, |caps: &regex::Captures| mark(&caps[0]));
         result.into_owned()
     }
 
@@ -148,3 +957,459 @@ impl Printer {
         }
     }
 }
+
+/// Escape a cell for use inside a markdown table row.
+fn markdown_escape(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// A byte count in binary units, e.g. `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// A plain number with its integer part grouped in thousands, e.g.
+/// `-1234567.5` as `-1,234,567.5` with `sep` ','. `None` for anything else,
+/// including numbers too short to group and ones with leading zeros (more
+/// likely codes than amounts).
+pub fn group_thousands(cell: &str, sep: &str) -> Option<String> {
+    let (sign, number) = match cell.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", cell),
+    };
+    let (int, frac) = match number.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (number, None),
+    };
+    if int.len() < 4
+        || int.starts_with('0')
+        || !int.bytes().all(|b| b.is_ascii_digit())
+        || frac.is_some_and(|f| f.is_empty() || !f.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+
+    let mut grouped = String::from(sign);
+    for (i, digit) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push_str(sep);
+        }
+        grouped.push(digit);
+    }
+    if let Some(frac) = frac {
+        grouped.push('.');
+        grouped.push_str(frac);
+    }
+    Some(grouped)
+}
+
+/// The plain form of a number grouped in thousands with `sep`, e.g.
+/// `1,234,567.5` as `1234567.5`; `None` unless `cell` is one.
+pub fn ungroup_thousands(cell: &str, sep: &str) -> Option<String> {
+    if sep.is_empty() || !cell.contains(sep) {
+        return None;
+    }
+    let plain = cell.replace(sep, "");
+    (group_thousands(&plain, sep).as_deref() == Some(cell)).then_some(plain)
+}
+
+/// `cell` indented over several lines if it's a JSON object or array, else
+/// `None`. Only cells starting with `{` or `[` are parsed at all.
+fn pretty_json(cell: &str) -> Option<String> {
+    let trimmed = cell.trim_start();
+    if !trimmed.starts_with(['{', '[']) {
+        return None;
+    }
+    let value: Value = serde_json::from_str(trimmed).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+/// Shorten `cell` to at most `max` user-perceived characters (grapheme
+/// clusters), ending in `…` when anything was cut.
+pub fn truncate_display(cell: &str, max: usize) -> Cow<'_, str> {
+    match cell.grapheme_indices(true).nth(max) {
+        None => Cow::Borrowed(cell),
+        Some(_) => {
+            let keep = max.saturating_sub(1);
+            let end = cell
+                .grapheme_indices(true)
+                .nth(keep)
+                .map_or(cell.len(), |(i, _)| i);
+            Cow::Owned(format!("{}…", &cell[..end]))
+        }
+    }
+}
+
+/// Convert a rendered cell to a JSON value using its column's arrow type.
+/// Null cells, and cells of numeric, boolean or nested columns that don't
+/// parse, become JSON null.
+pub fn json_value(cell: &str, data_type: Option<&DataType>, is_null: bool) -> Value {
+    match data_type {
+        _ if is_null => Value::Null,
+        // Nested cells are already rendered as JSON text
+        Some(dt) if dt.is_nested() => serde_json::from_str(cell).unwrap_or(Value::Null),
+        Some(dt) if dt.is_integer() => cell
+            .parse::<i64>()
+            .map(Value::from)
+            .or_else(|_| cell.parse::<u64>().map(Value::from))
+            .unwrap_or(Value::Null),
+        Some(dt) if dt.is_floating() => match cell.parse::<f64>() {
+            Ok(f) => serde_json::Number::from_f64(f)
+                .map(Value::Number)
+                // NaN and infinities have no JSON representation
+                .unwrap_or_else(|| Value::from(cell)),
+            Err(_) => Value::Null,
+        },
+        Some(DataType::Boolean) => cell.parse::<bool>().map(Value::Bool).unwrap_or(Value::Null),
+        _ => Value::from(cell),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Forces `colored` to paint while held, for one test at a time, and
+    /// leaves the override unset again when dropped.
+    struct ForcedColor {
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    fn force_color() -> ForcedColor {
+        static LOCK: Mutex<()> = Mutex::new(());
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        colored::control::set_override(true);
+        ForcedColor { _lock: guard }
+    }
+
+    impl Drop for ForcedColor {
+        fn drop(&mut self) {
+            colored::control::unset_override();
+        }
+    }
+
+    #[test]
+    fn test_color_spec_parse() {
+        let spec = ColorSpec::parse("Blue, underline").unwrap();
+        assert_eq!(spec.color, Some(colored::Color::Blue));
+        assert!(spec.underline && !spec.bold);
+        assert_eq!(
+            ColorSpec::parse("none").unwrap(),
+            ColorSpec::new(None, false)
+        );
+        assert_eq!(
+            ColorSpec::parse("bold,bright-red").unwrap(),
+            ColorSpec::new(Some(colored::Color::BrightRed), true)
+        );
+        assert!(ColorSpec::parse("pink").is_err());
+    }
+
+    #[test]
+    fn test_column_colors() {
+        let colors = ColumnColors::parse("error=red,warning=yellow,underline").unwrap();
+        assert_eq!(
+            colors.get("error"),
+            Some(ColorSpec::new(Some(colored::Color::Red), false))
+        );
+        let warning = colors.get("warning").unwrap();
+        assert_eq!(warning.color, Some(colored::Color::Yellow));
+        assert!(warning.underline);
+        assert_eq!(colors.get("level"), None);
+        assert!(ColumnColors::parse("red").is_err());
+        assert!(ColumnColors::parse("error=pink").is_err());
+
+        // Without color, cells are left alone whatever their column
+        let mut printer = Printer::new(false, false, OutputFormat::Csv, false, false);
+        printer.column_colors = colors;
+        let pattern = Regex::new("disk").unwrap();
+        let error = "error".to_string();
+        assert_eq!(
+            printer.highlight_cell("disk full", &pattern, Some(&error)),
+            "disk full"
+        );
+
+        // With color, a named column gets its own style and the rest the
+        // usual one
+        printer.use_color = true;
+        assert_eq!(
+            printer.highlight_style(Some(&error)),
+            ColorSpec::new(Some(colored::Color::Red), false)
+        );
+        let level = "level".to_string();
+        assert_eq!(
+            printer.highlight_style(Some(&level)),
+            printer.palette.highlight
+        );
+        assert_eq!(printer.highlight_style(None), printer.palette.highlight);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(
+            group_thousands("1000000", ",").as_deref(),
+            Some("1,000,000")
+        );
+        assert_eq!(
+            group_thousands("-12345.678", "_").as_deref(),
+            Some("-12_345.678")
+        );
+        assert_eq!(group_thousands("999", ","), None);
+        assert_eq!(group_thousands("01234", ","), None);
+        assert_eq!(group_thousands("1e20", ","), None);
+        assert_eq!(group_thousands("1234.", ","), None);
+
+        assert_eq!(
+            ungroup_thousands("1,000,000", ",").as_deref(),
+            Some("1000000")
+        );
+        assert_eq!(
+            ungroup_thousands("12 345.5", " ").as_deref(),
+            Some("12345.5")
+        );
+        assert_eq!(ungroup_thousands("1,00,000", ","), None);
+        assert_eq!(ungroup_thousands("a,b", ","), None);
+    }
+
+    #[test]
+    fn test_type_label() {
+        use arrow::datatypes::TimeUnit;
+
+        assert_eq!(type_label(&DataType::Float64), "f64");
+        assert_eq!(type_label(&DataType::Utf8), "str");
+        assert_eq!(
+            type_label(&DataType::Timestamp(TimeUnit::Millisecond, None)),
+            "timestamp[ms]"
+        );
+        assert_eq!(type_label(&DataType::Decimal128(10, 2)), "decimal(10,2)");
+        assert_eq!(
+            type_label(&DataType::Dictionary(
+                Box::new(DataType::Int32),
+                Box::new(DataType::Utf8)
+            )),
+            "str"
+        );
+    }
+
+    #[test]
+    fn test_write_filename_null_separated() {
+        let written = |null: bool| {
+            let printer = Printer::new(false, true, OutputFormat::Csv, false, null);
+            let mut out = Vec::new();
+            for name in ["a.csv", "dir/b c.parquet"] {
+                printer.write_filename(&mut out, name, '\n').unwrap();
+            }
+            out
+        };
+        assert_eq!(written(false), b"a.csv\ndir/b c.parquet\n");
+        assert_eq!(written(true), b"a.csv\0dir/b c.parquet\0");
+
+        // --no-heading row prefixes end in ':' or '-', or NUL under -Z
+        let printer = Printer::new(false, true, OutputFormat::Csv, false, false);
+        let mut out = Vec::new();
+        printer.write_filename(&mut out, "a.csv", ':').unwrap();
+        printer.write_filename(&mut out, "a.csv", '-').unwrap();
+        assert_eq!(out, b"a.csv:a.csv-");
+        let printer = Printer::new(false, true, OutputFormat::Csv, false, true);
+        let mut out = Vec::new();
+        printer.write_filename(&mut out, "a.csv", ':').unwrap();
+        assert_eq!(out, b"a.csv\0");
+    }
+
+    #[test]
+    fn test_max_matches_per_cell() {
+        let mut printer = Printer::new(true, false, OutputFormat::Csv, false, false);
+        let pattern = Regex::new("ab").unwrap();
+        let marked = |printer: &Printer| {
+            printer.mark_matches("ab-ab-ab-ab", &pattern, |text| format!("[{}]", text))
+        };
+        assert_eq!(marked(&printer), "[ab]-[ab]-[ab]-[ab]");
+        printer.max_matches_per_cell = Some(1);
+        assert_eq!(marked(&printer), "[ab]-ab-ab-ab");
+        printer.max_matches_per_cell = Some(10);
+        assert_eq!(marked(&printer), "[ab]-[ab]-[ab]-[ab]");
+    }
+
+    #[test]
+    fn test_table_highlights_matched_text() {
+        let _color = force_color();
+        let printer = Printer::new(true, false, OutputFormat::Table, false, false);
+        let headers = ["name".to_string(), "city".to_string()];
+        let row = |is_context| OutputRow {
+            row_num: 3,
+            cells: vec!["Alice".to_string(), "Alicante".to_string()],
+            is_context,
+            nulls: None,
+        };
+        let pattern = Regex::new("Ali").unwrap();
+        let table = printer
+            .table(&headers, None, &[row(false)], &pattern, true, 1)
+            .to_string();
+        // Only the match is painted, not the rest of its cell, and never in
+        // the plain leading columns
+        assert!(table.contains(" Alice "), "{}", table);
+        assert!(table.contains("\u{1b}[1;31mAli\u{1b}[0mcante"), "{}", table);
+        // Context rows aren't highlighted at all
+        let table = printer
+            .table(&headers, None, &[row(true)], &pattern, true, 0)
+            .to_string();
+        assert!(!table.contains("\u{1b}[1;31m"), "{}", table);
+    }
+
+    #[test]
+    fn test_vertical() {
+        let mut printer = Printer::new(false, false, OutputFormat::Vertical, false, false);
+        let headers = ["id".to_string(), "payload".to_string()];
+        let rows = [
+            OutputRow {
+                row_num: 1,
+                cells: vec!["7".to_string(), "plain".to_string()],
+                is_context: true,
+                nulls: None,
+            },
+            OutputRow {
+                row_num: 2,
+                cells: vec!["8".to_string(), "[1]".to_string()],
+                is_context: false,
+                nulls: None,
+            },
+        ];
+        let pattern = Regex::new("1").unwrap();
+        assert_eq!(
+            printer.vertical(&headers, &rows, &pattern),
+            "-[ row 1 (context) ]-\n     id: 7\npayload: plain\n\
+             -[ row 2 ]-\n     id: 8\npayload: [1]\n"
+        );
+        // Pretty-printed JSON continues under the start of its value
+        printer.pretty_json = true;
+        assert_eq!(
+            printer.vertical(&headers, &rows[1..], &pattern),
+            "-[ row 2 ]-\n     id: 8\npayload: [\n           1\n         ]\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_escape() {
+        assert_eq!(markdown_escape("a|b"), "a\\|b");
+        assert_eq!(markdown_escape("line1\nline2"), "line1<br>line2");
+        assert_eq!(markdown_escape("plain"), "plain");
+    }
+
+    #[test]
+    fn test_pretty_json() {
+        assert_eq!(
+            pretty_json(r#"{"b":1,"a":[true,null]}"#).unwrap(),
+            "{\n  \"b\": 1,\n  \"a\": [\n    true,\n    null\n  ]\n}"
+        );
+        assert_eq!(pretty_json("[1,2"), None);
+        assert_eq!(pretty_json("42"), None);
+        assert_eq!(pretty_json("plain"), None);
+
+        let mut printer = Printer::new(false, false, OutputFormat::Table, false, false);
+        assert_eq!(printer.display_cell("[1]"), "[1]");
+        printer.pretty_json = true;
+        assert_eq!(printer.display_cell("[1]"), "[\n  1\n]");
+    }
+
+    #[test]
+    fn test_truncate_display() {
+        assert_eq!(truncate_display("abc", 3), "abc");
+        assert_eq!(truncate_display("abcdef", 4), "abc…");
+        // A flag emoji and an accented letter are single graphemes
+        assert_eq!(truncate_display("🇨🇦e\u{301}xyz", 3), "🇨🇦e\u{301}…");
+        assert_eq!(truncate_display("日本語テキスト", 3), "日本…");
+    }
+
+    #[test]
+    fn test_json_value_uses_column_type() {
+        assert_eq!(
+            json_value("42", Some(&DataType::Int64), false),
+            Value::from(42)
+        );
+        assert_eq!(
+            json_value("-1.5", Some(&DataType::Float64), false),
+            Value::from(-1.5)
+        );
+        assert_eq!(
+            json_value("true", Some(&DataType::Boolean), false),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            json_value("NULL", Some(&DataType::Int32), true),
+            Value::Null
+        );
+        // Only real nulls: a string that reads like --null-text stays one
+        assert_eq!(
+            json_value("NULL", Some(&DataType::Utf8), false),
+            Value::from("NULL")
+        );
+        assert_eq!(
+            json_value("", Some(&DataType::Utf8), false),
+            Value::from("")
+        );
+        assert_eq!(json_value("N/A", Some(&DataType::Utf8), true), Value::Null);
+        // Untyped (CSV) and string columns stay strings
+        assert_eq!(json_value("42", None, false), Value::from("42"));
+        assert_eq!(
+            json_value("42", Some(&DataType::Utf8), false),
+            Value::from("42")
+        );
+        // Nested cells are embedded as JSON rather than as strings
+        let list = DataType::new_list(DataType::Utf8, true);
+        assert_eq!(
+            json_value(r#"["a","b"]"#, Some(&list), false),
+            serde_json::json!(["a", "b"])
+        );
+        assert_eq!(json_value("NULL", Some(&list), true), Value::Null);
+    }
+
+    #[test]
+    fn test_merged_table() {
+        let strings = |cells: &[&str]| cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let row = |row_num, cells: &[&str]| OutputRow {
+            row_num,
+            cells: strings(cells),
+            is_context: false,
+            nulls: None,
+        };
+        let mut merged = MergedTable::default();
+        merged.add(
+            "a.csv",
+            &strings(&["name", "age"]),
+            None,
+            &[row(3, &["Alice", "30"])],
+        );
+        merged.add(
+            "b.parquet",
+            &strings(&["age", "city", "file"]),
+            Some(&[DataType::Int64, DataType::Utf8, DataType::Utf8]),
+            &[row(1, &["28", "Oslo", "x"])],
+        );
+        merged.pad_rows();
+        assert_eq!(merged.headers, ["file", "name", "age", "city", "file"]);
+        assert_eq!(merged.rows[0].cells, ["a.csv", "Alice", "30", "", ""]);
+        assert_eq!(merged.rows[1].cells, ["b.parquet", "", "28", "Oslo", "x"]);
+        // Row numbers stay those of each file
+        assert_eq!(merged.rows[1].row_num, 1);
+        // 'age' was untyped in the CSV, so it's text in the merged table
+        assert_eq!(merged.column_types().unwrap(), vec![DataType::Utf8; 5]);
+    }
+}
@@ -0,0 +1,133 @@
+//! A progress bar on stderr for searches of a directory or prefix: files done
+//! out of those found, and the one being searched.
+
+use crate::cli::Cli;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Searches quicker than this finish without ever showing the bar.
+const SHOW_AFTER: Duration = Duration::from_millis(500);
+
+/// The bar of the search under way, if it has one, for `report!` to clear.
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Like `eprintln!`, but with any progress bar cleared off the terminal while
+/// the line is written, so warnings don't land in the middle of it.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! report {
+    ($($arg:tt)*) => {
+        $crate::progress::suspend(|| eprintln!($($arg)*))
+    };
+}
+
+/// Run `f` with any progress bar hidden, redrawing it afterwards.
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    let bar = ACTIVE.lock().ok().and_then(|active| active.clone());
+    match bar {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
+
+pub struct Progress {
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    /// A bar, drawn once the search has run a while, or none at all: with -q
+    /// or --no-progress, when stderr isn't a terminal, and when stdout is,
+    /// since redrawing the bar would garble the matches printed there.
+    pub fn new(cli: &Cli) -> Self {
+        let enabled = is_enabled(
+            cli,
+            atty::is(atty::Stream::Stderr),
+            atty::is(atty::Stream::Stdout),
+        );
+        let progress = Self {
+            bar: enabled.then(|| {
+                let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::hidden());
+                bar.set_style(
+                    ProgressStyle::with_template("[{elapsed}] {wide_bar} {pos}/{len} {msg}")
+                        .expect("valid progress template")
+                        .progress_chars("=> "),
+                );
+                let shown = bar.downgrade();
+                std::thread::spawn(move || {
+                    std::thread::sleep(SHOW_AFTER);
+                    if let Some(bar) = shown.upgrade()
+                        && !bar.is_finished()
+                    {
+                        bar.set_draw_target(ProgressDrawTarget::stderr());
+                        bar.enable_steady_tick(Duration::from_millis(200));
+                    }
+                });
+                bar
+            }),
+        };
+        if let Ok(mut active) = ACTIVE.lock() {
+            active.clone_from(&progress.bar);
+        }
+        progress
+    }
+
+    pub fn set_total(&self, total: usize) {
+        if let Some(bar) = &self.bar {
+            bar.set_length(total as u64);
+        }
+    }
+
+    /// Show `name` as the file being searched.
+    pub fn start(&self, name: &str) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(name.to_string());
+        }
+    }
+
+    /// Count the file being searched as done.
+    pub fn done(&self) {
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+    }
+}
+
+impl Drop for Progress {
+    /// Clear the bar off the terminal however the search ends.
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+        if let Ok(mut active) = ACTIVE.lock() {
+            *active = None;
+        }
+    }
+}
+
+/// Whether to show a bar, given whether stderr and stdout are terminals.
+fn is_enabled(cli: &Cli, stderr_is_terminal: bool, stdout_is_terminal: bool) -> bool {
+    !cli.no_progress && !cli.quiet && stderr_is_terminal && !stdout_is_terminal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_progress_enabled() {
+        let cli = Cli::parse_from(["table-grep", "a", "."]);
+        assert!(is_enabled(&cli, true, false));
+        // Not when stderr isn't a terminal, nor over matches printed to one
+        assert!(!is_enabled(&cli, false, false));
+        assert!(!is_enabled(&cli, true, true));
+        let cli = Cli::parse_from(["table-grep", "--no-progress", "a", "."]);
+        assert!(!is_enabled(&cli, true, false));
+        let cli = Cli::parse_from(["table-grep", "-q", "a", "."]);
+        assert!(!is_enabled(&cli, true, false));
+
+        // Without a bar, reports are printed as they are
+        assert_eq!(suspend(|| 1 + 1), 2);
+    }
+}
@@ -0,0 +1,602 @@
+//! `--query` expressions: SQL-style WHERE clauses such as
+//! `status = 'active' AND (amount > 100 OR region LIKE 'EU%')`, parsed once
+//! and evaluated against every row.
+//!
+//! Grammar (keywords are case-insensitive):
+//!
+//! ```text
+//! expr      := and ("OR" and)*
+//! and       := not ("AND" not)*
+//! not       := "NOT" not | "(" expr ")" | predicate
+//! predicate := column op literal
+//!            | column ["NOT"] ("LIKE" | "ILIKE") string
+//!            | column ["NOT"] "IN" "(" literal ("," literal)* ")"
+//!            | column ["NOT"] "BETWEEN" literal "AND" literal
+//!            | column "IS" ["NOT"] "NULL"
+//! op        := "=" | "==" | "!=" | "<>" | "<" | "<=" | ">" | ">="
+//! column    := identifier | "quoted name" | `quoted name`
+//! literal   := number | 'string' | TRUE | FALSE
+//! ```
+
+use crate::filter::Op;
+use arrow::datatypes::DataType;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+
+/// A value compared against a cell. Numbers compare numerically, strings
+/// as text (or numerically, when the column has a numeric type).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Number(f64),
+    Text(String),
+}
+
+/// A parsed `--query` expression. Columns are named by `C`: a `String` as
+/// written, and after [`Expr::bind`] an index into one file's headers
+/// (`None` when the file lacks the column).
+#[derive(Debug, Clone)]
+pub enum Expr<C> {
+    And(Box<Expr<C>>, Box<Expr<C>>),
+    Or(Box<Expr<C>>, Box<Expr<C>>),
+    Not(Box<Expr<C>>),
+    Compare(C, Op, Literal),
+    In(C, Vec<Literal>),
+    Like(C, Regex),
+    IsNull(C),
+}
+
+pub type Query = Expr<String>;
+
+impl Query {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected '{}' in '{}'", token.text, s)),
+        }
+    }
+
+    /// Every column the expression refers to, in order of appearance.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        self.visit_columns(&mut |column: &String| columns.push(column.as_str()));
+        columns
+    }
+
+    /// Resolve column names against one file's headers.
+    pub fn bind(&self, resolve: &mut impl FnMut(&str) -> Option<usize>) -> Expr<Option<usize>> {
+        self.map_columns(&mut |column: &String| resolve(column))
+    }
+}
+
+impl<C> Expr<C> {
+    fn visit_columns<'a>(&'a self, f: &mut impl FnMut(&'a C)) {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.visit_columns(f);
+                b.visit_columns(f);
+            }
+            Expr::Not(inner) => inner.visit_columns(f),
+            Expr::Compare(c, ..) | Expr::In(c, _) | Expr::Like(c, _) | Expr::IsNull(c) => f(c),
+        }
+    }
+
+    fn map_columns<D>(&self, f: &mut impl FnMut(&C) -> D) -> Expr<D> {
+        match self {
+            Expr::And(a, b) => Expr::And(Box::new(a.map_columns(f)), Box::new(b.map_columns(f))),
+            Expr::Or(a, b) => Expr::Or(Box::new(a.map_columns(f)), Box::new(b.map_columns(f))),
+            Expr::Not(inner) => Expr::Not(Box::new(inner.map_columns(f))),
+            Expr::Compare(c, op, literal) => Expr::Compare(f(c), *op, literal.clone()),
+            Expr::In(c, literals) => Expr::In(f(c), literals.clone()),
+            Expr::Like(c, pattern) => Expr::Like(f(c), pattern.clone()),
+            Expr::IsNull(c) => Expr::IsNull(f(c)),
+        }
+    }
+}
+
+impl Expr<Option<usize>> {
+    /// Evaluate against one row with SQL's three-valued logic: `None` means
+    /// unknown, as when comparing a null cell, a missing column, or a
+    /// non-numeric cell with a number. Only rows evaluating to `Some(true)`
+    /// match.
+    pub fn eval(
+        &self,
+        row: &[String],
+        is_null: &dyn Fn(usize) -> bool,
+        column_types: Option<&[DataType]>,
+    ) -> Option<bool> {
+        let cell = |idx: &Option<usize>| {
+            let idx = (*idx)?;
+            let cell = row.get(idx)?;
+            let numeric = column_types
+                .and_then(|types| types.get(idx))
+                .is_some_and(DataType::is_numeric);
+            (!is_null(idx)).then_some((cell.as_str(), numeric))
+        };
+
+        match self {
+            Expr::And(a, b) => match a.eval(row, is_null, column_types) {
+                Some(false) => Some(false),
+                left => match (left, b.eval(row, is_null, column_types)?) {
+                    (_, false) => Some(false),
+                    (left, true) => left,
+                },
+            },
+            Expr::Or(a, b) => match a.eval(row, is_null, column_types) {
+                Some(true) => Some(true),
+                left => match (left, b.eval(row, is_null, column_types)?) {
+                    (_, true) => Some(true),
+                    (left, false) => left,
+                },
+            },
+            Expr::Not(inner) => inner.eval(row, is_null, column_types).map(|b| !b),
+            Expr::Compare(idx, op, literal) => {
+                let (cell, numeric) = cell(idx)?;
+                compare(cell, literal, numeric).map(|ordering| op_holds(*op, ordering))
+            }
+            Expr::In(idx, literals) => {
+                let (cell, numeric) = cell(idx)?;
+                let mut unknown = false;
+                for literal in literals {
+                    match compare(cell, literal, numeric) {
+                        Some(Ordering::Equal) => return Some(true),
+                        Some(_) => {}
+                        None => unknown = true,
+                    }
+                }
+                (!unknown).then_some(false)
+            }
+            Expr::Like(idx, pattern) => cell(idx).map(|(cell, _)| pattern.is_match(cell)),
+            Expr::IsNull(idx) => idx.map(is_null),
+        }
+    }
+}
+
+/// Order `cell` against `literal`, or `None` when they can't be compared.
+/// Text like 'inf' or 'NaN' isn't a number.
+fn compare(cell: &str, literal: &Literal, numeric: bool) -> Option<Ordering> {
+    let number = |s: &str| s.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+    match literal {
+        Literal::Number(n) => number(cell)?.partial_cmp(n),
+        Literal::Text(text) if numeric => number(cell)?.partial_cmp(&number(text)?),
+        Literal::Text(text) => Some(cell.cmp(text.as_str())),
+    }
+}
+
+fn op_holds(op: Op, ordering: Ordering) -> bool {
+    match op {
+        Op::Lt => ordering.is_lt(),
+        Op::Le => ordering.is_le(),
+        Op::Gt => ordering.is_gt(),
+        Op::Ge => ordering.is_ge(),
+        Op::Eq => ordering.is_eq(),
+        Op::Ne => ordering.is_ne(),
+    }
+}
+
+/// Compile a LIKE pattern: `%` matches any run of characters, `_` any one.
+fn like_regex(pattern: &str, ignore_case: bool) -> Regex {
+    let mut re = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '%' => re.push_str(".*"),
+            '_' => re.push('.'),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    RegexBuilder::new(&re)
+        .case_insensitive(ignore_case)
+        .dot_matches_new_line(true)
+        .build()
+        .expect("escaped LIKE pattern is a valid regex")
+}
+
+// ── tokenizer ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    /// A bare word: a column name or a keyword.
+    Word,
+    /// A "double-quoted" or `backquoted` column name.
+    Column,
+    Str,
+    Number(f64),
+    Op(Op),
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    /// The token as written (unquoted for strings and quoted columns).
+    text: String,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let token = |kind, text: &[char]| Token {
+        kind,
+        text: text.iter().collect(),
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let starts_number = c.is_ascii_digit()
+            || (matches!(c, '-' | '.') && chars.get(i + 1).is_some_and(char::is_ascii_digit));
+        if c.is_whitespace() {
+            i += 1;
+        } else if matches!(c, '\'' | '"' | '`') {
+            // Quotes inside are doubled, as in SQL: 'it''s'
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(format!("unterminated {} in '{}'", c, s)),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            let kind = if c == '\'' { Kind::Str } else { Kind::Column };
+            tokens.push(Token { kind, text });
+        } else if starts_number {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric()
+                    || chars[i] == '.'
+                    || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number in '{}'", text, s))?;
+            tokens.push(Token {
+                kind: Kind::Number(n),
+                text,
+            });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.')) {
+                i += 1;
+            }
+            tokens.push(token(Kind::Word, &chars[start..i]));
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let (kind, len) = match (two.as_str(), c) {
+                ("<=", _) => (Kind::Op(Op::Le), 2),
+                (">=", _) => (Kind::Op(Op::Ge), 2),
+                ("==", _) => (Kind::Op(Op::Eq), 2),
+                ("!=" | "<>", _) => (Kind::Op(Op::Ne), 2),
+                (_, '<') => (Kind::Op(Op::Lt), 1),
+                (_, '>') => (Kind::Op(Op::Gt), 1),
+                (_, '=') => (Kind::Op(Op::Eq), 1),
+                (_, '(') => (Kind::LParen, 1),
+                (_, ')') => (Kind::RParen, 1),
+                (_, ',') => (Kind::Comma, 1),
+                _ => return Err(format!("unexpected '{}' in '{}'", c, s)),
+            };
+            tokens.push(token(kind, &chars[i..i + len]));
+            i += len;
+        }
+    }
+    Ok(tokens)
+}
+
+// ── parser ───────────────────────────────────────────────────────────────────
+
+const KEYWORDS: [&str; 9] = [
+    "AND", "OR", "NOT", "IS", "NULL", "LIKE", "ILIKE", "IN", "BETWEEN",
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| format!("expected {} at end of query", expected))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == Kind::Word && t.text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        let token = self.next(keyword)?;
+        if token.kind == Kind::Word && token.text.eq_ignore_ascii_case(keyword) {
+            Ok(())
+        } else {
+            Err(format!("expected {}, got '{}'", keyword, token.text))
+        }
+    }
+
+    fn expect(&mut self, kind: Kind, what: &str) -> Result<(), String> {
+        let token = self.next(what)?;
+        if token.kind == kind {
+            Ok(())
+        } else {
+            Err(format!("expected {}, got '{}'", what, token.text))
+        }
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut expr = self.not()?;
+        while self.eat_keyword("AND") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Query, String> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.peek().is_some_and(|t| t.kind == Kind::LParen) {
+            self.pos += 1;
+            let expr = self.or()?;
+            self.expect(Kind::RParen, "')'")?;
+            return Ok(expr);
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> Result<Query, String> {
+        let token = self.next("a column name")?;
+        let column = match token.kind {
+            Kind::Column => token.text,
+            Kind::Word if !KEYWORDS.iter().any(|k| token.text.eq_ignore_ascii_case(k)) => {
+                token.text
+            }
+            _ => return Err(format!("expected a column name, got '{}'", token.text)),
+        };
+
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            let expr = Expr::IsNull(column);
+            return Ok(if negated {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            });
+        }
+
+        let negated = self.eat_keyword("NOT");
+        let expr = if self.at_keyword("LIKE") || self.at_keyword("ILIKE") {
+            let ignore_case = self.at_keyword("ILIKE");
+            self.pos += 1;
+            let token = self.next("a LIKE pattern")?;
+            if token.kind != Kind::Str {
+                return Err(format!(
+                    "expected a quoted LIKE pattern, got '{}'",
+                    token.text
+                ));
+            }
+            Expr::Like(column, like_regex(&token.text, ignore_case))
+        } else if self.eat_keyword("IN") {
+            self.expect(Kind::LParen, "'(' after IN")?;
+            let mut literals = vec![self.literal()?];
+            while self.peek().is_some_and(|t| t.kind == Kind::Comma) {
+                self.pos += 1;
+                literals.push(self.literal()?);
+            }
+            self.expect(Kind::RParen, "')' to close the IN list")?;
+            Expr::In(column, literals)
+        } else if self.eat_keyword("BETWEEN") {
+            let low = self.literal()?;
+            self.expect_keyword("AND")?;
+            let high = self.literal()?;
+            Expr::And(
+                Box::new(Expr::Compare(column.clone(), Op::Ge, low)),
+                Box::new(Expr::Compare(column, Op::Le, high)),
+            )
+        } else if negated {
+            let token = self.next("LIKE, IN or BETWEEN")?;
+            return Err(format!(
+                "expected LIKE, IN or BETWEEN after NOT, got '{}'",
+                token.text
+            ));
+        } else {
+            let token = self.next("a comparison")?;
+            let Kind::Op(op) = token.kind else {
+                return Err(format!(
+                    "expected a comparison after '{}', got '{}'",
+                    column, token.text
+                ));
+            };
+            Expr::Compare(column, op, self.literal()?)
+        };
+        Ok(if negated {
+            Expr::Not(Box::new(expr))
+        } else {
+            expr
+        })
+    }
+
+    fn literal(&mut self) -> Result<Literal, String> {
+        let token = self.next("a value")?;
+        match token.kind {
+            Kind::Number(n) => Ok(Literal::Number(n)),
+            Kind::Str => Ok(Literal::Text(token.text)),
+            Kind::Word
+                if token.text.eq_ignore_ascii_case("true")
+                    || token.text.eq_ignore_ascii_case("false") =>
+            {
+                Ok(Literal::Text(token.text.to_lowercase()))
+            }
+            _ => Err(format!(
+                "expected a number or 'quoted string', got '{}'",
+                token.text
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_grammar() {
+        let headers = ["status", "amount", "region", "note", "unit price"];
+        let rows = [
+            ["active", "150", "EU-West", "", "9.5"],
+            ["active", "80", "US-East", "it's late", "12"],
+            ["closed", "500", "eu-north", "rush", "abc"],
+            ["pending", "100", "APAC", "", ""],
+        ];
+        // Each query and the (1-based) rows it keeps
+        let cases: &[(&str, &[usize])] = &[
+            ("status = 'active'", &[1, 2]),
+            ("status == 'active' AND amount > 100", &[1]),
+            (
+                "status = 'active' and amount > 100 or region LIKE 'EU%'",
+                &[1],
+            ),
+            ("amount >= 100 AND NOT status = 'closed'", &[1, 4]),
+            (
+                "(status = 'closed' OR status = 'pending') AND amount < 200",
+                &[4],
+            ),
+            ("status != 'active'", &[3, 4]),
+            ("status <> 'active' AND amount <= 100", &[4]),
+            ("region ILIKE 'eu%'", &[1, 3]),
+            ("region NOT LIKE '%-%'", &[4]),
+            ("region LIKE '__-____'", &[1, 2]),
+            ("status IN ('closed', 'pending')", &[3, 4]),
+            ("amount IN (80, 100)", &[2, 4]),
+            ("status NOT IN ('active')", &[3, 4]),
+            ("amount BETWEEN 90 AND 200", &[1, 4]),
+            ("amount NOT BETWEEN 90 AND 200", &[2, 3]),
+            ("note IS NULL", &[1, 4]),
+            ("note IS NOT NULL", &[2, 3]),
+            ("note = 'it''s late'", &[2]),
+            // Comparisons with NULL are unknown, and so is their negation
+            ("note != 'rush'", &[2]),
+            ("NOT note = 'rush'", &[2]),
+            ("note = 'rush' OR amount = 500", &[3]),
+            // Unparsable cells compare with numbers as unknown
+            ("\"unit price\" < 10", &[1]),
+            ("NOT `unit price` < 10", &[2]),
+            // Unquoted numbers compare numerically, quoted text lexically
+            ("amount > 99", &[1, 3, 4]),
+            ("amount > '99'", &[]),
+            // A missing column is unknown
+            ("missing = 1 OR status = 'pending'", &[4]),
+        ];
+
+        for (query, expected) in cases {
+            let parsed = Query::parse(query).unwrap_or_else(|e| panic!("{}: {}", query, e));
+            let bound = parsed.bind(&mut |name| headers.iter().position(|h| *h == name));
+            let kept: Vec<usize> = rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| {
+                    let row: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+                    let is_null = |i: usize| row[i].is_empty();
+                    bound.eval(&row, &is_null, None) == Some(true)
+                })
+                .map(|(i, _)| i + 1)
+                .collect();
+            assert_eq!(&kept, expected, "{}", query);
+        }
+
+        // Text that Rust would read as a float isn't a number here
+        let query = Query::parse("amount > 100").unwrap();
+        let bound = query.bind(&mut |name| (name == "amount").then_some(0));
+        let no_nulls = |_: usize| false;
+        let kept: Vec<&str> = ["inf", "150", "NaN", "-infinity"]
+            .into_iter()
+            .filter(|cell| bound.eval(&[cell.to_string()], &no_nulls, None) == Some(true))
+            .collect();
+        assert_eq!(kept, ["150"]);
+    }
+
+    #[test]
+    fn test_query_typed_columns() {
+        let query = Query::parse("amount > '99' AND flag = TRUE").unwrap();
+        let bound = query.bind(&mut |name| ["amount", "flag"].iter().position(|h| *h == name));
+        let row = vec!["150".to_string(), "true".to_string()];
+        let no_nulls = |_: usize| false;
+        // Text literals compare numerically against numeric columns
+        let types = [DataType::Int64, DataType::Boolean];
+        assert_eq!(bound.eval(&row, &no_nulls, Some(&types)), Some(true));
+        assert_eq!(bound.eval(&row, &no_nulls, None), Some(false));
+    }
+
+    #[test]
+    fn test_query_parse_errors() {
+        assert_eq!(
+            Query::parse("a = 1 AND (b = 2 OR c IS NULL)")
+                .unwrap()
+                .columns(),
+            ["a", "b", "c"]
+        );
+        for bad in [
+            "",
+            "status",
+            "status = ",
+            "status = active",
+            "status = 'active",
+            "(status = 'a'",
+            "status = 'a')",
+            "status LIKE 5",
+            "status IN ()",
+            "status NOT = 'a'",
+            "status IS 'a'",
+            "AND = 1",
+            "a = 1 b = 2",
+            "a # 1",
+            "a = 1.2.3",
+        ] {
+            assert!(Query::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Reading remote files piecemeal, a byte range at a time, shared by the
+//! `http` and `object-store` features.
+
+use anyhow::Result;
+use bytes::Bytes;
+use parquet::errors::ParquetError;
+use parquet::file::reader::{ChunkReader, Length};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+/// How much `RangedFile` fetches at least per request. Parquet reads a page
+/// header and then its data, so reading ahead saves a round trip for each.
+const READ_AHEAD: u64 = 1 << 20;
+
+/// A remote file that can be read a byte range at a time.
+pub trait RangeSource: Send + Sync {
+    /// The file's URL, for messages.
+    fn name(&self) -> &str;
+
+    /// The file's size in bytes.
+    fn size(&self) -> u64;
+
+    /// Exactly `length` bytes starting at `start`.
+    fn get_range(&self, start: u64, length: u64) -> Result<Bytes>;
+}
+
+/// A `RangeSource` read as Parquet needs it. Each handle keeps the last block
+/// it fetched; clones start without one, so threads don't contend for it.
+pub struct RangedFile<S> {
+    source: Arc<S>,
+    block: Arc<Mutex<(u64, Bytes)>>,
+}
+
+impl<S: RangeSource> RangedFile<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source: Arc::new(source),
+            block: Arc::default(),
+        }
+    }
+
+    /// A reader from `start` to the end of the file, fetched a block at a
+    /// time so it can be abandoned early cheaply.
+    pub fn reader(&self, start: u64) -> RangedReader<S> {
+        // Shares this handle's block, which the page data read next is
+        // likely in.
+        RangedReader {
+            file: Self {
+                source: Arc::clone(&self.source),
+                block: Arc::clone(&self.block),
+            },
+            pos: start,
+            buf: Bytes::new(),
+        }
+    }
+
+    /// `length` bytes at `start`, from the last block fetched if it has them.
+    fn fetch(&self, start: u64, length: u64) -> parquet::errors::Result<Bytes> {
+        let mut block = self.block.lock().unwrap_or_else(|e| e.into_inner());
+        let (block_start, bytes) = &*block;
+        if start >= *block_start && start + length <= block_start + bytes.len() as u64 {
+            let offset = (start - block_start) as usize;
+            return Ok(bytes.slice(offset..offset + length as usize));
+        }
+
+        let size = self.source.size();
+        let wanted = length.max(READ_AHEAD).min(size.saturating_sub(start));
+        if wanted < length {
+            return Err(ParquetError::EOF(format!(
+                "'{}' is {} bytes; can't read {} at offset {}",
+                self.source.name(),
+                size,
+                length,
+                start
+            )));
+        }
+        let bytes = self
+            .source
+            .get_range(start, wanted)
+            .map_err(|e| ParquetError::General(e.to_string()))?;
+        if bytes.len() as u64 != wanted {
+            return Err(ParquetError::EOF(format!(
+                "'{}' sent {} of {} bytes at offset {}",
+                self.source.name(),
+                bytes.len(),
+                wanted,
+                start
+            )));
+        }
+        *block = (start, bytes.clone());
+        Ok(bytes.slice(..length as usize))
+    }
+
+    /// Some of the bytes from `start` on: the rest of the last block fetched
+    /// if it has `start`, otherwise a new block. Empty at the end of the file.
+    fn fetch_from(&self, start: u64) -> parquet::errors::Result<Bytes> {
+        {
+            let block = self.block.lock().unwrap_or_else(|e| e.into_inner());
+            let (block_start, bytes) = &*block;
+            if start >= *block_start && start < block_start + bytes.len() as u64 {
+                return Ok(bytes.slice((start - block_start) as usize..));
+            }
+        }
+        let remaining = self.source.size().saturating_sub(start);
+        if remaining == 0 {
+            return Ok(Bytes::new());
+        }
+        self.fetch(start, remaining.min(READ_AHEAD))
+    }
+}
+
+impl<S> Clone for RangedFile<S> {
+    fn clone(&self) -> Self {
+        Self {
+            source: Arc::clone(&self.source),
+            block: Arc::default(),
+        }
+    }
+}
+
+impl<S: RangeSource> Length for RangedFile<S> {
+    fn len(&self) -> u64 {
+        self.source.size()
+    }
+}
+
+impl<S: RangeSource + 'static> ChunkReader for RangedFile<S> {
+    type T = RangedReader<S>;
+
+    fn get_read(&self, start: u64) -> parquet::errors::Result<Self::T> {
+        Ok(self.reader(start))
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> parquet::errors::Result<Bytes> {
+        if length == 0 {
+            return Ok(Bytes::new());
+        }
+        self.fetch(start, length as u64)
+    }
+}
+
+/// Reads a `RangedFile` from some offset to the end, a block at a time.
+pub struct RangedReader<S> {
+    file: RangedFile<S>,
+    pos: u64,
+    buf: Bytes,
+}
+
+impl<S: RangeSource> Read for RangedReader<S> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.buf.is_empty() {
+            self.buf = self
+                .file
+                .fetch_from(self.pos)
+                .map_err(std::io::Error::other)?;
+            self.pos += self.buf.len() as u64;
+        }
+        let n = out.len().min(self.buf.len());
+        out[..n].copy_from_slice(&self.buf.split_to(n));
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// An in-memory file that counts the requests made of it.
+    struct Counted {
+        data: Bytes,
+        requests: AtomicUsize,
+    }
+
+    impl RangeSource for Counted {
+        fn name(&self) -> &str {
+            "memory"
+        }
+
+        fn size(&self) -> u64 {
+            self.data.len() as u64
+        }
+
+        fn get_range(&self, start: u64, length: u64) -> Result<Bytes> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Ok(self.data.slice(start as usize..(start + length) as usize))
+        }
+    }
+
+    #[test]
+    fn test_ranged_file_reads_ahead() {
+        let data: Vec<u8> = (0..3 * READ_AHEAD).map(|i| i as u8).collect();
+        let file = RangedFile::new(Counted {
+            data: Bytes::from(data.clone()),
+            requests: AtomicUsize::new(0),
+        });
+        let requests = || file.source.requests.load(Ordering::Relaxed);
+
+        assert_eq!(file.get_bytes(10, 5).unwrap(), &data[10..15]);
+        assert_eq!(file.get_bytes(100, 50).unwrap(), &data[100..150]);
+        assert_eq!(requests(), 1);
+
+        let mut header = [0; 4];
+        file.get_read(200).unwrap().read_exact(&mut header).unwrap();
+        assert_eq!(header, data[200..204]);
+        assert_eq!(requests(), 1);
+
+        let mut rest = Vec::new();
+        file.get_read(5).unwrap().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[5..]);
+
+        assert!(file.get_bytes(data.len() as u64 - 2, 5).is_err());
+    }
+}
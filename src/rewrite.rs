@@ -0,0 +1,206 @@
+//! Writing copies of files with their matches replaced (`--replace` with
+//! `--replace-output-dir` or `--in-place`), for redacting a whole dataset.
+//!
+//! Each copy is written to a temporary file next to its destination and
+//! renamed into place once complete, so an interrupted run never leaves a
+//! half-written file behind (or, with --in-place, destroys the original).
+
+use crate::cli::Cli;
+use crate::grep::{self, SearchContext, SearchStats};
+use crate::{http, store};
+use anyhow::Result;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+pub struct Rewriter {
+    /// The directory searched, or the searched file's own directory; copies
+    /// keep their paths relative to it.
+    root: PathBuf,
+    /// Where copies go, as given and canonicalized, or `None` to replace the
+    /// originals.
+    output_dir: Option<(PathBuf, PathBuf)>,
+}
+
+impl Rewriter {
+    /// A rewriter for --in-place or --replace-output-dir, or `None` when
+    /// files are only searched. Creates the output directory.
+    pub fn new(cli: &Cli) -> Result<Option<Self>> {
+        if !cli.rewrites_files() {
+            return Ok(None);
+        }
+        if cli.path() == "-" || http::is_url(cli.path()) || store::is_store_url(cli.path()) {
+            anyhow::bail!(
+                "--in-place and --replace-output-dir only rewrite local files, not '{}'",
+                if cli.path() == "-" {
+                    "<stdin>"
+                } else {
+                    cli.path()
+                }
+            );
+        }
+
+        let path = Path::new(cli.path());
+        let root = if path.is_dir() {
+            path.to_path_buf()
+        } else {
+            path.parent().unwrap_or(Path::new("")).to_path_buf()
+        };
+        let output_dir = match &cli.replace_output_dir {
+            Some(dir) => {
+                fs::create_dir_all(dir).map_err(|e| {
+                    anyhow::anyhow!("Failed to create output directory '{}': {}", dir, e)
+                })?;
+                let canonical = Path::new(dir).canonicalize()?;
+                if root
+                    .canonicalize()
+                    .is_ok_and(|root| root.starts_with(&canonical))
+                {
+                    anyhow::bail!(
+                        "--replace-output-dir '{}' holds the files being rewritten; use --in-place to replace them",
+                        dir
+                    );
+                }
+                Some((PathBuf::from(dir), canonical))
+            }
+            None => None,
+        };
+        Ok(Some(Self { root, output_dir }))
+    }
+
+    /// Whether `path` is in the output directory, so is a copy rather than
+    /// a file to rewrite.
+    pub fn is_output(&self, path: &Path) -> bool {
+        self.output_dir
+            .as_ref()
+            .is_some_and(|(_, dir)| path.canonicalize().is_ok_and(|path| path.starts_with(dir)))
+    }
+
+    /// Where the copy of `path` goes.
+    fn destination(&self, path: &Path) -> PathBuf {
+        match &self.output_dir {
+            Some((dir, _)) => dir.join(path.strip_prefix(&self.root).unwrap_or(path)),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Rewrite one file, if it has any matches, and print where the copy
+    /// went. Files without matches are left alone (and not copied).
+    pub fn rewrite_file(&self, path: &Path, ctx: &SearchContext) -> Result<SearchStats> {
+        let (stats, dest) = self.write_copy(path, ctx)?;
+        if let Some(dest) = dest {
+            ctx.printer.print_filename(&dest.display().to_string());
+        }
+        Ok(stats)
+    }
+
+    /// `rewrite_file` without the printing: the stats, and where the copy
+    /// went if one was written.
+    fn write_copy(
+        &self,
+        path: &Path,
+        ctx: &SearchContext,
+    ) -> Result<(SearchStats, Option<PathBuf>)> {
+        let dest = self.destination(path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                anyhow::anyhow!("Failed to create directory '{}': {}", parent.display(), e)
+            })?;
+        }
+        let name = dest.file_name().unwrap_or_default().to_string_lossy();
+        let temp = dest.with_file_name(format!(".{}.table-grep-tmp", name));
+        let file = File::create(&temp)
+            .map_err(|e| anyhow::anyhow!("Failed to create '{}': {}", temp.display(), e))?;
+
+        match grep::rewrite_table(path, file, ctx) {
+            Ok(stats) if stats.matches > 0 => {
+                // The copy keeps the original's permissions
+                if let Ok(metadata) = fs::metadata(path) {
+                    let _ = fs::set_permissions(&temp, metadata.permissions());
+                }
+                fs::rename(&temp, &dest)
+                    .map_err(|e| anyhow::anyhow!("Failed to write '{}': {}", dest.display(), e))?;
+                Ok((stats, Some(dest)))
+            }
+            result => {
+                let _ = fs::remove_file(&temp);
+                result.map(|stats| (stats, None))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Printer;
+    use clap::Parser;
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::parse_from(["table-grep", "-r", "X"].iter().chain(args))
+    }
+
+    #[test]
+    fn test_rewriter_paths() {
+        let dir = std::env::temp_dir().join(format!("table-grep-rewriter-{}", std::process::id()));
+        let input = dir.join("in");
+        fs::create_dir_all(input.join("sub")).unwrap();
+        let (input_str, output_str) = (
+            input.to_str().unwrap(),
+            dir.join("out").to_str().unwrap().to_string(),
+        );
+
+        assert!(Rewriter::new(&cli(&["a", input_str])).unwrap().is_none());
+        assert!(Rewriter::new(&cli(&["--in-place", "a", "-"])).is_err());
+        // The copies mustn't overwrite the files being rewritten
+        let args = [
+            "--replace-output-dir",
+            dir.to_str().unwrap(),
+            "a",
+            input_str,
+        ];
+        assert!(Rewriter::new(&cli(&args)).is_err());
+
+        let args = ["--replace-output-dir", &output_str, "a", input_str];
+        let rewriter = Rewriter::new(&cli(&args)).unwrap().unwrap();
+        // Copies keep their place in the tree, under the directory as given
+        let dest = rewriter.destination(&input.join("sub/a.csv"));
+        assert_eq!(dest, Path::new(&output_str).join("sub/a.csv"));
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::write(&dest, "").unwrap();
+        assert!(rewriter.is_output(&dest));
+        assert!(!rewriter.is_output(&input.join("sub")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_in_place() {
+        let dir = std::env::temp_dir().join(format!("table-grep-in-place-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let people = dir.join("people.csv");
+        let other = dir.join("other.csv");
+        fs::write(&people, "name,age\nAlice,30\nBob,45\n").unwrap();
+        fs::write(&other, "name,age\nCarol,52\n").unwrap();
+
+        let cli = cli(&["--in-place", "Alice", dir.to_str().unwrap()]);
+        let pattern = cli.build_regex().unwrap();
+        let printer = Printer::new(false, false, cli.format, false, false);
+        let ctx = SearchContext::new(&pattern, &cli, &printer);
+        let rewriter = Rewriter::new(&cli).unwrap().unwrap();
+
+        let (stats, dest) = rewriter.write_copy(&people, &ctx).unwrap();
+        assert_eq!(stats.matches, 1);
+        assert_eq!(dest.as_deref(), Some(people.as_path()));
+        assert_eq!(
+            fs::read_to_string(&people).unwrap(),
+            "name,age\nX,30\nBob,45\n"
+        );
+        // A file without matches is left alone, and no temporary file stays
+        let (stats, dest) = rewriter.write_copy(&other, &ctx).unwrap();
+        assert_eq!((stats.matches, dest), (0, None));
+        assert_eq!(fs::read_to_string(&other).unwrap(), "name,age\nCarol,52\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+//! The library entry point: searching files for matching rows and handing
+//! them back as data rather than printing them.
+
+use crate::cli::Cli;
+use crate::filter::{ColumnMatch, ColumnSet};
+use crate::grep::{self, SearchContext};
+use crate::output::Printer;
+use anyhow::Result;
+use regex::Regex;
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::Arc;
+
+/// One matching row, as it would have been printed: after --replace, but
+/// with every column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRow {
+    /// The file the row came from, as given to [`Searcher::search_file`].
+    pub file: String,
+    /// 1-based row number, not counting the header row.
+    pub row_num: usize,
+    /// The file's column names, shared by all its rows.
+    pub headers: Arc<[String]>,
+    pub cells: Vec<String>,
+}
+
+/// Searches files with options parsed as for the command line, compiled
+/// once up front. Options that only change what's printed (formats, colors)
+/// have no effect; those that summarize or write files instead of yielding
+/// rows are refused.
+pub struct Searcher {
+    cli: Cli,
+    pattern: Regex,
+    column_pattern: Option<Regex>,
+    column_matches: Vec<ColumnMatch>,
+    column_sets: Vec<ColumnSet>,
+    exclude_pattern: Option<Regex>,
+    printer: Printer,
+}
+
+impl Searcher {
+    /// Compile the patterns and conditions in `cli`. Its path is ignored:
+    /// pass one to `search_file` instead.
+    pub fn new(cli: Cli) -> Result<Self> {
+        if cli.count
+            || cli.aggregates()
+            || cli.describes_only()
+            || cli.lists_files()
+            || cli.lists_cells()
+            || cli.quiet
+            || cli.output_file.is_some()
+            || cli.rewrites_files()
+        {
+            anyhow::bail!(
+                "Searcher only collects matching rows; counting, listing, describing and writing options aren't supported"
+            );
+        }
+        Ok(Self {
+            pattern: cli.build_regex()?,
+            column_pattern: cli.build_column_regex()?,
+            column_matches: cli.build_column_matches()?,
+            column_sets: cli.build_column_sets()?,
+            exclude_pattern: cli.build_exclude_regex()?,
+            printer: Printer::new(false, false, cli.format, false, false),
+            cli,
+        })
+    }
+
+    /// The matching rows of one file, in order, or an error if it can't be
+    /// read. Files of an unsupported type have none.
+    ///
+    /// The whole file is searched before this returns, with every matching
+    /// row held in memory, so a file with millions of matches needs room for
+    /// them all; `-m`/`--max-total` in the options bound how many are kept.
+    pub fn search_file(&self, path: impl AsRef<Path>) -> Result<std::vec::IntoIter<MatchRow>> {
+        let ctx = SearchContext {
+            column_pattern: self.column_pattern.clone(),
+            column_matches: self.column_matches.clone(),
+            column_sets: self.column_sets.clone(),
+            exclude_pattern: self.exclude_pattern.clone(),
+            collected: Some(RefCell::default()),
+            ..SearchContext::new(&self.pattern, &self.cli, &self.printer)
+        };
+        grep::search_file(path.as_ref(), &ctx)?;
+        Ok(ctx.collected.unwrap_or_default().into_inner().into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn searcher(args: &[&str]) -> Searcher {
+        let cli = Cli::parse_from(["table-grep"].iter().chain(args).chain(&["."]));
+        Searcher::new(cli).unwrap()
+    }
+
+    #[test]
+    fn test_searcher_collects_rows() {
+        let rows: Vec<MatchRow> = searcher(&["-r", "A.", "Ali"])
+            .search_file("test_data/test_file_1.csv")
+            .unwrap()
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].file, "test_data/test_file_1.csv");
+        assert_eq!(rows[0].row_num, 3);
+        assert_eq!(&*rows[0].headers, ["name", "age", "position"]);
+        assert_eq!(rows[0].cells, ["A.ce", "30", "Engineer"]);
+
+        let rows = searcher(&["-m", "1", "Ali"])
+            .search_file("test_data/test_file_1.csv")
+            .unwrap();
+        assert_eq!(rows.count(), 1);
+
+        let cli = Cli::parse_from(["table-grep", "-c", "Ali", "."]);
+        assert!(Searcher::new(cli).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Writing matching rows to a file (`--output-file`), in CSV or Parquet format,
+//! or to stdout for piping into another tool.
+//!
+//! Matches from every searched file are appended to one writer, so all files
+//! must share the same columns (and, for Parquet, the same types).
+
+use crate::cli::OutputFileFormat;
+use crate::grep::{ValueFormat, array_value_to_string};
+use anyhow::Result;
+use arrow::array::{ArrayRef, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The output file, or stdout.
+type Output = Box<dyn Write + Send>;
+
+pub struct OutputSink {
+    path: String,
+    target: Target,
+    /// Rendering of typed values written to CSV.
+    value_format: ValueFormat,
+}
+
+enum Target {
+    Csv {
+        writer: csv::Writer<Output>,
+        headers: Option<Vec<String>>,
+    },
+    Parquet {
+        /// Held until the first batch fixes the schema and creates the writer.
+        file: Option<Output>,
+        writer: Option<(ArrowWriter<Output>, SchemaRef)>,
+    },
+}
+
+impl OutputSink {
+    /// Create the output file (stdout for `-`), in `format` or else the one
+    /// its extension implies.
+    pub fn create(
+        path: &str,
+        format: Option<OutputFileFormat>,
+        value_format: ValueFormat,
+    ) -> Result<Self> {
+        let to_stdout = path == "-";
+        let format = match format.or_else(|| OutputFileFormat::from_path(Path::new(path))) {
+            Some(format) => format,
+            None if to_stdout => OutputFileFormat::Csv,
+            None => anyhow::bail!(
+                "Cannot infer the output format of '{}': use a .csv, .tsv, .psv or .parquet extension, or --output-format",
+                path
+            ),
+        };
+        let delimiter = match format {
+            OutputFileFormat::Csv => Some(b','),
+            OutputFileFormat::Tsv => Some(b'\t'),
+            OutputFileFormat::Psv => Some(b'|'),
+            OutputFileFormat::Parquet => None,
+        };
+
+        let file: Output =
+            if to_stdout {
+                if delimiter.is_none() && atty::is(atty::Stream::Stdout) {
+                    anyhow::bail!("Not writing Parquet to a terminal: redirect stdout or pipe it");
+                }
+                Box::new(std::io::stdout())
+            } else {
+                Box::new(File::create(path).map_err(|e| {
+                    anyhow::anyhow!("Failed to create output file '{}': {}", path, e)
+                })?)
+            };
+
+        let target = match delimiter {
+            Some(delimiter) => Target::Csv {
+                writer: csv::WriterBuilder::new()
+                    .delimiter(delimiter)
+                    .flexible(true)
+                    .from_writer(file),
+                headers: None,
+            },
+            None => Target::Parquet {
+                file: Some(file),
+                writer: None,
+            },
+        };
+
+        Ok(Self {
+            path: if to_stdout { "<stdout>" } else { path }.to_string(),
+            target,
+            value_format,
+        })
+    }
+
+    /// Append rows from a text format, where every cell is a string.
+    pub fn write_rows(
+        &mut self,
+        source: &str,
+        headers: &[String],
+        rows: &[Vec<String>],
+    ) -> Result<()> {
+        match &mut self.target {
+            Target::Csv {
+                writer,
+                headers: written,
+            } => {
+                check_csv_headers(written, writer, headers, source, &self.path)?;
+                for row in rows {
+                    writer.write_record(row)?;
+                }
+                Ok(())
+            }
+            Target::Parquet { .. } => {
+                let batch = string_batch(headers, rows)?;
+                self.write_batch(source, &batch)
+            }
+        }
+    }
+
+    /// Append a batch of rows, keeping its arrow types when writing Parquet.
+    pub fn write_batch(&mut self, source: &str, batch: &RecordBatch) -> Result<()> {
+        match &mut self.target {
+            Target::Csv { writer, headers } => {
+                let batch_headers: Vec<String> = batch
+                    .schema()
+                    .fields()
+                    .iter()
+                    .map(|f| f.name().clone())
+                    .collect();
+                check_csv_headers(headers, writer, &batch_headers, source, &self.path)?;
+                for row_idx in 0..batch.num_rows() {
+                    let row: Vec<String> = batch
+                        .columns()
+                        .iter()
+                        .map(|col| array_value_to_string(col.as_ref(), row_idx, &self.value_format))
+                        .collect();
+                    writer.write_record(&row)?;
+                }
+                Ok(())
+            }
+            Target::Parquet { file, writer } => {
+                let (writer, schema) = match writer {
+                    Some(existing) => existing,
+                    None => {
+                        let file = file.take().expect("file is held until the writer exists");
+                        let schema = batch.schema();
+                        writer.insert((ArrowWriter::try_new(file, schema.clone(), None)?, schema))
+                    }
+                };
+                if schema.fields() != batch.schema().fields() {
+                    anyhow::bail!(
+                        "Cannot append matches from '{}' to '{}': its schema differs from earlier files",
+                        source,
+                        self.path
+                    );
+                }
+                writer.write(batch)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Flush and finalize the output file.
+    pub fn close(self) -> Result<()> {
+        match self.target {
+            Target::Csv { mut writer, .. } => writer.flush()?,
+            Target::Parquet { file, writer } => {
+                let writer = match writer {
+                    Some((writer, _)) => writer,
+                    // No matches: still write a valid (empty) Parquet file.
+                    None => ArrowWriter::try_new(
+                        file.expect("file is held until the writer exists"),
+                        Arc::new(Schema::empty()),
+                        None,
+                    )?,
+                };
+                writer.close()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the CSV header row on first use, and reject later files whose
+/// columns differ from it.
+fn check_csv_headers(
+    written: &mut Option<Vec<String>>,
+    writer: &mut csv::Writer<Output>,
+    headers: &[String],
+    source: &str,
+    path: &str,
+) -> Result<()> {
+    match written {
+        Some(existing) if existing.as_slice() != headers => anyhow::bail!(
+            "Cannot append matches from '{}' to '{}': its columns differ from earlier files",
+            source,
+            path
+        ),
+        Some(_) => Ok(()),
+        None => {
+            writer.write_record(headers)?;
+            *written = Some(headers.to_vec());
+            Ok(())
+        }
+    }
+}
+
+/// Build an all-Utf8 batch from string rows. Short rows are padded with nulls
+/// and extra cells beyond the header are dropped.
+fn string_batch(headers: &[String], rows: &[Vec<String>]) -> Result<RecordBatch> {
+    let schema: SchemaRef = Arc::new(Schema::new(
+        headers
+            .iter()
+            .map(|h| Field::new(h, DataType::Utf8, true))
+            .collect::<Vec<_>>(),
+    ));
+    let columns: Vec<ArrayRef> = (0..headers.len())
+        .map(|idx| {
+            let values: StringArray = rows.iter().map(|row| row.get(idx)).collect();
+            Arc::new(values) as ArrayRef
+        })
+        .collect();
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+
+    #[test]
+    fn test_string_batch_pads_ragged_rows() {
+        let headers = vec!["a".to_string(), "b".to_string()];
+        let rows = vec![
+            vec!["1".to_string()],
+            vec!["2".to_string(), "x".to_string(), "extra".to_string()],
+        ];
+        let batch = string_batch(&headers, &rows).unwrap();
+
+        assert_eq!(batch.num_columns(), 2);
+        assert_eq!(batch.num_rows(), 2);
+        let b = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!(b.is_null(0));
+        assert_eq!(b.value(1), "x");
+    }
+
+    #[test]
+    fn test_output_format_from_path() {
+        let format = |p: &str| OutputFileFormat::from_path(Path::new(p));
+        assert_eq!(
+            format("out/matches.parquet"),
+            Some(OutputFileFormat::Parquet)
+        );
+        assert_eq!(format("matches.tab"), Some(OutputFileFormat::Tsv));
+        assert_eq!(format("matches.txt"), None);
+        assert_eq!(format("-"), None);
+
+        let err = OutputSink::create("matches.txt", None, ValueFormat::default())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("--output-format"));
+    }
+}
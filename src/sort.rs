@@ -0,0 +1,170 @@
+//! Ordering matched rows by column values (`--sort COLUMN[:desc]`).
+
+use crate::grep::find_column;
+use crate::output::{OutputRow, is_null_cell};
+use crate::report;
+use arrow::datatypes::DataType;
+use std::cmp::Ordering;
+
+/// One `--sort` key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Parse `COLUMN`, `COLUMN:asc` or `COLUMN:desc`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (column, descending) = match s.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            _ => (s, false),
+        };
+        if column.is_empty() {
+            return Err(format!("missing column name in '{}'", s));
+        }
+        Ok(Self {
+            column: column.to_string(),
+            descending,
+        })
+    }
+}
+
+/// Stable-sort `rows` by `keys`, later keys breaking ties. A column compares
+/// numerically when its arrow type is numeric or, without types, when every
+/// non-null value parses as a number; otherwise lexically. Null (empty or
+/// `null_text`) cells sort last in either direction. Keys naming a missing column are
+/// skipped with a warning.
+pub fn sort_rows(
+    rows: &mut [OutputRow],
+    headers: &[String],
+    column_types: Option<&[DataType]>,
+    keys: &[SortKey],
+    null_text: &str,
+    ignore_case: bool,
+    filename: &str,
+) {
+    let keys: Vec<(usize, bool, bool)> = keys
+        .iter()
+        .filter_map(|key| {
+            let Some(idx) = find_column(headers, &key.column, ignore_case) else {
+                report!(
+                    "Warning: --sort column '{}' not found in '{}'",
+                    key.column,
+                    filename
+                );
+                return None;
+            };
+            let numeric = match column_types.and_then(|types| types.get(idx)) {
+                Some(dt) => dt.is_numeric(),
+                None => rows
+                    .iter()
+                    .filter_map(|row| row.cells.get(idx))
+                    .filter(|cell| !is_null_cell(cell, null_text))
+                    .all(|cell| cell.trim().parse::<f64>().is_ok()),
+            };
+            Some((idx, numeric, key.descending))
+        })
+        .collect();
+
+    rows.sort_by(|a, b| {
+        keys.iter()
+            .map(|&(idx, numeric, descending)| {
+                compare_cells(
+                    non_null(a, idx, null_text),
+                    non_null(b, idx, null_text),
+                    numeric,
+                    descending,
+                )
+            })
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// A row's cell in column `idx`, or `None` if it's missing or null.
+fn non_null<'r>(row: &'r OutputRow, idx: usize, null_text: &str) -> Option<&'r str> {
+    row.cells
+        .get(idx)
+        .map(String::as_str)
+        .filter(|cell| !is_null_cell(cell, null_text))
+}
+
+/// Compare two cells, `None` being null.
+fn compare_cells(a: Option<&str>, b: Option<&str>, numeric: bool, descending: bool) -> Ordering {
+    let (a, b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        // Nulls last, whatever the direction
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) => return Ordering::Greater,
+        (Some(_), None) => return Ordering::Less,
+    };
+    let ord = if numeric {
+        match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.total_cmp(&y),
+            _ => a.cmp(b),
+        }
+    } else {
+        a.cmp(b)
+    };
+    if descending { ord.reverse() } else { ord }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(cells: &[[&str; 2]]) -> Vec<OutputRow> {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(i, row)| OutputRow {
+                row_num: i + 1,
+                cells: row.iter().map(|c| c.to_string()).collect(),
+                is_context: false,
+                nulls: None,
+            })
+            .collect()
+    }
+
+    fn sorted(cells: &[[&str; 2]], keys: &[&str], types: Option<&[DataType]>) -> Vec<usize> {
+        let headers = vec!["name".to_string(), "n".to_string()];
+        let keys: Vec<SortKey> = keys.iter().map(|k| SortKey::parse(k).unwrap()).collect();
+        let mut rows = rows(cells);
+        sort_rows(&mut rows, &headers, types, &keys, "NULL", false, "test.csv");
+        rows.iter().map(|r| r.row_num).collect()
+    }
+
+    #[test]
+    fn test_parse_sort_key() {
+        assert_eq!(
+            SortKey::parse("ts:desc").unwrap(),
+            SortKey {
+                column: "ts".to_string(),
+                descending: true
+            }
+        );
+        assert!(!SortKey::parse("ts:asc").unwrap().descending);
+        // Only a trailing ':asc'/':desc' is a direction
+        assert_eq!(SortKey::parse("a:b").unwrap().column, "a:b");
+        assert!(SortKey::parse(":desc").is_err());
+    }
+
+    #[test]
+    fn test_sort_rows() {
+        let cells = [["b", "10"], ["a", "9"], ["c", "NULL"], ["a", "10"]];
+
+        // Sniffed as numeric: 9 < 10, NULL last; equal keys keep file order
+        assert_eq!(sorted(&cells, &["n"], None), [2, 1, 4, 3]);
+        assert_eq!(sorted(&cells, &["n:desc"], None), [1, 4, 2, 3]);
+        assert_eq!(sorted(&cells, &["name", "n:desc"], None), [4, 2, 1, 3]);
+
+        // A typed string column compares lexically even if values look numeric
+        let types = [DataType::Utf8, DataType::Utf8];
+        assert_eq!(sorted(&cells, &["n"], Some(&types)), [1, 4, 2, 3]);
+
+        // Unknown columns leave the order alone
+        assert_eq!(sorted(&cells, &["missing"], None), [1, 2, 3, 4]);
+    }
+}
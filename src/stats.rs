@@ -0,0 +1,213 @@
+//! Parquet row-group pruning using column chunk min/max statistics, and
+//! counting matches from them alone.
+//!
+//! Pruning is only sound when a cell must be *exactly equal* to a literal:
+//! min/max bounds say nothing about substrings, so callers gate on that.
+
+use arrow::datatypes::DataType;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::statistics::Statistics;
+
+/// The value a matching cell must have, converted to the column's physical type.
+enum Target<'a> {
+    Bytes(&'a [u8]),
+    Int(i64),
+    /// No non-null cell of this column can render as the literal.
+    Never,
+}
+
+/// Return the row groups that may contain a cell in `column_name` equal to
+/// `literal`, in file order, given that null cells render as `null_text`.
+/// Returns `None` when statistics can't be used for this column (nested, or
+/// a type whose rendering we don't model).
+pub fn candidate_row_groups(
+    metadata: &ParquetMetaData,
+    column_name: &str,
+    data_type: &DataType,
+    literal: &str,
+    null_text: &str,
+) -> Option<Vec<usize>> {
+    let (leaf, target) = resolve(metadata, column_name, data_type, literal, null_text)?;
+    Some(
+        (0..metadata.num_row_groups())
+            .filter(
+                |&rg| match metadata.row_group(rg).column(leaf).statistics() {
+                    Some(stats) => may_contain(stats, &target),
+                    None => !matches!(target, Target::Never),
+                },
+            )
+            .collect(),
+    )
+}
+
+/// What statistics say about how many cells of a row group equal a literal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LiteralCount {
+    /// Exactly this many: every non-null cell does.
+    Exactly(usize),
+    /// Unknown, but no more than the row group's non-null cells.
+    AtMost(usize),
+}
+
+/// For every row group in file order, how many cells in `column_name` equal
+/// `literal`, as far as statistics can tell without reading any data. Like
+/// `candidate_row_groups`, `None` when statistics can't be used.
+pub fn literal_counts(
+    metadata: &ParquetMetaData,
+    column_name: &str,
+    data_type: &DataType,
+    literal: &str,
+    null_text: &str,
+) -> Option<Vec<LiteralCount>> {
+    let (leaf, target) = resolve(metadata, column_name, data_type, literal, null_text)?;
+    Some(
+        metadata
+            .row_groups()
+            .iter()
+            .map(|rg| {
+                let rows = rg.num_rows() as usize;
+                let Some(stats) = rg.column(leaf).statistics() else {
+                    return match target {
+                        Target::Never => LiteralCount::Exactly(0),
+                        _ => LiteralCount::AtMost(rows),
+                    };
+                };
+                let non_null = rows.saturating_sub(stats.null_count_opt().unwrap_or(0) as usize);
+                if !may_contain(stats, &target) {
+                    LiteralCount::Exactly(0)
+                } else if stats.null_count_opt().is_some() && only_contains(stats, &target) {
+                    LiteralCount::Exactly(non_null)
+                } else {
+                    LiteralCount::AtMost(non_null)
+                }
+            })
+            .collect(),
+    )
+}
+
+/// The leaf column statistics are kept for, and what its cells must equal.
+fn resolve<'a>(
+    metadata: &ParquetMetaData,
+    column_name: &str,
+    data_type: &DataType,
+    literal: &'a str,
+    null_text: &str,
+) -> Option<(usize, Target<'a>)> {
+    // Null cells render as `null_text`, which statistics know nothing about.
+    if literal == null_text {
+        return None;
+    }
+
+    let leaf = metadata
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .position(|c| c.path().parts() == [column_name])?;
+
+    let target = match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 => Target::Bytes(literal.as_bytes()),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            match literal.parse::<i64>() {
+                // "007" parses, but no integer cell is ever rendered that way
+                Ok(v) if v.to_string() == literal => Target::Int(v),
+                _ => Target::Never,
+            }
+        }
+        _ => return None,
+    };
+    Some((leaf, target))
+}
+
+/// Whether the bounds pin every non-null cell to the target: min and max
+/// are both exactly it (not truncated).
+fn only_contains(stats: &Statistics, target: &Target) -> bool {
+    if !(stats.min_is_exact() && stats.max_is_exact()) {
+        return false;
+    }
+    match (stats, target) {
+        (Statistics::ByteArray(s), Target::Bytes(b)) => {
+            s.min_opt().is_some_and(|min| min.data() == *b)
+                && s.max_opt().is_some_and(|max| max.data() == *b)
+        }
+        (Statistics::Int32(s), Target::Int(v)) => {
+            s.min_opt().is_some_and(|min| i64::from(*min) == *v)
+                && s.max_opt().is_some_and(|max| i64::from(*max) == *v)
+        }
+        (Statistics::Int64(s), Target::Int(v)) => s.min_opt() == Some(v) && s.max_opt() == Some(v),
+        _ => false,
+    }
+}
+
+fn may_contain(stats: &Statistics, target: &Target) -> bool {
+    match (stats, target) {
+        (_, Target::Never) => false,
+        (Statistics::ByteArray(s), Target::Bytes(b)) => match (s.min_opt(), s.max_opt()) {
+            (Some(min), Some(max)) => min.data() <= *b && *b <= max.data(),
+            _ => true,
+        },
+        (Statistics::Int32(s), Target::Int(v)) => match (s.min_opt(), s.max_opt()) {
+            (Some(min), Some(max)) => i64::from(*min) <= *v && *v <= i64::from(*max),
+            _ => true,
+        },
+        (Statistics::Int64(s), Target::Int(v)) => match (s.min_opt(), s.max_opt()) {
+            (Some(min), Some(max)) => min <= v && v <= max,
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+    use parquet::arrow::ArrowWriter;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc;
+
+    /// Three row groups: ids 1-2, 3-4, 5-6 and names a-b, c-d, e-f.
+    fn three_row_group_metadata() -> Arc<ParquetMetaData> {
+        let ids: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3, 4, 5, 6]));
+        let names: ArrayRef = Arc::new(StringArray::from(vec!["a", "b", "c", "d", "e", "f"]));
+        let batch = RecordBatch::try_from_iter([("id", ids), ("name", names)]).unwrap();
+
+        let mut buf = Vec::new();
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .build();
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(buf))
+            .unwrap()
+            .metadata()
+            .clone()
+    }
+
+    #[test]
+    fn test_candidate_row_groups() {
+        let md = three_row_group_metadata();
+
+        let groups = |col, dt, lit| candidate_row_groups(&md, col, dt, lit, "NULL");
+
+        assert_eq!(groups("id", &DataType::Int64, "3"), Some(vec![1]));
+        assert_eq!(groups("id", &DataType::Int64, "42"), Some(vec![]));
+        // Not a canonical integer rendering: nothing can match
+        assert_eq!(groups("id", &DataType::Int64, "03"), Some(vec![]));
+        assert_eq!(groups("name", &DataType::Utf8, "f"), Some(vec![2]));
+        // Within the min/max range of the first group, even though absent
+        assert_eq!(groups("name", &DataType::Utf8, "ab"), Some(vec![0]));
+
+        // Unsupported situations fall back to a full scan
+        assert_eq!(groups("name", &DataType::Utf8, "NULL"), None);
+        assert_eq!(
+            candidate_row_groups(&md, "name", &DataType::Utf8, "N/A", "N/A"),
+            None
+        );
+        assert_eq!(groups("missing", &DataType::Utf8, "a"), None);
+        assert_eq!(groups("id", &DataType::Float64, "1"), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Searching object stores (S3, GCS and Azure) by URL (the `object-store`
+//! feature). Credentials and regions come from the usual environment
+//! variables (`AWS_*`, `GOOGLE_*`, `AZURE_*`).
+
+/// URL schemes naming an object store.
+const SCHEMES: [&str; 8] = ["s3", "s3a", "gs", "az", "adl", "azure", "abfs", "abfss"];
+
+/// Whether `path` names an object or prefix in an object store.
+pub fn is_store_url(path: &str) -> bool {
+    path.split_once("://")
+        .is_some_and(|(scheme, _)| SCHEMES.iter().any(|s| s.eq_ignore_ascii_case(scheme)))
+}
+
+#[cfg(feature = "object-store")]
+pub use remote::*;
+
+#[cfg(feature = "object-store")]
+mod remote {
+    use crate::ranged::RangeSource;
+    use anyhow::Result;
+    use bytes::Bytes;
+    use futures_util::TryStreamExt;
+    use object_store::path::Path as ObjectPath;
+    use object_store::{ObjectStore, ObjectStoreExt, ObjectStoreScheme};
+    use std::path::Path;
+    use std::sync::{Arc, LazyLock};
+    use url::{Position, Url};
+
+    /// Drives object store requests; everything else is synchronous.
+    static RUNTIME: LazyLock<tokio::runtime::Runtime> = LazyLock::new(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start the I/O runtime")
+    });
+
+    /// One object in a store.
+    pub struct StoreObject {
+        store: Arc<dyn ObjectStore>,
+        location: ObjectPath,
+        size: u64,
+        url: String,
+    }
+
+    impl StoreObject {
+        pub fn url(&self) -> &str {
+            &self.url
+        }
+
+        /// The object's key, for telling its format and matching
+        /// `--include`/`--exclude`.
+        pub fn key(&self) -> &Path {
+            Path::new(self.location.as_ref())
+        }
+
+        /// The whole object, in memory.
+        pub fn download(&self) -> Result<Bytes> {
+            RUNTIME
+                .block_on(async { self.store.get(&self.location).await?.bytes().await })
+                .map_err(|e| anyhow::anyhow!("Failed to download '{}': {}", self.url, e))
+        }
+    }
+
+    impl RangeSource for StoreObject {
+        fn name(&self) -> &str {
+            &self.url
+        }
+
+        fn size(&self) -> u64 {
+            self.size
+        }
+
+        fn get_range(&self, start: u64, length: u64) -> Result<Bytes> {
+            RUNTIME
+                .block_on(self.store.get_range(&self.location, start..start + length))
+                .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", self.url, e))
+        }
+    }
+
+    /// A store client for `url`, configured from the environment, the
+    /// `scheme://bucket` part of the URL, and the path within the bucket.
+    fn connect(url: &str) -> Result<(Arc<dyn ObjectStore>, String, ObjectPath)> {
+        use object_store::aws::AmazonS3Builder;
+        use object_store::azure::MicrosoftAzureBuilder;
+        use object_store::gcp::GoogleCloudStorageBuilder;
+
+        let parsed =
+            Url::parse(url).map_err(|e| anyhow::anyhow!("Invalid URL '{}': {}", url, e))?;
+        let (scheme, path) = ObjectStoreScheme::parse(&parsed)
+            .map_err(|e| anyhow::anyhow!("Invalid object store URL '{}': {}", url, e))?;
+        let store: Arc<dyn ObjectStore> = match scheme {
+            ObjectStoreScheme::AmazonS3 => {
+                Arc::new(AmazonS3Builder::from_env().with_url(url).build()?)
+            }
+            ObjectStoreScheme::GoogleCloudStorage => Arc::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_url(url)
+                    .build()?,
+            ),
+            ObjectStoreScheme::MicrosoftAzure => {
+                Arc::new(MicrosoftAzureBuilder::from_env().with_url(url).build()?)
+            }
+            _ => anyhow::bail!("'{}' is not an S3, GCS or Azure URL", url),
+        };
+        Ok((store, parsed[..Position::BeforePath].to_string(), path))
+    }
+
+    /// The objects `url` names: just the one object, when it names one, or
+    /// else every object under it as a prefix, ordered by key. The bool says
+    /// which. Nothing there at all is an error.
+    pub fn resolve(url: &str) -> Result<(Vec<StoreObject>, bool)> {
+        let (store, base, path) = connect(url)?;
+        let object = |meta: object_store::ObjectMeta| StoreObject {
+            store: Arc::clone(&store),
+            url: format!("{}/{}", base, meta.location),
+            location: meta.location,
+            size: meta.size,
+        };
+
+        if !url.ends_with('/') {
+            match RUNTIME.block_on(store.head(&path)) {
+                Ok(meta) => return Ok((vec![object(meta)], true)),
+                Err(object_store::Error::NotFound { .. }) => {}
+                Err(e) => anyhow::bail!("Failed to read '{}': {}", url, e),
+            }
+        }
+
+        let prefix = (!path.as_ref().is_empty()).then_some(&path);
+        let mut objects: Vec<StoreObject> = RUNTIME
+            .block_on(store.list(prefix).try_collect::<Vec<_>>())
+            .map_err(|e| anyhow::anyhow!("Failed to list '{}': {}", url, e))?
+            .into_iter()
+            .map(object)
+            .collect();
+        if objects.is_empty() {
+            anyhow::bail!("'{}' is not a valid object or prefix", url);
+        }
+        objects.sort_by(|a, b| a.location.cmp(&b.location));
+        Ok((objects, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_store_url() {
+        assert!(is_store_url("s3://bucket/data/events.parquet"));
+        assert!(is_store_url("gs://bucket/prefix/"));
+        assert!(is_store_url(
+            "abfss://container@account.dfs.core.windows.net/lake"
+        ));
+        assert!(!is_store_url("https://example.com/data.csv"));
+        assert!(!is_store_url("data/s3.csv"));
+    }
+}